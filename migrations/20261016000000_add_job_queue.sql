@@ -0,0 +1,13 @@
+-- Persistent job queue consumed by dispatcher workers
+CREATE TABLE job_queue (
+    job_id TEXT PRIMARY KEY,
+    project_config TEXT NOT NULL,           -- JSON snapshot of ProjectConfig at enqueue time
+    webhook_data TEXT NOT NULL,             -- JSON WebhookData passed to the pipeline
+    priority INTEGER NOT NULL DEFAULT 0,    -- Higher runs first
+    enqueued_at TEXT NOT NULL,              -- RFC 3339
+    claimed_at TEXT,                        -- Set when a worker picks the job up
+
+    FOREIGN KEY (job_id) REFERENCES jobs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_job_queue_pending ON job_queue(claimed_at, priority DESC, enqueued_at);
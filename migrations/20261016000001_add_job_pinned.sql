@@ -0,0 +1,4 @@
+-- Pinned jobs are exempt from retention pruning
+ALTER TABLE jobs ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_jobs_pinned ON jobs(pinned);
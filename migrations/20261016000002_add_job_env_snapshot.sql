@@ -0,0 +1,2 @@
+-- Environment passed to the job's scripts (JSON object, secrets masked)
+ALTER TABLE jobs ADD COLUMN env_snapshot TEXT;
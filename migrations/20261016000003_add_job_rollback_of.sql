@@ -0,0 +1,2 @@
+-- Rollback jobs link to the successful job whose commit they restore
+ALTER TABLE jobs ADD COLUMN rollback_of TEXT;
@@ -0,0 +1,2 @@
+-- Queue entries held back (e.g. during a deploy freeze) until this time (RFC 3339)
+ALTER TABLE job_queue ADD COLUMN not_before TEXT;
@@ -0,0 +1,6 @@
+-- Projects in maintenance mode: their queued jobs are held until it is lifted
+CREATE TABLE project_maintenance (
+    project_name TEXT PRIMARY KEY,
+    enabled_at TEXT NOT NULL,               -- RFC 3339
+    reason TEXT
+);
@@ -0,0 +1,8 @@
+-- Persisted rate limit token buckets (with [server] persist_rate_limits)
+CREATE TABLE rate_limit_buckets (
+    key TEXT PRIMARY KEY,
+    tokens REAL NOT NULL,
+    capacity REAL NOT NULL,
+    refill_per_sec REAL NOT NULL,           -- Infinite for a zero-length window
+    updated_at TEXT NOT NULL                -- RFC 3339
+);
@@ -0,0 +1,2 @@
+-- JSON summary of the main-script output diff against the previous run
+ALTER TABLE jobs ADD COLUMN output_diff TEXT;
@@ -0,0 +1,2 @@
+-- Commits deployed by the job since the last successful deploy (git log --oneline)
+ALTER TABLE jobs ADD COLUMN changelog TEXT;
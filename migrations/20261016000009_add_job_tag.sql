@@ -0,0 +1,2 @@
+-- Release tag of jobs triggered by a tag push (their branch holds the tag name)
+ALTER TABLE jobs ADD COLUMN tag TEXT;
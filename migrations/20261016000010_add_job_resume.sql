@@ -0,0 +1,7 @@
+-- Pipeline inputs of the job (the queued config and webhook data), so a
+-- failed job can be resumed after its queue entry is gone
+ALTER TABLE jobs ADD COLUMN project_config TEXT;
+ALTER TABLE jobs ADD COLUMN webhook_data TEXT;
+
+-- Resumed jobs link to the failed job whose pipeline they continue
+ALTER TABLE jobs ADD COLUMN resumed_from TEXT;
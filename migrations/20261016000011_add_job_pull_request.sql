@@ -0,0 +1,2 @@
+-- Pull request validation jobs record the number of the pull request
+ALTER TABLE jobs ADD COLUMN pull_request INTEGER;
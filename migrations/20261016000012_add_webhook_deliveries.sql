@@ -0,0 +1,8 @@
+-- GitHub delivery IDs (X-GitHub-Delivery) of handled webhooks, so
+-- redeliveries of the same event don't start another job
+CREATE TABLE webhook_deliveries (
+    delivery_id TEXT PRIMARY KEY,
+    received_at TEXT NOT NULL               -- RFC 3339
+);
+
+CREATE INDEX idx_webhook_deliveries_received_at ON webhook_deliveries(received_at);
@@ -0,0 +1,2 @@
+-- Jobs whose webhook skipped the rate limit with the bypass token
+ALTER TABLE jobs ADD COLUMN rate_limit_bypassed BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,10 @@
+-- Webhook requests accepted but not yet fully processed. Records left after
+-- a crash are processed again on startup, so no accepted push is lost.
+CREATE TABLE webhook_intake (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    received_at TEXT NOT NULL,              -- RFC 3339
+    uri TEXT NOT NULL,                      -- Request path and query
+    headers TEXT NOT NULL,                  -- JSON list of [name, value] pairs
+    body BLOB NOT NULL,
+    client_ip TEXT                          -- Client address, if known
+);
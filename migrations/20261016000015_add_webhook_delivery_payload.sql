@@ -0,0 +1,6 @@
+-- Requests of handled deliveries, so they can be redelivered
+-- (NULL for deliveries recorded before this migration)
+ALTER TABLE webhook_deliveries ADD COLUMN uri TEXT;
+ALTER TABLE webhook_deliveries ADD COLUMN headers TEXT;    -- JSON list of [name, value] pairs
+ALTER TABLE webhook_deliveries ADD COLUMN body BLOB;
+ALTER TABLE webhook_deliveries ADD COLUMN client_ip TEXT;
@@ -0,0 +1,3 @@
+-- Raw webhook body of the delivery that created the job, capped in size
+ALTER TABLE jobs ADD COLUMN payload BLOB;
+ALTER TABLE jobs ADD COLUMN payload_truncated BOOLEAN NOT NULL DEFAULT 0;
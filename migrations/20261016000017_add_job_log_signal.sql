@@ -0,0 +1,2 @@
+-- Signal that ended a stopped step, e.g. SIGTERM or SIGKILL
+ALTER TABLE job_logs ADD COLUMN signal TEXT;
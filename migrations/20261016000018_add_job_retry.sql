@@ -0,0 +1,2 @@
+-- Retried jobs link to the job whose pipeline they run again
+ALTER TABLE jobs ADD COLUMN retried_from TEXT;
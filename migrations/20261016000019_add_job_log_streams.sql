@@ -0,0 +1,3 @@
+-- What a step's script wrote to stdout and stderr, besides the combined output
+ALTER TABLE job_logs ADD COLUMN stdout TEXT;
+ALTER TABLE job_logs ADD COLUMN stderr TEXT;
@@ -0,0 +1,2 @@
+-- Whether a step's output was cut to stay within max_step_output_bytes
+ALTER TABLE job_logs ADD COLUMN output_truncated BOOLEAN NOT NULL DEFAULT 0;
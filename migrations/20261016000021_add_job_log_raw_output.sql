@@ -0,0 +1,3 @@
+-- A step's output as written, kept when ANSI escape sequences are stripped
+-- from the stored output (keep_ansi_in_download)
+ALTER TABLE job_logs ADD COLUMN raw_output TEXT;
@@ -0,0 +1,12 @@
+-- Files collected from a job's script directory (`artifacts`), stored under
+-- `<[server] artifacts_dir>/<job_id>/<name>`
+CREATE TABLE artifacts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id TEXT NOT NULL,
+    name TEXT NOT NULL,                     -- Path relative to the script directory
+    size INTEGER NOT NULL,                  -- Bytes
+    created_at TEXT NOT NULL,               -- RFC 3339
+
+    FOREIGN KEY (job_id) REFERENCES jobs(id) ON DELETE CASCADE,
+    UNIQUE (job_id, name)
+);
@@ -0,0 +1,4 @@
+-- Jobs queued for a persisted webhook request, so a restart doesn't process
+-- a request that already queued its jobs again
+ALTER TABLE jobs ADD COLUMN intake_id INTEGER;           -- webhook_intake record the job was created for
+ALTER TABLE webhook_intake ADD COLUMN job_ids TEXT;      -- Comma-separated IDs of the jobs queued for it
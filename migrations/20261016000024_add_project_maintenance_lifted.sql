@@ -0,0 +1,6 @@
+-- When each project's maintenance mode was last lifted, so failures before
+-- that don't count towards `max_consecutive_failures` again
+CREATE TABLE project_maintenance_lifted (
+    project_name TEXT PRIMARY KEY,
+    lifted_at TEXT NOT NULL                 -- RFC 3339
+);
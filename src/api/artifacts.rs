@@ -0,0 +1,109 @@
+//! Job artifact endpoints
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, State as AxumState},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::SharedState;
+use crate::artifacts;
+use crate::error::CicdError;
+
+/// Bytes read from disk per chunk when streaming an artifact
+const ARTIFACT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// GET /api/jobs/{id}/artifacts - List the files collected from a job
+pub async fn get_job_artifacts(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.job_store.job_exists(&id).await {
+        Ok(true) => {}
+        Ok(false) => return not_found("Job not found"),
+        Err(e) => return internal_error(e),
+    }
+    match state.job_store.get_artifacts(&id).await {
+        Ok(artifacts) => Json(json!({ "artifacts": artifacts })).into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// GET /api/jobs/{id}/artifacts/{name} - Download a file collected from a
+/// job; `name` is its path relative to the script directory
+pub async fn download_job_artifact(
+    AxumState(state): AxumState<SharedState>,
+    Path((id, name)): Path<(String, String)>,
+) -> Response {
+    // Only recorded names are served, so `name` can't point outside the
+    // job's artifact directory
+    let artifact = match state.job_store.get_artifact(&id, &name).await {
+        Ok(Some(artifact)) => artifact,
+        Ok(None) => return not_found("Artifact not found"),
+        Err(e) => return internal_error(e),
+    };
+    let artifacts_dir = state.config.load().server.artifacts_dir().to_string();
+    let path = artifacts::job_dir(&artifacts_dir, &id).join(&artifact.name);
+    let mut file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return not_found("Artifact file is missing");
+        }
+        Err(e) => return internal_error(e.into()),
+    };
+
+    // Small buffer: the reader only stays a few chunks ahead of the client
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, std::io::Error>>(4);
+    tokio::spawn(async move {
+        loop {
+            let mut chunk = vec![0; ARTIFACT_CHUNK_SIZE];
+            match file.read(&mut chunk).await {
+                Ok(0) => return,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        return; // client went away
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            }
+        }
+    });
+
+    let file_name = artifact.name.rsplit('/').next().unwrap_or(&artifact.name);
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (header::CONTENT_LENGTH, artifact.size.to_string()),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response();
+    if let Ok(value) = format!("attachment; filename=\"{}\"", file_name.replace('"', "")).parse() {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+fn not_found(message: &str) -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({"error": message}))).into_response()
+}
+
+fn internal_error(e: CicdError) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": e.to_string()})),
+    )
+        .into_response()
+}
@@ -0,0 +1,107 @@
+//! Project cache endpoints: list and purge the stored caches
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::SharedState;
+use crate::cache;
+use crate::error::Result;
+
+/// GET /api/projects/{name}/caches - List a project's stored caches
+pub async fn get_project_caches(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+) -> Response {
+    let Some(dir) = cache_dir(&state, &name) else {
+        return error_response(StatusCode::NOT_FOUND, "Project not found".to_string());
+    };
+    match run_blocking(move || cache::list(&dir)).await {
+        Ok(caches) => Json(json!({ "project": name, "caches": caches })).into_response(),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// DELETE /api/projects/{name}/caches - Purge all of a project's caches;
+/// the next job starts without them
+pub async fn purge_project_caches(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+) -> Response {
+    let Some(dir) = cache_dir(&state, &name) else {
+        return error_response(StatusCode::NOT_FOUND, "Project not found".to_string());
+    };
+    let purge = move || {
+        let mut purged = Vec::new();
+        for stored in cache::list(&dir)? {
+            if cache::purge(&dir, &stored.name)? {
+                purged.push(stored.name);
+            }
+        }
+        Ok(purged)
+    };
+    match run_blocking(purge).await {
+        Ok(purged) => {
+            info!("Purged {} cache(s) of project '{}'", purged.len(), name);
+            Json(json!({ "project": name, "purged": purged })).into_response()
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// DELETE /api/projects/{name}/caches/{cache} - Purge one of a project's
+/// caches
+pub async fn purge_project_cache(
+    AxumState(state): AxumState<SharedState>,
+    Path((name, cache_name)): Path<(String, String)>,
+) -> Response {
+    let Some(dir) = cache_dir(&state, &name) else {
+        return error_response(StatusCode::NOT_FOUND, "Project not found".to_string());
+    };
+    if cache_name.starts_with('.') || cache_name.contains(['/', '\\']) {
+        return error_response(StatusCode::NOT_FOUND, "Cache not found".to_string());
+    }
+    let purge = {
+        let cache_name = cache_name.clone();
+        move || cache::purge(&dir, &cache_name)
+    };
+    match run_blocking(purge).await {
+        Ok(true) => {
+            info!("Purged cache '{}' of project '{}'", cache_name, name);
+            Json(json!({ "project": name, "purged": [cache_name] })).into_response()
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Cache not found".to_string()),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Where the caches of project `name` are stored, if it is configured
+fn cache_dir(state: &SharedState, name: &str) -> Option<PathBuf> {
+    let config = state.config.load();
+    config
+        .project
+        .iter()
+        .any(|p| p.name == name)
+        .then(|| cache::project_dir(config.server.cache_dir(), name))
+}
+
+/// Run a cache operation, which walks directories, off the async runtime
+async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
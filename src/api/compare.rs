@@ -0,0 +1,220 @@
+//! Job comparison endpoint
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use similar::TextDiff;
+
+use crate::SharedState;
+use crate::db::store::JobLog;
+use crate::job::{Job, JobStatus};
+
+/// Lines of unchanged context around each hunk
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// Give up looking for a minimal diff after this long and return a coarser one
+const DIFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Summary of one side of a comparison
+#[derive(Debug, Serialize)]
+pub struct JobSummary {
+    pub id: String,
+    pub status: JobStatus,
+    pub commit_sha: Option<String>,
+    pub duration_ms: Option<i64>,
+}
+
+/// One side of a compared step
+#[derive(Debug, Serialize)]
+pub struct StepSummary {
+    pub status: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+}
+
+/// A step present in at least one of the compared jobs
+#[derive(Debug, Serialize)]
+pub struct StepComparison {
+    pub log_type: String,
+    /// The step in `{id}`, if it ran there
+    pub job: Option<StepSummary>,
+    /// The step in `{other}`, if it ran there
+    pub other: Option<StepSummary>,
+    /// `other` duration minus `job` duration
+    pub duration_delta_ms: Option<i64>,
+    pub exit_code_changed: bool,
+}
+
+/// Response for GET /api/jobs/{id}/compare/{other}
+#[derive(Debug, Serialize)]
+pub struct JobComparison {
+    pub job: JobSummary,
+    pub other: JobSummary,
+    /// `other` duration minus `job` duration
+    pub duration_delta_ms: Option<i64>,
+    pub steps: Vec<StepComparison>,
+    /// Unified diff from `{id}`'s output to `{other}`'s output (empty if identical)
+    pub output_diff: String,
+}
+
+/// GET /api/jobs/{id}/compare/{other} - Step-by-step comparison of two jobs
+pub async fn compare_jobs(
+    AxumState(state): AxumState<SharedState>,
+    Path((id, other_id)): Path<(String, String)>,
+) -> Response {
+    let (job, job_logs) = match load_job(&state, &id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let (other, other_logs) = match load_job(&state, &other_id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+
+    let steps = compare_steps(&job_logs, &other_logs);
+    let duration_delta_ms = delta(job_duration_ms(&job), job_duration_ms(&other));
+
+    let old_label = format!("job/{}", job.id);
+    let new_label = format!("job/{}", other.id);
+    let old_output = job.output.clone().unwrap_or_default();
+    let new_output = other.output.clone().unwrap_or_default();
+    // Diffing large outputs is CPU-bound; keep it off the async workers
+    let output_diff = tokio::task::spawn_blocking(move || {
+        unified_diff(&old_output, &new_output, &old_label, &new_label)
+    })
+    .await
+    .unwrap_or_default();
+
+    Json(JobComparison {
+        job: summarize(&job),
+        other: summarize(&other),
+        duration_delta_ms,
+        steps,
+        output_diff,
+    })
+    .into_response()
+}
+
+/// Unified line diff between two outputs; empty when they are identical
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+    TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new)
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+async fn load_job(state: &SharedState, id: &str) -> Result<(Job, Vec<JobLog>), Response> {
+    let job = match state.job_store.get_job(id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": format!("Job not found: {}", id)})),
+            )
+                .into_response());
+        }
+        Err(e) => return Err(internal_error(e.to_string())),
+    };
+    let logs = state
+        .job_store
+        .get_job_logs(id)
+        .await
+        .map_err(|e| internal_error(e.to_string()))?;
+    Ok((job, logs))
+}
+
+fn internal_error(message: String) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": message})),
+    )
+        .into_response()
+}
+
+/// Pair steps by type and occurrence (e.g. the second `main_script` with the
+/// second `main_script`), in the order they ran in `{id}`; steps that only ran
+/// in `{other}` are appended.
+fn compare_steps(job_logs: &[JobLog], other_logs: &[JobLog]) -> Vec<StepComparison> {
+    let other_keyed = keyed_steps(other_logs);
+    let other_order: Vec<_> = other_keyed.iter().map(|(key, _)| key.clone()).collect();
+    let mut other_steps: HashMap<_, _> = other_keyed.into_iter().collect();
+
+    let mut steps: Vec<StepComparison> = keyed_steps(job_logs)
+        .into_iter()
+        .map(|(key, log)| {
+            let other = other_steps.remove(&key);
+            compare_step(key.0, Some(log), other)
+        })
+        .collect();
+    for key in other_order {
+        if let Some(log) = other_steps.remove(&key) {
+            steps.push(compare_step(key.0, None, Some(log)));
+        }
+    }
+    steps
+}
+
+/// Key each step by its type and how many times that type has run so far
+fn keyed_steps(logs: &[JobLog]) -> Vec<((String, usize), &JobLog)> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    logs.iter()
+        .map(|log| {
+            let n = seen.entry(&log.log_type).or_default();
+            *n += 1;
+            ((log.log_type.clone(), *n), log)
+        })
+        .collect()
+}
+
+fn compare_step(log_type: String, job: Option<&JobLog>, other: Option<&JobLog>) -> StepComparison {
+    StepComparison {
+        log_type,
+        duration_delta_ms: delta(
+            job.and_then(|l| l.duration_ms),
+            other.and_then(|l| l.duration_ms),
+        ),
+        exit_code_changed: job.map(|l| l.exit_code) != other.map(|l| l.exit_code),
+        job: job.map(step_summary),
+        other: other.map(step_summary),
+    }
+}
+
+fn step_summary(log: &JobLog) -> StepSummary {
+    StepSummary {
+        status: log.status.clone(),
+        exit_code: log.exit_code,
+        duration_ms: log.duration_ms,
+    }
+}
+
+fn summarize(job: &Job) -> JobSummary {
+    JobSummary {
+        id: job.id.clone(),
+        status: job.status.clone(),
+        commit_sha: job.commit_sha.clone(),
+        duration_ms: job_duration_ms(job),
+    }
+}
+
+fn job_duration_ms(job: &Job) -> Option<i64> {
+    job.completed_at
+        .map(|done| (done - job.started_at).num_milliseconds())
+}
+
+fn delta(before: Option<i64>, after: Option<i64>) -> Option<i64> {
+    Some(after? - before?)
+}
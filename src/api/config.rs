@@ -3,8 +3,9 @@
 use axum::{Json, extract::State as AxumState, http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 use serde_json::json;
+use std::sync::Arc;
 use tokio::fs;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{SharedState, reload_config};
 
@@ -17,6 +18,25 @@ pub struct ConfigResponse {
 
 /// GET /api/config/current - Get current configuration file content
 pub async fn get_config(AxumState(state): AxumState<SharedState>) -> impl IntoResponse {
+    if state.config_from_env {
+        // No file to show; render the effective config instead
+        let config = state.config.load();
+        return match toml::to_string_pretty(config.as_ref()) {
+            Ok(content) => Json(ConfigResponse {
+                config_toml: content,
+                path: "(environment)".to_string(),
+            })
+            .into_response(),
+            Err(e) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": format!("Failed to render config: {}", e)
+                })),
+            )
+                .into_response(),
+        };
+    }
+
     let path = &state.config_path;
 
     match fs::read_to_string(path).await {
@@ -36,22 +56,49 @@ pub async fn get_config(AxumState(state): AxumState<SharedState>) -> impl IntoRe
 }
 
 /// POST /api/reload - Reload configuration from disk
-/// Waits for current job to finish before applying the new config
+/// The new config applies to jobs triggered from now on; jobs that are already
+/// queued or running keep the project config they were created with.
 pub async fn reload_config_endpoint(AxumState(state): AxumState<SharedState>) -> impl IntoResponse {
-    // Wait for current job to finish before reloading
-    let _guard = state.job_execution_lock.lock().await;
+    if state.config_from_env {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "status": "error",
+                "message": "Configuration comes from CICD_* environment variables; restart the server to change it"
+            })),
+        )
+            .into_response();
+    }
 
     match reload_config(&state.config_path).await {
         Ok(new_config) => {
-            let mut config = state.config.write().unwrap();
-            *config = new_config;
+            if new_config.server.base_path() != state.base_path {
+                warn!(
+                    "base_path changed to {:?}; restart the server to apply it",
+                    new_config.server.base_path()
+                );
+            }
+            state.config.store(Arc::new(new_config));
             info!(
                 "Configuration reloaded successfully from {:?}",
                 state.config_path
             );
+
+            let running = state.job_store.get_running_count().await.unwrap_or(0);
+            let queued = state.job_store.get_queued_count().await.unwrap_or(0);
+            let message = if running + queued > 0 {
+                format!(
+                    "Configuration reloaded successfully. {} running and {} queued job(s) will finish with the previous config",
+                    running, queued
+                )
+            } else {
+                "Configuration reloaded successfully".to_string()
+            };
             Json(json!({
                 "status": "success",
-                "message": "Configuration reloaded successfully"
+                "message": message,
+                "running_jobs": running,
+                "queued_jobs": queued
             }))
             .into_response()
         }
@@ -0,0 +1,55 @@
+//! Redeliver endpoint: handle a recorded webhook delivery again
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use tracing::info;
+
+use crate::SharedState;
+use crate::api::webhook::{handle_durably, headers_from_json};
+use crate::ip_filter::ClientIp;
+
+/// POST /api/deliveries/{id}/redeliver - Handle a webhook delivery again
+/// with the request GitHub sent (`X-GitHub-Delivery` ID), e.g. to rerun a
+/// deploy that failed for environmental reasons. The request goes through
+/// the same checks as a new one, signature and rate limit included, and
+/// the response is the one the webhook would get.
+pub async fn redeliver(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let delivery = match state.job_store.get_delivery(&id).await {
+        Ok(Some(delivery)) => delivery,
+        Ok(None) => {
+            return error_response(
+                StatusCode::NOT_FOUND,
+                format!("Delivery {} not found (or older than 7 days)", id),
+            );
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let (Some(uri), Some(headers), Some(body)) = (delivery.uri, delivery.headers, delivery.body)
+    else {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!("Delivery {} was recorded without its request", id),
+        );
+    };
+
+    let uri: Uri = uri.parse().unwrap_or_else(|_| Uri::from_static("/"));
+    let headers = headers_from_json(&headers);
+    let client_ip = ClientIp(delivery.client_ip.and_then(|ip| ip.parse().ok()));
+    info!(
+        "Redelivering webhook delivery {} received at {}",
+        id, delivery.received_at
+    );
+    handle_durably(&state, &uri, &headers, &body, client_ip).await
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
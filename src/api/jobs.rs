@@ -2,18 +2,28 @@
 
 use axum::{
     Json,
+    body::Body,
     extract::{Path, Query, State as AxumState},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{HeaderName, StatusCode, header},
+    response::{IntoResponse, Response},
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::info;
 
 use crate::SharedState;
+use crate::api::stream::JobEvent;
+use crate::error::CicdError;
 use crate::job::{Job, JobStatus};
 
+/// Bytes read from the database per chunk when streaming output
+const OUTPUT_CHUNK_SIZE: i64 = 64 * 1024;
+
 /// Query parameters for job listing
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct JobsQuery {
     /// Filter by project name
     pub project: Option<String>,
@@ -23,6 +33,8 @@ pub struct JobsQuery {
     pub status: Option<String>,
     /// Filter by dry_run (true/false)
     pub dry_run: Option<bool>,
+    /// Filter by pinned (true/false)
+    pub pinned: Option<bool>,
     /// Number of items per page (default: 50, max: 100)
     pub limit: Option<i64>,
     /// Offset for pagination (default: 0)
@@ -30,7 +42,7 @@ pub struct JobsQuery {
 }
 
 /// Response for paginated job listing
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct JobsResponse {
     pub jobs: Vec<Job>,
     pub total: i64,
@@ -65,15 +77,20 @@ pub async fn get_jobs(
             "running" => JobStatus::Running,
             "success" => JobStatus::Success,
             "failed" => JobStatus::Failed,
+            "skipped" => JobStatus::Skipped,
+            "cancelled" => JobStatus::Cancelled,
+            "timed_out" => JobStatus::TimedOut,
             _ => {
                 return (
                     StatusCode::BAD_REQUEST,
-                    Json(json!({"error": "Invalid status. Use: queued, running, success, failed"})),
+                    Json(json!({"error": "Invalid status. Use: queued, running, success, failed, skipped, cancelled, timed_out"})),
                 )
                     .into_response();
             }
         };
         state.job_store.get_jobs_by_status(status, limit).await
+    } else if params.pinned == Some(true) {
+        state.job_store.get_pinned_jobs(limit).await
     } else {
         state.job_store.get_recent_jobs(limit).await
     };
@@ -88,6 +105,13 @@ pub async fn get_jobs(
             } else {
                 jobs
             };
+            let jobs: Vec<Job> = if let Some(pinned_filter) = params.pinned {
+                jobs.into_iter()
+                    .filter(|j| j.pinned == pinned_filter)
+                    .collect()
+            } else {
+                jobs
+            };
 
             let total = jobs.len() as i64;
             Json(JobsResponse {
@@ -161,3 +185,275 @@ pub async fn get_job_logs(
             .into_response(),
     }
 }
+
+/// POST /api/jobs/{id}/pin - Exempt a job from retention pruning
+pub async fn pin_job(AxumState(state): AxumState<SharedState>, Path(id): Path<String>) -> Response {
+    set_pinned(&state, &id, true).await
+}
+
+/// DELETE /api/jobs/{id}/pin - Make a job subject to retention pruning again
+pub async fn unpin_job(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    set_pinned(&state, &id, false).await
+}
+
+async fn set_pinned(state: &SharedState, id: &str, pinned: bool) -> Response {
+    match state.job_store.set_job_pinned(id, pinned).await {
+        Ok(true) => {}
+        Ok(false) => return job_not_found(),
+        Err(e) => return internal_error(e),
+    }
+    match state.job_store.get_job(id).await {
+        Ok(Some(job)) => Json(job).into_response(),
+        Ok(None) => job_not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Response to a cancellation request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelResponse {
+    pub job_id: String,
+    /// `cancelled` for a job that hadn't started, `cancelling` for a running
+    /// job that is being stopped (its status changes once it has)
+    pub status: String,
+}
+
+/// POST /api/jobs/{id}/cancel - Cancel a queued job, or stop a running one.
+/// A running job's script is killed along with the processes it started,
+/// and no further steps or hooks run.
+pub async fn cancel_job(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let job = match state.job_store.get_job(&id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return job_not_found(),
+        Err(e) => return internal_error(e),
+    };
+
+    if job.status == JobStatus::Queued {
+        match state
+            .job_store
+            .cancel_queued_job(&id, "Cancelled through the API")
+            .await
+        {
+            Ok(true) => {
+                info!("Job {} cancelled before it started", id);
+                state.job_events.send(JobEvent {
+                    event_type: "cancelled".to_string(),
+                    job_id: id.clone(),
+                    project_name: job.project_name,
+                    branch: job.branch,
+                    timestamp: Utc::now().to_rfc3339(),
+                });
+                return Json(CancelResponse {
+                    job_id: id,
+                    status: "cancelled".to_string(),
+                })
+                .into_response();
+            }
+            // Started in the meantime
+            Ok(false) => {}
+            Err(e) => return internal_error(e),
+        }
+    }
+
+    if state.dispatcher.cancel(&id) {
+        info!("Cancelling running job {}", id);
+        return (
+            StatusCode::ACCEPTED,
+            Json(CancelResponse {
+                job_id: id,
+                status: "cancelling".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    (
+        StatusCode::CONFLICT,
+        Json(json!({"error": format!("Job {} is not queued or running", id)})),
+    )
+        .into_response()
+}
+
+/// Where a streamed piece of output is read from
+enum OutputSource {
+    Job(String),
+    Step(i64),
+    /// A step's output as written, ANSI escape sequences included
+    RawStep(i64),
+}
+
+/// Stream output from the database in fixed-size chunks as `text/plain`,
+/// so large logs are never held in memory as a whole. Each part may be
+/// preceded by a header line.
+fn stream_output(state: SharedState, parts: Vec<(Option<String>, OutputSource)>) -> Response {
+    // Small buffer: the reader only stays a few chunks ahead of the client
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, CicdError>>(4);
+
+    tokio::spawn(async move {
+        for (header, source) in parts {
+            if let Some(header) = header
+                && tx.send(Ok(header.into_bytes())).await.is_err()
+            {
+                return;
+            }
+            let mut offset = 0;
+            loop {
+                let chunk = match &source {
+                    OutputSource::Job(id) => {
+                        state
+                            .job_store
+                            .read_job_output_chunk(id, offset, OUTPUT_CHUNK_SIZE)
+                            .await
+                    }
+                    OutputSource::Step(id) => {
+                        state
+                            .job_store
+                            .read_log_output_chunk(*id, offset, OUTPUT_CHUNK_SIZE, false)
+                            .await
+                    }
+                    OutputSource::RawStep(id) => {
+                        state
+                            .job_store
+                            .read_log_output_chunk(*id, offset, OUTPUT_CHUNK_SIZE, true)
+                            .await
+                    }
+                };
+                match chunk {
+                    Ok(chunk) if chunk.is_empty() => break,
+                    Ok(chunk) => {
+                        offset += chunk.len() as i64;
+                        if tx.send(Ok(chunk)).await.is_err() {
+                            return; // client went away
+                        }
+                    }
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+fn job_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": "Job not found"})),
+    )
+        .into_response()
+}
+
+fn internal_error(e: CicdError) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({"error": e.to_string()})),
+    )
+        .into_response()
+}
+
+/// GET /api/jobs/{id}/output - Stream a job's combined output as plain text
+pub async fn get_job_output(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.job_store.job_exists(&id).await {
+        Ok(true) => stream_output(state, vec![(None, OutputSource::Job(id))]),
+        Ok(false) => job_not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// GET /api/jobs/{id}/payload - The raw webhook body that created a job
+pub async fn get_job_payload(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.job_store.get_job_payload(&id).await {
+        Ok(Some((Some(payload), false))) => {
+            ([(header::CONTENT_TYPE, "application/json")], payload).into_response()
+        }
+        // A cut-off body isn't valid JSON anymore
+        Ok(Some((Some(payload), true))) => (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+                (HeaderName::from_static("x-payload-truncated"), "true"),
+            ],
+            payload,
+        )
+            .into_response(),
+        Ok(Some((None, _))) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Job has no stored webhook payload"})),
+        )
+            .into_response(),
+        Ok(None) => job_not_found(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// GET /api/jobs/{id}/logs/{log_id}/output - Stream a single step's output as plain text
+pub async fn get_job_log_output(
+    AxumState(state): AxumState<SharedState>,
+    Path((id, log_id)): Path<(String, i64)>,
+) -> Response {
+    match state.job_store.get_job_log_steps(&id).await {
+        Ok(steps) if steps.iter().any(|(step_id, ..)| *step_id == log_id) => {
+            stream_output(state, vec![(None, OutputSource::Step(log_id))])
+        }
+        Ok(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Log not found"})),
+        )
+            .into_response(),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// GET /api/jobs/{id}/logs/download - Download all step logs as one plain text file,
+/// with ANSI escape sequences if the project keeps them for the download
+pub async fn download_job_logs(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    match state.job_store.job_exists(&id).await {
+        Ok(true) => {}
+        Ok(false) => return job_not_found(),
+        Err(e) => return internal_error(e),
+    }
+    let steps = match state.job_store.get_job_log_steps(&id).await {
+        Ok(steps) => steps,
+        Err(e) => return internal_error(e),
+    };
+
+    let parts = steps
+        .into_iter()
+        .map(|(log_id, sequence, log_type, command)| {
+            let header = match command {
+                Some(command) => format!("\n==> [{}] {}: {}\n", sequence, log_type, command),
+                None => format!("\n==> [{}] {}\n", sequence, log_type),
+            };
+            (Some(header), OutputSource::RawStep(log_id))
+        })
+        .collect();
+
+    let mut response = stream_output(state, parts);
+    if let Ok(value) = format!("attachment; filename=\"job-{}.log\"", id).parse() {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
@@ -0,0 +1,97 @@
+//! Maintenance mode endpoints: pause automatic deploys of a project
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::SharedState;
+
+/// Optional request body for enabling maintenance mode
+#[derive(Debug, Default, Deserialize)]
+pub struct MaintenanceRequest {
+    /// Shown in project summaries while maintenance mode is on
+    pub reason: Option<String>,
+}
+
+/// POST /api/projects/{name}/maintenance - Put a project into maintenance
+/// mode: webhooks are still accepted, but its jobs stay queued until it is lifted
+pub async fn enable_maintenance(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+    request: Option<Json<MaintenanceRequest>>,
+) -> Response {
+    if !project_exists(&state, &name) {
+        return error_response(StatusCode::NOT_FOUND, "Project not found".to_string());
+    }
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+
+    match state
+        .job_store
+        .enable_maintenance(&name, request.reason.as_deref())
+        .await
+    {
+        Ok(true) => info!("Project '{}' entered maintenance mode", name),
+        Ok(false) => {}
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    maintenance_status(&state, &name).await
+}
+
+/// DELETE /api/projects/{name}/maintenance - Leave maintenance mode and
+/// release the held jobs
+pub async fn disable_maintenance(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+) -> Response {
+    if !project_exists(&state, &name) {
+        return error_response(StatusCode::NOT_FOUND, "Project not found".to_string());
+    }
+
+    match state.job_store.disable_maintenance(&name).await {
+        Ok(true) => {
+            info!("Project '{}' left maintenance mode", name);
+            state.dispatcher.wake_all();
+        }
+        Ok(false) => {}
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    maintenance_status(&state, &name).await
+}
+
+fn project_exists(state: &SharedState, name: &str) -> bool {
+    let config = state.config.load();
+    config.project.iter().any(|p| p.name == name)
+}
+
+async fn maintenance_status(state: &SharedState, name: &str) -> Response {
+    let maintenance = match state.job_store.get_maintenance(name).await {
+        Ok(m) => m,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let held_jobs = match &maintenance {
+        Some(_) => match state.job_store.get_held_job_count(name).await {
+            Ok(count) => count,
+            Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        None => 0,
+    };
+
+    Json(json!({
+        "project": name,
+        "maintenance": maintenance.is_some(),
+        "since": maintenance.as_ref().map(|m| &m.enabled_at),
+        "reason": maintenance.as_ref().and_then(|m| m.reason.as_ref()),
+        "held_jobs": held_jobs,
+    }))
+    .into_response()
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
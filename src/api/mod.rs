@@ -2,17 +2,40 @@
 //!
 //! Contains both core endpoints and new REST API endpoints for the Web UI
 
+pub mod artifacts;
+pub mod caches;
+pub mod compare;
 pub mod config;
+pub mod deliveries;
 pub mod jobs;
+pub mod maintenance;
 pub mod projects;
+pub mod resume;
+pub mod retry;
+pub mod rollback;
+pub mod server;
 pub mod stats;
 pub mod stream;
 pub mod webhook;
+pub mod workspaces;
 
 // Re-export handlers
+pub use artifacts::{download_job_artifact, get_job_artifacts};
+pub use caches::{get_project_caches, purge_project_cache, purge_project_caches};
+pub use compare::compare_jobs;
 pub use config::{get_config, reload_config_endpoint};
-pub use jobs::{get_job, get_job_logs, get_jobs};
-pub use projects::get_projects;
+pub use deliveries::redeliver;
+pub use jobs::{
+    cancel_job, download_job_logs, get_job, get_job_log_output, get_job_logs, get_job_output,
+    get_job_payload, get_jobs, pin_job, unpin_job,
+};
+pub use maintenance::{disable_maintenance, enable_maintenance};
+pub use projects::{get_project_flaky, get_project_releases, get_projects, simulate_push};
+pub use resume::resume_job;
+pub use retry::retry_job;
+pub use rollback::rollback_project;
+pub use server::get_server_logs;
 pub use stats::{get_stats, status};
 pub use stream::{LogChunkEvent, stream_jobs, stream_logs};
-pub use webhook::handle_webhook;
+pub use webhook::{handle_webhook, process_pending_intake, route_webhooks};
+pub use workspaces::delete_job_workspace;
@@ -1,10 +1,25 @@
 //! Projects API endpoints
 
-use axum::{Json, extract::State as AxumState};
-use serde::Serialize;
+use axum::{
+    Json,
+    extract::{Path, Query, State as AxumState},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::SharedState;
+use crate::api::webhook::create_push_job;
+use crate::git;
 use crate::job::JobStatus;
+use crate::params;
+use crate::version;
+use crate::webhook::{PushEvent, PushRef};
+
+/// Pusher/author name reported for simulated pushes
+const SIMULATED_PUSHER: &str = "simulate";
 
 /// Summary of a project with recent job stats
 #[derive(Debug, Serialize)]
@@ -15,17 +30,21 @@ pub struct ProjectSummary {
     pub last_job_at: Option<String>,
     pub success_rate: f64,
     pub total_jobs: i64,
+    /// Automatic deploys are paused (see `POST /api/projects/{name}/maintenance`)
+    pub maintenance: bool,
+    pub maintenance_since: Option<String>,
+    pub maintenance_reason: Option<String>,
 }
 
 /// GET /api/projects - Get all projects with summaries
 pub async fn get_projects(AxumState(state): AxumState<SharedState>) -> Json<serde_json::Value> {
-    // Clone project configs to avoid holding lock across await
+    // Clone project configs so the config snapshot isn't held across await
     let projects: Vec<_> = {
-        let config = state.config.read().unwrap();
+        let config = state.config.load();
         config
             .project
             .iter()
-            .map(|p| (p.name.clone(), p.branches.clone()))
+            .map(|p| (p.name.clone(), p.branches.patterns().to_vec()))
             .collect()
     };
 
@@ -63,11 +82,20 @@ pub async fn get_projects(AxumState(state): AxumState<SharedState>) -> Json<serd
                     JobStatus::Running => "running",
                     JobStatus::Success => "success",
                     JobStatus::Failed => "failed",
+                    JobStatus::Skipped => "skipped",
+                    JobStatus::Cancelled => "cancelled",
+                    JobStatus::TimedOut => "timed_out",
                 };
                 (Some(status.to_string()), Some(j.started_at.to_rfc3339()))
             })
             .unwrap_or((None, None));
 
+        let maintenance = state
+            .job_store
+            .get_maintenance(&name)
+            .await
+            .unwrap_or_default();
+
         summaries.push(ProjectSummary {
             name,
             branches,
@@ -75,6 +103,9 @@ pub async fn get_projects(AxumState(state): AxumState<SharedState>) -> Json<serd
             last_job_at,
             success_rate,
             total_jobs,
+            maintenance: maintenance.is_some(),
+            maintenance_since: maintenance.as_ref().map(|m| m.enabled_at.clone()),
+            maintenance_reason: maintenance.and_then(|m| m.reason),
         });
     }
 
@@ -83,3 +114,362 @@ pub async fn get_projects(AxumState(state): AxumState<SharedState>) -> Json<serd
         "count": summaries.len()
     }))
 }
+
+/// Query parameters for flaky step analytics
+#[derive(Debug, Deserialize)]
+pub struct FlakyQuery {
+    /// Number of recent jobs to analyze (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// Failure statistics for one pipeline step
+#[derive(Debug, Serialize)]
+pub struct StepFlakiness {
+    pub step: String,
+    pub total: i64,
+    pub failed: i64,
+    pub failure_rate: f64,
+    /// Times this step failed and a rerun of the same commit then succeeded
+    pub flaky_recoveries: i64,
+}
+
+/// A failed job followed by a successful rerun of the same commit
+#[derive(Debug, Serialize)]
+pub struct FlakySequence {
+    pub branch: String,
+    pub commit_sha: String,
+    pub failed_job_id: String,
+    pub failed_step: Option<String>,
+    pub recovered_job_id: String,
+    pub failed_at: String,
+    pub recovered_at: String,
+}
+
+/// GET /api/projects/{name}/flaky - Per-step failure rates and flaky reruns
+pub async fn get_project_flaky(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+    Query(params): Query<FlakyQuery>,
+) -> impl IntoResponse {
+    let exists = {
+        let config = state.config.load();
+        config.project.iter().any(|p| p.name == name)
+    };
+    if !exists {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Project not found"})),
+        )
+            .into_response();
+    }
+
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    let counts = match state.job_store.get_step_failure_counts(&name, limit).await {
+        Ok(c) => c,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+
+    let failed_steps: HashMap<String, String> =
+        match state.job_store.get_failed_steps(&name, limit).await {
+            Ok(steps) => steps.into_iter().collect(),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": e.to_string()})),
+                )
+                    .into_response();
+            }
+        };
+
+    // Oldest first, so a failure can be paired with a later rerun
+    let mut jobs = match state.job_store.get_jobs_by_project(&name, limit).await {
+        Ok(j) => j,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+    jobs.retain(|j| !j.dry_run);
+    jobs.reverse();
+
+    // Find failed (or timed out) jobs whose next run on the same branch +
+    // commit succeeded
+    let is_failure = |status: &JobStatus| matches!(status, JobStatus::Failed | JobStatus::TimedOut);
+    let mut sequences = Vec::new();
+    for (i, failed) in jobs.iter().enumerate() {
+        if !is_failure(&failed.status) {
+            continue;
+        }
+        let Some(sha) = &failed.commit_sha else {
+            continue;
+        };
+        let next = jobs[i + 1..].iter().find(|j| {
+            j.branch == failed.branch
+                && j.commit_sha.as_ref() == Some(sha)
+                && (j.status == JobStatus::Success || is_failure(&j.status))
+        });
+        if let Some(recovered) = next.filter(|j| j.status == JobStatus::Success) {
+            let failed_step = failed_steps.get(&failed.id).cloned();
+            sequences.push(FlakySequence {
+                branch: failed.branch.clone(),
+                commit_sha: sha.clone(),
+                failed_job_id: failed.id.clone(),
+                failed_step,
+                recovered_job_id: recovered.id.clone(),
+                failed_at: failed.started_at.to_rfc3339(),
+                recovered_at: recovered.started_at.to_rfc3339(),
+            });
+        }
+    }
+
+    let steps: Vec<StepFlakiness> = counts
+        .into_iter()
+        .map(|c| {
+            let flaky_recoveries = sequences
+                .iter()
+                .filter(|s| s.failed_step.as_deref() == Some(c.log_type.as_str()))
+                .count() as i64;
+            let failure_rate = if c.total > 0 {
+                (c.failed as f64 / c.total as f64) * 100.0
+            } else {
+                0.0
+            };
+            StepFlakiness {
+                step: c.log_type,
+                total: c.total,
+                failed: c.failed,
+                failure_rate,
+                flaky_recoveries,
+            }
+        })
+        .collect();
+
+    Json(json!({
+        "project": name,
+        "jobs_analyzed": jobs.len(),
+        "steps": steps,
+        "flaky_sequences": sequences,
+    }))
+    .into_response()
+}
+
+/// Query parameters for listing releases
+#[derive(Debug, Deserialize)]
+pub struct ReleasesQuery {
+    /// Number of recent tag jobs to include (default: 200, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// A semver release tag with the jobs that built it
+#[derive(Debug, Serialize)]
+pub struct ReleaseSummary {
+    pub tag: String,
+    pub version: String,
+    pub prerelease: bool,
+    pub commit_sha: Option<String>,
+    /// Status of the latest job for the tag that wasn't a dry run
+    pub status: Option<JobStatus>,
+    /// Newest first
+    pub jobs: Vec<ReleaseJob>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReleaseJob {
+    pub id: String,
+    pub status: JobStatus,
+    pub dry_run: bool,
+    pub started_at: String,
+    pub completed_at: Option<String>,
+}
+
+/// GET /api/projects/{name}/releases - Semver tag releases, highest version
+/// first, with their job results
+pub async fn get_project_releases(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+    Query(params): Query<ReleasesQuery>,
+) -> impl IntoResponse {
+    let exists = {
+        let config = state.config.load();
+        config.project.iter().any(|p| p.name == name)
+    };
+    if !exists {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Project not found"})),
+        )
+            .into_response();
+    }
+
+    let limit = params.limit.unwrap_or(200).clamp(1, 1000);
+    let jobs = match state.job_store.get_tag_jobs(&name, limit).await {
+        Ok(j) => j,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            )
+                .into_response();
+        }
+    };
+
+    // Jobs come newest first, so each release's first job is its latest
+    let mut releases: Vec<(semver::Version, ReleaseSummary)> = Vec::new();
+    for job in jobs {
+        let Some(tag) = job.tag.clone() else {
+            continue;
+        };
+        let Some(version) = version::parse_tag(&tag) else {
+            continue;
+        };
+        let release_job = ReleaseJob {
+            id: job.id,
+            status: job.status.clone(),
+            dry_run: job.dry_run,
+            started_at: job.started_at.to_rfc3339(),
+            completed_at: job.completed_at.map(|t| t.to_rfc3339()),
+        };
+        let status = (!job.dry_run).then_some(job.status);
+        match releases.iter_mut().find(|(_, r)| r.tag == tag) {
+            Some((_, release)) => {
+                release.status = release.status.take().or(status);
+                release.jobs.push(release_job);
+            }
+            None => releases.push((
+                version.clone(),
+                ReleaseSummary {
+                    tag,
+                    version: version.to_string(),
+                    prerelease: !version.pre.is_empty(),
+                    commit_sha: job.commit_sha,
+                    status,
+                    jobs: vec![release_job],
+                },
+            )),
+        }
+    }
+    releases.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let releases: Vec<ReleaseSummary> = releases.into_iter().map(|(_, r)| r).collect();
+
+    Json(json!({
+        "project": name,
+        "count": releases.len(),
+        "releases": releases,
+    }))
+    .into_response()
+}
+
+/// Request body for simulating a push
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SimulateRequest {
+    /// Branch to simulate a push to (must match one of the project's branches)
+    pub branch: String,
+    /// Commit SHA to report (default: the last fetched `origin/<branch>`)
+    pub sha: Option<String>,
+    /// Commit message to report
+    pub message: Option<String>,
+    /// Validate instead of executing (see dry run mode)
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Parameters passed to scripts as `CICD_PARAM_<NAME>`, checked against
+    /// the project's `inputs`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+/// POST /api/projects/{name}/simulate - Run a fabricated push through the
+/// webhook pipeline, to test a project config without pushing a commit
+pub async fn simulate_push(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+    Json(request): Json<SimulateRequest>,
+) -> impl IntoResponse {
+    let (project, name_exists) = {
+        let config = state.config.load();
+        let project = config
+            .project
+            .iter()
+            .find(|p| p.name == name && p.accepts_branch(&request.branch))
+            .cloned();
+        (project, config.project.iter().any(|p| p.name == name))
+    };
+    let Some(project) = project else {
+        let (status, error) = if name_exists {
+            (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Branch '{}' is not configured for project '{}'",
+                    request.branch, name
+                ),
+            )
+        } else {
+            (StatusCode::NOT_FOUND, "Project not found".to_string())
+        };
+        return (status, Json(json!({"error": error}))).into_response();
+    };
+    let params = match params::resolve(project.inputs.as_deref(), request.params) {
+        Ok(params) => params,
+        Err(error) => {
+            return (StatusCode::BAD_REQUEST, Json(json!({"error": error}))).into_response();
+        }
+    };
+
+    let commit_sha = match request.sha {
+        Some(sha) => Some(sha),
+        None => {
+            let repo_path = project.repo_path.clone();
+            let spec = format!("origin/{}", request.branch);
+            git::run_blocking("git rev-parse", move || git::rev_parse(&repo_path, &spec))
+                .await
+                .ok()
+        }
+    };
+    let message = request
+        .message
+        .unwrap_or_else(|| "Simulated push".to_string());
+
+    // A push like one from a webhook, so it takes the same path as real ones
+    let push = PushEvent {
+        push_ref: PushRef::Branch(request.branch.clone()),
+        repo_name: project.repository().to_string(),
+        repo_full_name: project.repo_full_name.clone(),
+        deleted: false,
+        forced: false,
+        commit_sha: commit_sha.clone(),
+        commit_message: Some(message),
+        commit_author_name: Some(SIMULATED_PUSHER.to_string()),
+        commit_author_email: None,
+        pusher_name: Some(SIMULATED_PUSHER.to_string()),
+        repository_url: None,
+        changed_files: None,
+        pull_request: None,
+        published_release: None,
+        params,
+    };
+
+    match create_push_job(&state, &project, &push, request.dry_run, None, false, None).await {
+        Ok(job_id) => Json(json!({
+            "job_id": job_id,
+            "project": project.name,
+            "branch": request.branch,
+            "commit_sha": commit_sha,
+            "dry_run": request.dry_run,
+        }))
+        .into_response(),
+        Err(status) => (
+            status,
+            Json(json!({"error": "Failed to create simulated job"})),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,187 @@
+//! Resume endpoint: re-run a failed job from the step that failed
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::api::stream::JobEvent;
+use crate::job::{Job, JobStatus, ResumeStep};
+use crate::webhook::WebhookData;
+use crate::{ProjectConfig, SharedState};
+
+/// Steps whose failure doesn't fail the job, so they don't tell where it failed
+const NON_FATAL_STEPS: &[&str] = &[
+    "resume",
+    "git_log",
+    "mirror",
+    "artifacts",
+    "cache_restore",
+    "cache_save",
+    "post_success",
+    "post_failure",
+    "post_script",
+    "post_always",
+    "release_cleanup",
+];
+
+/// POST /api/jobs/{id}/resume - Re-run a failed job's failed step and
+/// everything after it as a new, linked job. The fetch (and the pre-script,
+/// if it succeeded) are skipped; the job's stored config snapshot and commit
+/// are used.
+pub async fn resume_job(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let original = match state.job_store.get_job(&id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Job not found".to_string()),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if original.status != JobStatus::Failed || original.dry_run {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "Job {} is not a failed run; only failed jobs can be resumed",
+                id
+            ),
+        );
+    }
+
+    let snapshot = match state.job_store.get_pipeline_snapshot(&id).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            return error_response(
+                StatusCode::CONFLICT,
+                format!("Job {} has no stored pipeline snapshot to resume from", id),
+            );
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let (project, mut webhook_data) = match (
+        serde_json::from_str::<ProjectConfig>(&snapshot.0),
+        serde_json::from_str::<WebhookData>(&snapshot.1),
+    ) {
+        (Ok(project), Ok(webhook_data)) => (project, webhook_data),
+        (Err(e), _) | (_, Err(e)) => {
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Invalid pipeline snapshot of job {}: {}", id, e),
+            );
+        }
+    };
+
+    // The first fatal step that didn't succeed is where the job failed
+    // (or was interrupted)
+    let logs = match state.job_store.get_job_logs(&id).await {
+        Ok(logs) => logs,
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let Some(failed_step) = logs
+        .into_iter()
+        .find(|log| log.status != "success" && !NON_FATAL_STEPS.contains(&log.log_type.as_str()))
+    else {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!("Job {} has no failed step to resume from", id),
+        );
+    };
+    // A job that failed in one of its configured steps reruns all of them
+    let in_steps = project
+        .steps
+        .iter()
+        .flatten()
+        .any(|step| step.name == failed_step.log_type);
+    let resume_from = if in_steps {
+        Some(ResumeStep::MainScript)
+    } else {
+        ResumeStep::for_failed_step(&failed_step.log_type)
+    };
+    let Some(resume_from) = resume_from else {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "Job {} failed in '{}' before its scripts ran; there are no steps to skip",
+                id, failed_step.log_type
+            ),
+        );
+    };
+
+    // Build the commit the original run built, even if the branch moved on
+    webhook_data.resume_from = Some(resume_from);
+    if webhook_data.checkout_sha.is_none() {
+        webhook_data.checkout_sha = original.commit_sha.clone();
+    }
+
+    let mut job = Job::from_webhook(
+        original.project_name.clone(),
+        original.branch.clone(),
+        original.commit_sha.clone(),
+        original.commit_message.clone(),
+        original.commit_author.clone(),
+    );
+    job.rollback_of = original.rollback_of.clone();
+    job.tag = original.tag.clone();
+    job.pull_request = original.pull_request;
+    job.resumed_from = Some(id.clone());
+    let job_id = job.id.clone();
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!("Failed to create resumed job: {}", e);
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    // Scripts get the original webhook body as CICD_PAYLOAD_FILE
+    if let Err(e) = state.job_store.copy_job_payload(&id, &job_id).await {
+        error!("Failed to copy webhook payload to job {}: {}", job_id, e);
+    }
+    info!(
+        "Created job {} resuming job {} from {}",
+        job_id,
+        id,
+        resume_from.as_str()
+    );
+    state.job_events.send(JobEvent {
+        event_type: "created".to_string(),
+        job_id: job_id.clone(),
+        project_name: job.project_name.clone(),
+        branch: job.branch.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    if let Err(e) = state
+        .dispatcher
+        .enqueue(&state.job_store, &job_id, &project, &webhook_data, None)
+        .await
+    {
+        error!("Failed to enqueue resumed job {}: {}", job_id, e);
+        let _ = state
+            .job_store
+            .complete_job(
+                &job_id,
+                JobStatus::Failed,
+                None,
+                Some(e.to_string()),
+                Utc::now(),
+            )
+            .await;
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    Json(json!({
+        "job_id": job_id,
+        "project": job.project_name,
+        "branch": job.branch,
+        "commit_sha": job.commit_sha,
+        "resumed_from": id,
+        "resume_step": resume_from,
+    }))
+    .into_response()
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
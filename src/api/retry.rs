@@ -0,0 +1,129 @@
+//! Retry endpoint: run a failed job's pipeline again, e.g. after a
+//! transient failure, without pushing an empty commit
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::api::stream::JobEvent;
+use crate::job::{Job, JobStatus};
+use crate::webhook::WebhookData;
+use crate::{ProjectConfig, SharedState};
+
+/// POST /api/jobs/{id}/retry - Run a failed, timed out or cancelled job's
+/// pipeline again as a new, linked job, with the job's stored config
+/// snapshot and webhook data
+pub async fn retry_job(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let original = match state.job_store.get_job(&id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Job not found".to_string()),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let retryable = matches!(
+        original.status,
+        JobStatus::Failed | JobStatus::TimedOut | JobStatus::Cancelled
+    );
+    if !retryable || original.dry_run {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!(
+                "Job {} is not a failed run; only failed, timed out or cancelled jobs can be retried",
+                id
+            ),
+        );
+    }
+
+    let snapshot = match state.job_store.get_pipeline_snapshot(&id).await {
+        Ok(Some(snapshot)) => snapshot,
+        Ok(None) => {
+            return error_response(
+                StatusCode::CONFLICT,
+                format!("Job {} has no stored pipeline snapshot to retry", id),
+            );
+        }
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let (project, webhook_data) = match (
+        serde_json::from_str::<ProjectConfig>(&snapshot.0),
+        serde_json::from_str::<WebhookData>(&snapshot.1),
+    ) {
+        (Ok(project), Ok(webhook_data)) => (project, webhook_data),
+        (Err(e), _) | (_, Err(e)) => {
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Invalid pipeline snapshot of job {}: {}", id, e),
+            );
+        }
+    };
+
+    let mut job = Job::from_webhook(
+        original.project_name.clone(),
+        original.branch.clone(),
+        original.commit_sha.clone(),
+        original.commit_message.clone(),
+        original.commit_author.clone(),
+    );
+    job.rollback_of = original.rollback_of.clone();
+    job.tag = original.tag.clone();
+    job.resumed_from = original.resumed_from.clone();
+    job.pull_request = original.pull_request;
+    job.retried_from = Some(id.clone());
+    let job_id = job.id.clone();
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!("Failed to create retried job: {}", e);
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    // Scripts get the original webhook body as CICD_PAYLOAD_FILE
+    if let Err(e) = state.job_store.copy_job_payload(&id, &job_id).await {
+        error!("Failed to copy webhook payload to job {}: {}", job_id, e);
+    }
+    info!("Created job {} retrying job {}", job_id, id);
+    state.job_events.send(JobEvent {
+        event_type: "created".to_string(),
+        job_id: job_id.clone(),
+        project_name: job.project_name.clone(),
+        branch: job.branch.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    if let Err(e) = state
+        .dispatcher
+        .enqueue(&state.job_store, &job_id, &project, &webhook_data, None)
+        .await
+    {
+        error!("Failed to enqueue retried job {}: {}", job_id, e);
+        let _ = state
+            .job_store
+            .complete_job(
+                &job_id,
+                JobStatus::Failed,
+                None,
+                Some(e.to_string()),
+                Utc::now(),
+            )
+            .await;
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    Json(json!({
+        "job_id": job_id,
+        "project": job.project_name,
+        "branch": job.branch,
+        "commit_sha": job.commit_sha,
+        "retried_from": id,
+    }))
+    .into_response()
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
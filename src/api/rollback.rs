@@ -0,0 +1,191 @@
+//! Rollback endpoint: redeploy the previous successful commit
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, info};
+
+use crate::SharedState;
+use crate::api::stream::JobEvent;
+use crate::job::{Job, JobStatus};
+use crate::webhook::WebhookData;
+
+/// Optional request body for a rollback
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RollbackRequest {
+    /// Branch to roll back (default: the branch of the project's latest job)
+    pub branch: Option<String>,
+    /// Restore this successful job's commit instead of the previous one
+    pub job_id: Option<String>,
+}
+
+/// POST /api/projects/{name}/rollback - Check out the previous successful
+/// commit and re-run the deploy (or `rollback_script`) as a new, linked job
+pub async fn rollback_project(
+    AxumState(state): AxumState<SharedState>,
+    Path(name): Path<String>,
+    request: Option<Json<RollbackRequest>>,
+) -> Response {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+
+    // The latest push-triggered run tells us the branch and the commit that
+    // should be replaced; earlier rollbacks are skipped so repeating a
+    // rollback doesn't flip back and forth
+    let latest = match state.job_store.get_jobs_by_project(&name, 50).await {
+        Ok(jobs) => jobs.into_iter().find(|j| {
+            !j.dry_run
+                && j.rollback_of.is_none()
+                && request.branch.as_ref().is_none_or(|b| *b == j.branch)
+        }),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let Some(branch) = request
+        .branch
+        .clone()
+        .or_else(|| latest.as_ref().map(|j| j.branch.clone()))
+    else {
+        return error_response(StatusCode::NOT_FOUND, "No jobs to roll back".to_string());
+    };
+
+    let project = {
+        let config = state.config.load();
+        config
+            .project
+            .iter()
+            .find(|p| p.name == name && p.accepts_branch(&branch))
+            .cloned()
+    };
+    let Some(mut project) = project else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            format!("No project '{}' configured for branch '{}'", name, branch),
+        );
+    };
+
+    let target = match &request.job_id {
+        Some(id) => match state.job_store.get_job(id).await {
+            Ok(Some(job))
+                if job.project_name == name
+                    && job.branch == branch
+                    && job.status == JobStatus::Success
+                    && !job.dry_run
+                    && job.commit_sha.is_some() =>
+            {
+                Some(job)
+            }
+            Ok(_) => {
+                return error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Job {} is not a successful run of '{}' on '{}' with a commit",
+                        id, name, branch
+                    ),
+                );
+            }
+            Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        None => {
+            let current_sha = latest.as_ref().and_then(|j| j.commit_sha.as_deref());
+            match state
+                .job_store
+                .get_last_successful_job(&name, &branch, current_sha)
+                .await
+            {
+                Ok(job) => job,
+                Err(e) => {
+                    return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+                }
+            }
+        }
+    };
+    let Some(target) = target else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            format!(
+                "No previous successful commit on '{}' to roll back to",
+                branch
+            ),
+        );
+    };
+    let sha = target.commit_sha.clone().unwrap_or_default();
+
+    // The queued config snapshot runs the rollback script as the main script
+    if let Some(script) = project.rollback_script.clone() {
+        project.run_script = script;
+        project.steps = None;
+        project.branch_scripts = None;
+    }
+
+    let mut job = Job::from_webhook(
+        name.clone(),
+        branch.clone(),
+        Some(sha.clone()),
+        Some(format!(
+            "Rollback to {} (job {})",
+            &sha[..7.min(sha.len())],
+            target.id
+        )),
+        None,
+    );
+    job.rollback_of = Some(target.id.clone());
+    let job_id = job.id.clone();
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!("Failed to create rollback job: {}", e);
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    info!(
+        "Created rollback job {} for project '{}' branch '{}' to {}",
+        job_id, name, branch, sha
+    );
+    state.job_events.send(JobEvent {
+        event_type: "created".to_string(),
+        job_id: job_id.clone(),
+        project_name: name.clone(),
+        branch: branch.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    let webhook_data = WebhookData {
+        commit_sha: Some(sha.clone()),
+        commit_message: job.commit_message.clone(),
+        checkout_sha: Some(sha.clone()),
+        ..WebhookData::minimal(name.clone(), branch.clone(), project.repo_path.clone())
+    };
+    if let Err(e) = state
+        .dispatcher
+        .enqueue(&state.job_store, &job_id, &project, &webhook_data, None)
+        .await
+    {
+        error!("Failed to enqueue rollback job {}: {}", job_id, e);
+        let _ = state
+            .job_store
+            .complete_job(
+                &job_id,
+                JobStatus::Failed,
+                None,
+                Some(e.to_string()),
+                Utc::now(),
+            )
+            .await;
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    Json(json!({
+        "job_id": job_id,
+        "project": name,
+        "branch": branch,
+        "commit_sha": sha,
+        "rollback_of": target.id,
+    }))
+    .into_response()
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
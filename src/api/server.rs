@@ -0,0 +1,55 @@
+//! Server introspection endpoints
+
+use axum::{
+    Json,
+    extract::{Query, State as AxumState},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::Level;
+
+use crate::SharedState;
+
+/// Query parameters for server log listing
+#[derive(Debug, Deserialize)]
+pub struct ServerLogsQuery {
+    /// Minimum level (error, warn, info, debug, trace; default: info)
+    pub level: Option<String>,
+    /// Maximum number of entries (default: 200, max: 2000)
+    pub limit: Option<usize>,
+}
+
+/// GET /api/server/logs - Recent in-memory server log entries
+pub async fn get_server_logs(
+    AxumState(state): AxumState<SharedState>,
+    Query(params): Query<ServerLogsQuery>,
+) -> impl IntoResponse {
+    let level = match params.level.as_deref().unwrap_or("info").parse::<Level>() {
+        Ok(level) => level,
+        Err(_) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Invalid level. Use: error, warn, info, debug, trace"})),
+            )
+                .into_response();
+        }
+    };
+    let limit = params
+        .limit
+        .unwrap_or(200)
+        .min(crate::logging::DEFAULT_LOG_CAPACITY);
+
+    let entries = match state.server_logs.lock() {
+        Ok(manager) => manager.recent(level, limit),
+        Err(_) => Vec::new(),
+    };
+
+    Json(json!({
+        "level": level.to_string().to_lowercase(),
+        "entries": entries,
+        "count": entries.len(),
+    }))
+    .into_response()
+}
@@ -30,6 +30,9 @@ pub struct JobStats {
     pub running: i64,
     pub success: i64,
     pub failed: i64,
+    pub skipped: i64,
+    pub cancelled: i64,
+    pub timed_out: i64,
     pub success_rate: f64,
 }
 
@@ -42,9 +45,9 @@ pub struct StatsResponse {
 
 /// GET /api/stats - Get server and job statistics
 pub async fn get_stats(AxumState(state): AxumState<SharedState>) -> Json<StatsResponse> {
-    // Get project count without holding lock across await
+    // Get project count without holding the config snapshot across await
     let total_projects = {
-        let config = state.config.read().unwrap();
+        let config = state.config.load();
         config.project.len()
     };
 
@@ -85,10 +88,33 @@ pub async fn get_stats(AxumState(state): AxumState<SharedState>) -> Json<StatsRe
     let failed = failed_jobs.len() as i64;
     let failed_non_dry_run = failed_jobs.iter().filter(|j| !j.dry_run).count() as i64;
 
-    let total = queued + running + success + failed;
+    let skipped = state
+        .job_store
+        .get_jobs_by_status(JobStatus::Skipped, 10000)
+        .await
+        .map(|j| j.len() as i64)
+        .unwrap_or(0);
+
+    let cancelled = state
+        .job_store
+        .get_jobs_by_status(JobStatus::Cancelled, 10000)
+        .await
+        .map(|j| j.len() as i64)
+        .unwrap_or(0);
+
+    // Timed out jobs count as failures in the success rate
+    let timed_out_jobs = state
+        .job_store
+        .get_jobs_by_status(JobStatus::TimedOut, 10000)
+        .await
+        .unwrap_or_default();
+    let timed_out = timed_out_jobs.len() as i64;
+    let timed_out_non_dry_run = timed_out_jobs.iter().filter(|j| !j.dry_run).count() as i64;
+
+    let total = queued + running + success + failed + skipped + cancelled + timed_out;
 
     // Calculate success rate excluding dry runs
-    let completed_non_dry_run = success_non_dry_run + failed_non_dry_run;
+    let completed_non_dry_run = success_non_dry_run + failed_non_dry_run + timed_out_non_dry_run;
     let success_rate = if completed_non_dry_run > 0 {
         (success_non_dry_run as f64 / completed_non_dry_run as f64) * 100.0
     } else {
@@ -101,6 +127,9 @@ pub async fn get_stats(AxumState(state): AxumState<SharedState>) -> Json<StatsRe
         running,
         success,
         failed,
+        skipped,
+        cancelled,
+        timed_out,
         success_rate,
     };
 
@@ -154,6 +183,21 @@ pub async fn status(
                 .get_jobs_by_status(JobStatus::Failed, 50)
                 .await
                 .unwrap_or_default(),
+            "skipped" => state
+                .job_store
+                .get_jobs_by_status(JobStatus::Skipped, 50)
+                .await
+                .unwrap_or_default(),
+            "cancelled" => state
+                .job_store
+                .get_jobs_by_status(JobStatus::Cancelled, 50)
+                .await
+                .unwrap_or_default(),
+            "timed_out" => state
+                .job_store
+                .get_jobs_by_status(JobStatus::TimedOut, 50)
+                .await
+                .unwrap_or_default(),
             _ => state
                 .job_store
                 .get_recent_jobs(10)
@@ -168,7 +212,7 @@ pub async fn status(
             .unwrap_or_default()
     };
 
-    let config = state.config.read().unwrap();
+    let config = state.config.load();
 
     Json(json!({
         "server": {
@@ -5,15 +5,17 @@ use axum::{
     response::sse::{Event, Sse},
 };
 use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
-use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::ReceiverStream;
 
 use crate::SharedState;
 
 /// Job event for SSE broadcasting
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct JobEvent {
-    pub event_type: String, // created, running, success, failed
+    pub event_type: String, // created, running, success, failed, skipped, cancelled, paused
     pub job_id: String,
     pub project_name: String,
     pub branch: String,
@@ -21,29 +23,120 @@ pub struct JobEvent {
 }
 
 /// Log chunk event for real-time log streaming
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct LogChunkEvent {
     pub job_id: String,
     pub step_type: String, // git_fetch, main_script, etc.
-    pub chunk: String,
+    pub chunk: Arc<str>,
+    /// Pipe the script wrote the chunk to
+    #[serde(default)]
+    pub channel: Channel,
     pub timestamp: String,
 }
 
+/// Output stream of a script a log chunk comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    /// Standard output, and output of the pipeline's own steps (git, releases)
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+/// Item delivered to a subscriber: an event, or a marker that events were dropped
+#[derive(Debug, Clone)]
+pub enum Delivery<T> {
+    Event(T),
+    Dropped(u64),
+}
+
+struct Subscriber<T> {
+    tx: mpsc::Sender<Delivery<T>>,
+    /// Events dropped since the last successful delivery
+    dropped: u64,
+}
+
+/// Fan-out hub giving each subscriber its own bounded queue.
+/// A slow subscriber only loses its own events, and is told how many were
+/// dropped (in order) once its queue has room again.
+pub struct EventHub<T> {
+    subscribers: Arc<Mutex<Vec<Subscriber<T>>>>,
+    capacity: usize,
+}
+
+impl<T> Clone for EventHub<T> {
+    fn clone(&self) -> Self {
+        Self {
+            subscribers: self.subscribers.clone(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl<T: Clone> EventHub<T> {
+    /// Create a hub with the given per-subscriber queue capacity
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            capacity: capacity.max(2),
+        }
+    }
+
+    /// Register a new subscriber
+    pub fn subscribe(&self) -> mpsc::Receiver<Delivery<T>> {
+        let (tx, rx) = mpsc::channel(self.capacity);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(Subscriber { tx, dropped: 0 });
+        }
+        rx
+    }
+
+    /// Publish an event to all subscribers without blocking
+    pub fn send(&self, event: T) {
+        let Ok(mut subscribers) = self.subscribers.lock() else {
+            return;
+        };
+        subscribers.retain(|s| !s.tx.is_closed());
+
+        for sub in subscribers.iter_mut() {
+            // Report earlier drops before newer events to keep ordering honest
+            if sub.dropped > 0 {
+                match sub.tx.try_send(Delivery::Dropped(sub.dropped)) {
+                    Ok(()) => sub.dropped = 0,
+                    Err(_) => {
+                        sub.dropped += 1;
+                        continue;
+                    }
+                }
+            }
+            if sub.tx.try_send(Delivery::Event(event.clone())).is_err() {
+                sub.dropped += 1;
+            }
+        }
+    }
+}
+
+/// SSE event telling the client how many events it missed
+fn dropped_event(count: u64) -> Event {
+    Event::default()
+        .event("dropped")
+        .data(serde_json::json!({ "dropped": count }).to_string())
+}
+
 /// GET /api/stream/jobs - SSE stream of job status changes
 pub async fn stream_jobs(
     AxumState(state): AxumState<SharedState>,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
     let rx = state.job_events.subscribe();
-    let stream = BroadcastStream::new(rx);
+    let stream = ReceiverStream::new(rx);
 
-    let event_stream = stream.filter_map(|result| {
-        match result {
-            Ok(event) => {
-                let data = serde_json::to_string(&event).unwrap_or_default();
-                Some(Ok(Event::default().event(&event.event_type).data(data)))
-            }
-            Err(_) => None, // Skip lagged messages
+    let event_stream = stream.map(|delivery| match delivery {
+        Delivery::Event(event) => {
+            let data = serde_json::to_string(&event).unwrap_or_default();
+            Ok(Event::default().event(&event.event_type).data(data))
         }
+        Delivery::Dropped(count) => Ok(dropped_event(count)),
     });
 
     Sse::new(event_stream)
@@ -54,16 +147,14 @@ pub async fn stream_logs(
     AxumState(state): AxumState<SharedState>,
 ) -> Sse<impl tokio_stream::Stream<Item = Result<Event, Infallible>>> {
     let rx = state.log_chunks.subscribe();
-    let stream = BroadcastStream::new(rx);
+    let stream = ReceiverStream::new(rx);
 
-    let event_stream = stream.filter_map(|result| {
-        match result {
-            Ok(chunk) => {
-                let data = serde_json::to_string(&chunk).unwrap_or_default();
-                Some(Ok(Event::default().event("log_chunk").data(data)))
-            }
-            Err(_) => None, // Skip lagged messages
+    let event_stream = stream.map(|delivery| match delivery {
+        Delivery::Event(chunk) => {
+            let data = serde_json::to_string(&chunk).unwrap_or_default();
+            Ok(Event::default().event("log_chunk").data(data))
         }
+        Delivery::Dropped(count) => Ok(dropped_event(count)),
     });
 
     Sse::new(event_stream)
@@ -1,29 +1,87 @@
-//! Webhook handler for GitHub push events
+//! Webhook handler for GitHub, GitLab, Azure DevOps and generic push events
+//! and GitHub pull request and release events
 
 use axum::{
+    Extension, Json,
     body::Bytes,
     extract::Query,
-    extract::State as AxumState,
-    http::{HeaderMap, StatusCode},
+    extract::{ConnectInfo, Request, State as AxumState},
+    handler::Handler,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde_json::json;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use tracing::{debug, error, info, warn};
 
-use crate::SharedState;
 use crate::api::stream::JobEvent;
-use crate::db::store::JobLog;
+use crate::forward;
+use crate::freeze::FreezeAction;
+use crate::ip_filter::{ClientIp, client_ip};
 use crate::job::{Job, JobStatus};
-use crate::utils::{find_matching_project_owned, run_job_pipeline, verify_github_signature};
-use crate::webhook::WebhookData;
+use crate::provider::{EventKind, Provider};
+use crate::rate_limit::{self, RateLimitStatus};
+use crate::utils::{
+    find_generic_pushes, find_matching_projects, find_published_release_projects,
+    find_pull_request_projects, find_release_projects, verify_basic_auth_password,
+    verify_github_sha1_signature, verify_github_signature, verify_gitlab_token,
+};
+use crate::webhook::{PushEvent, PushRef, WebhookData};
+use crate::{ProjectConfig, SharedState};
+
+/// Routes requests on configured webhook paths to `handle_webhook`.
+/// Paths come from the live config, so they follow config reloads.
+pub async fn route_webhooks(
+    AxumState(state): AxumState<SharedState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let client_ip = {
+        let config = state.config.load();
+        if !config.is_webhook_path(req.uri().path()) {
+            return next.run(req).await;
+        }
+        if req.method() != Method::POST {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        }
+
+        // Source IP allowlist, checked before anything else is done
+        let client_ip = ClientIp(req.extensions().get::<ConnectInfo<SocketAddr>>().map(
+            |ConnectInfo(peer)| {
+                client_ip(
+                    peer.ip(),
+                    req.headers(),
+                    config.server.trusted_proxies.as_ref(),
+                )
+            },
+        ));
+        if let Some(allowed) = &config.server.webhook_allowed_ips
+            && !client_ip.allowed_by(allowed)
+        {
+            warn!(
+                "Rejecting webhook from {}: not in webhook_allowed_ips",
+                client_ip
+            );
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        client_ip
+    };
+    req.extensions_mut().insert(client_ip);
+    Handler::call(handle_webhook, req, state).await
+}
 
-/// Handles the GitHub webhook POST request.
+/// Handles the GitHub, GitLab, Azure DevOps or generic webhook POST request.
 pub async fn handle_webhook(
     AxumState(state): AxumState<SharedState>,
+    Extension(client_ip): Extension<ClientIp>,
+    uri: Uri,
     Query(params): Query<HashMap<String, String>>,
     headers: HeaderMap,
     body: Bytes,
-) -> StatusCode {
+) -> Response {
     // Check for dry run mode
     let dry_run = params.get("dry_run").map(|v| v == "true").unwrap_or(false)
         || headers.get("X-Dry-Run").is_some();
@@ -31,445 +89,860 @@ pub async fn handle_webhook(
     if cfg!(debug_assertions) && params.contains_key("dev") {
         debug!("Debug mode");
         debug!("Query Params: {:?}", params);
-        return StatusCode::NO_CONTENT;
+        return StatusCode::NO_CONTENT.into_response();
+    }
+
+    // GitHub redelivers events whose delivery timed out; handle each
+    // delivery once. Dry runs may be repeated. The request is kept so it
+    // can be redelivered by hand (`POST /api/deliveries/{id}/redeliver`).
+    let delivery_id = headers
+        .get("X-GitHub-Delivery")
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| !dry_run);
+    if let Some(id) = delivery_id {
+        let client_ip = client_ip.0.map(|ip| ip.to_string());
+        match state
+            .job_store
+            .record_delivery(
+                id,
+                &uri.to_string(),
+                &headers_to_json(&headers),
+                &body,
+                client_ip.as_deref(),
+            )
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Ignoring redelivery of webhook delivery {}", id);
+                return StatusCode::OK.into_response();
+            }
+            Err(e) => error!("Failed to record webhook delivery {}: {}", id, e),
+        }
     }
-    // Only handle "push" events.
-    let event_opt = headers.get("X-GitHub-Event").and_then(|v| v.to_str().ok());
-    if event_opt != Some("push") {
-        info!("Not push event; Received {:?} event", event_opt);
-        return StatusCode::NO_CONTENT;
+
+    let response = if dry_run {
+        handle_event(&state, &uri, &headers, &body, client_ip, true).await
+    } else {
+        handle_durably(&state, &uri, &headers, &body, client_ip).await
+    };
+
+    // A rejected delivery didn't start any job, so its redelivery may (a
+    // webhook that started some of its projects' jobs is answered with a
+    // 2xx, see `combined_status`)
+    if let Some(id) = delivery_id
+        && !response.status().is_success()
+        && let Err(e) = state.job_store.forget_delivery(id).await
+    {
+        error!("Failed to forget webhook delivery {}: {}", id, e);
     }
+    response
+}
 
-    // Parse body as JSON and extract "ref" (branch) and repo name
-    let payload: serde_json::Value = match serde_json::from_slice(&body) {
-        Ok(v) => v,
+tokio::task_local! {
+    /// The persisted request (`webhook_intake` record) being handled; the
+    /// jobs it creates are linked to it (see `create_push_job`)
+    static INTAKE_ID: i64;
+}
+
+/// Handle a webhook event, persisting the request until it's processed so
+/// a crash can't lose it (see `process_pending_intake`). Failing to persist
+/// it is logged and the request still handled.
+pub(crate) async fn handle_durably(
+    state: &SharedState,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+    client_ip: ClientIp,
+) -> Response {
+    let ip = client_ip.0.map(|ip| ip.to_string());
+    let intake_id = match state
+        .job_store
+        .record_intake(
+            &uri.to_string(),
+            &headers_to_json(headers),
+            body,
+            ip.as_deref(),
+        )
+        .await
+    {
+        Ok(id) => Some(id),
         Err(e) => {
-            info!("Could not parse JSON body: {:?}", e);
-            return StatusCode::BAD_REQUEST;
+            error!("Failed to persist webhook before processing: {}", e);
+            None
         }
     };
 
-    let branch_ref = payload.get("ref").and_then(|r| r.as_str());
-    debug!("{:#?}", &payload);
-    let repo_name = payload
-        .get("repository")
-        .and_then(|r| r.get("name"))
-        .and_then(|n| n.as_str());
-
-    if branch_ref.is_none() || repo_name.is_none() {
-        error!("No ref or repository.name field in push event payload");
-        return StatusCode::BAD_REQUEST;
-    }
-    let branch_ref = branch_ref.unwrap();
-    let branch_name = branch_ref.strip_prefix("refs/heads/").unwrap_or(branch_ref);
-    let repo_name = repo_name.unwrap();
-
-    // Find matching project config based on repo name and branch
-    let maybe_project = {
-        let config = state.config.read().unwrap();
-        find_matching_project_owned(&config, repo_name, branch_name)
+    let event = handle_event(state, uri, headers, body, client_ip, false);
+    let response = match intake_id {
+        Some(id) => INTAKE_ID.scope(id, event).await,
+        None => event.await,
     };
 
-    if let Some(project) = maybe_project {
-        // check rate limits first
-        let rate_limit_sec = project.get_rate_limit();
-        let rate_limit_window = project.get_rate_limit_window();
-        let mut rate_limiter = state.rate_limiter.lock().await;
+    if let Some(id) = intake_id
+        && let Err(e) = state.job_store.delete_intake(id).await
+    {
+        error!("Failed to delete processed webhook {}: {}", id, e);
+    }
+    response
+}
+
+/// Serialize request headers for storage, as a JSON list of `[name, value]`
+fn headers_to_json(headers: &HeaderMap) -> String {
+    let pairs: Vec<(&str, String)> = headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect();
+    serde_json::to_string(&pairs).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Restore request headers stored by `headers_to_json`
+pub(crate) fn headers_from_json(json: &str) -> HeaderMap {
+    let pairs: Vec<(String, String)> = serde_json::from_str(json).unwrap_or_default();
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        if let (Ok(name), Ok(value)) = (
+            header::HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
+
+/// Process the webhook requests a crash or restart interrupted, turning
+/// them into jobs. Requests that already queued jobs are only deleted, so a
+/// push isn't deployed twice. Called once at startup, before requests are
+/// served.
+pub async fn process_pending_intake(state: &SharedState) {
+    let pending = match state.job_store.get_pending_intake().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!("Failed to load unprocessed webhooks: {}", e);
+            return;
+        }
+    };
+    for intake in pending {
+        if let Some(job_ids) = &intake.job_ids {
+            info!(
+                "Webhook received at {} already queued job(s) {}; not processing it again",
+                intake.received_at, job_ids
+            );
+            if let Err(e) = state.job_store.delete_intake(intake.id).await {
+                error!("Failed to delete processed webhook {}: {}", intake.id, e);
+            }
+            continue;
+        }
+        let headers = headers_from_json(&intake.headers);
+        let uri: Uri = intake.uri.parse().unwrap_or_else(|_| Uri::from_static("/"));
+        let client_ip = ClientIp(intake.client_ip.and_then(|ip| ip.parse().ok()));
 
-        if rate_limiter.check_rate_limit(&project.name, rate_limit_sec, rate_limit_window) {
+        info!(
+            "Processing webhook received at {} that was interrupted by a restart",
+            intake.received_at
+        );
+        let event = handle_event(state, &uri, &headers, &intake.body, client_ip, false);
+        let response = INTAKE_ID.scope(intake.id, event).await;
+        if !response.status().is_success() {
             warn!(
-                "Too many requests for project {:?} - {:?} requests per {:?} seconds",
-                &project.name, rate_limit_sec, rate_limit_window
+                "Interrupted webhook received at {} was rejected: {}",
+                intake.received_at,
+                response.status()
             );
-            return StatusCode::TOO_MANY_REQUESTS;
         }
+        if let Err(e) = state.job_store.delete_intake(intake.id).await {
+            error!("Failed to delete processed webhook {}: {}", intake.id, e);
+        }
+    }
+}
 
-        // Per-project webhook signature validation if required
-        if project.needs_webhook_secret() {
-            let signature_opt = headers
-                .get("X-Hub-Signature-256")
-                .and_then(|v| v.to_str().ok());
-            if signature_opt.is_none() {
-                error!(
-                    "Project '{}' requires webhook secret, but no signature header supplied.",
-                    project.name
-                );
-                return StatusCode::UNAUTHORIZED;
-            }
-            if !project.has_valid_secret() {
-                error!(
-                    "Project '{}' requires webhook secret, but none was configured.",
-                    project.name
-                );
-                return StatusCode::INTERNAL_SERVER_ERROR;
-            }
-            let signature = signature_opt.unwrap();
-            let secret = project.webhook_secret.as_ref().unwrap();
-            let valid = verify_github_signature(secret, &body, signature);
-            if !valid {
-                error!(
-                    "Signature verification failed for project '{}'!",
-                    project.name
-                );
-                return StatusCode::UNAUTHORIZED;
+/// Handle a webhook event: find the projects it triggers and dispatch it
+async fn handle_event(
+    state: &SharedState,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+    client_ip: ClientIp,
+    dry_run: bool,
+) -> Response {
+    // Only handle push (and GitHub pull request and release) events.
+    // Requests of no known provider may be generic pushes.
+    let (provider, kind) = match Provider::detect(headers, body) {
+        Some((provider, event)) => match provider.event_kind(&event) {
+            Some(kind) => (provider, kind),
+            None => {
+                info!("Not push event; Received {:?} event", event);
+                return forward::unmatched(state, headers, body).await;
             }
+        },
+        None => (Provider::Generic, EventKind::Push),
+    };
+
+    // Parse body as JSON and normalize the provider's push payload
+    let payload: serde_json::Value = match serde_json::from_slice(body) {
+        Ok(v) => v,
+        Err(e) if provider == Provider::Generic => {
+            info!("Not a known push event and not JSON: {:?}", e);
+            return forward::unmatched(state, headers, body).await;
+        }
+        Err(e) => {
+            info!("Could not parse JSON body: {:?}", e);
+            return StatusCode::BAD_REQUEST.into_response();
         }
+    };
+    debug!("{:#?}", &payload);
 
-        // Extract webhook data from payload
-        let commit_sha = payload
-            .get("after")
-            .and_then(|v| v.as_str())
-            .map(String::from);
-        let commit_message = payload
-            .get("head_commit")
-            .and_then(|c| c.get("message"))
-            .and_then(|v| v.as_str())
-            .map(|s| {
-                const MAX_COMMIT_MSG_LEN: usize = 500;
-                if s.len() > MAX_COMMIT_MSG_LEN {
-                    format!("{}... (truncated)", &s[..MAX_COMMIT_MSG_LEN])
-                } else {
-                    s.to_string()
-                }
-            });
-        let commit_author_name = payload
-            .get("head_commit")
-            .and_then(|c| c.get("author"))
-            .and_then(|a| a.get("name"))
-            .and_then(|v| v.as_str())
-            .map(String::from);
-
-        // Create a new job with webhook data
-        let job = if dry_run {
-            Job::from_webhook_dry_run(
-                repo_name.to_string(),
-                branch_name.to_string(),
-                commit_sha.clone(),
-                commit_message.clone(),
-                commit_author_name.clone(),
-            )
-        } else {
-            Job::from_webhook(
-                repo_name.to_string(),
-                branch_name.to_string(),
-                commit_sha.clone(),
-                commit_message.clone(),
-                commit_author_name.clone(),
-            )
+    let pushes = if provider == Provider::Generic {
+        let pushes = find_generic_pushes(&state.config.load(), &payload, uri.path());
+        if pushes.is_empty() {
+            warn!("No provider header and no matching generic project, skipping.");
+        }
+        pushes
+    } else if kind == EventKind::Release {
+        let Some(push) = PushEvent::from_github_release(&payload) else {
+            info!("Ignoring release event (not published)");
+            return forward::unmatched(state, headers, body).await;
         };
-        let job_id = job.id.clone();
+        let projects = find_published_release_projects(
+            &state.config.load(),
+            provider,
+            &push.repo_name,
+            push.repo_full_name.as_deref(),
+            push.push_ref.name(),
+            uri.path(),
+        );
+        if projects.is_empty() {
+            warn!(
+                "No project deploys releases of repo '{}', skipping release '{}'.",
+                push.repo_name,
+                push.push_ref.name()
+            );
+        }
+        projects
+            .into_iter()
+            .map(|project| (project, push.clone()))
+            .collect()
+    } else if kind == EventKind::PullRequest {
+        let Some(push) = PushEvent::from_github_pull_request(&payload) else {
+            info!("Ignoring pull request event (not opened or synchronized)");
+            return forward::unmatched(state, headers, body).await;
+        };
+        let base_branch = push.pull_request.as_ref().map(|pr| pr.base_branch.as_str());
+        let projects = find_pull_request_projects(
+            &state.config.load(),
+            provider,
+            &push.repo_name,
+            push.repo_full_name.as_deref(),
+            base_branch.unwrap_or_default(),
+            uri.path(),
+        );
+        if projects.is_empty() {
+            warn!(
+                "No project validates pull requests for repo '{}' into '{}', skipping.",
+                push.repo_name,
+                base_branch.unwrap_or_default()
+            );
+        }
+        projects
+            .into_iter()
+            .map(|project| (project, push.clone()))
+            .collect()
+    } else {
+        let Some(push) = PushEvent::parse(provider, &payload) else {
+            error!("No ref or repository name in push event payload");
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let projects = match_projects(state, provider, &push, uri.path());
+        if projects.is_empty() {
+            warn!(
+                "No matching project for repo '{}' and branch '{}', skipping.",
+                push.repo_name,
+                push.push_ref.name()
+            );
+        }
+        projects
+            .into_iter()
+            .map(|project| (project, push.clone()))
+            .collect()
+    };
 
-        // Add job to store
-        if let Err(e) = state.job_store.create_job(&job).await {
-            error!("Failed to create job in database: {}", e);
-            return StatusCode::INTERNAL_SERVER_ERROR;
+    match pushes.as_slice() {
+        [] => forward::unmatched(state, headers, body).await,
+        [(project, push)] => {
+            dispatch_push(state, project, push, headers, body, client_ip, dry_run).await
         }
+        _ => {
+            // Several matching projects: one job each, with each project's
+            // status in the body
+            let mut statuses = Vec::new();
+            let mut results = Vec::new();
+            for (project, push) in &pushes {
+                let response =
+                    dispatch_push(state, project, push, headers, body, client_ip, dry_run).await;
+                statuses.push(response.status());
+                results.push(json!({
+                    "project": project.name,
+                    "status": response.status().as_u16(),
+                }));
+            }
+            let status = combined_status(&statuses);
+            (status, Json(json!({ "projects": results }))).into_response()
+        }
+    }
+}
 
-        if dry_run {
-            info!(
-                "[DRY_RUN] Created job {} for project '{}' branch '{}'",
-                job_id, repo_name, branch_name
+/// Status code of a webhook that triggered several projects: their common
+/// status, or `207 Multi-Status` if they differ and any project got a job.
+/// That is a success, so the delivery stays recorded and a redelivery can't
+/// start those jobs again. If none got one, the worst rejection is reported
+/// and the delivery can be redelivered.
+fn combined_status(statuses: &[StatusCode]) -> StatusCode {
+    let created_job =
+        |status: &StatusCode| matches!(*status, StatusCode::OK | StatusCode::ACCEPTED);
+    match statuses {
+        [first, rest @ ..] if rest.iter().all(|status| status == first) => *first,
+        _ if statuses.iter().any(created_job) => StatusCode::MULTI_STATUS,
+        _ => statuses
+            .iter()
+            .copied()
+            .filter(|status| !status.is_success())
+            .max()
+            .unwrap_or(StatusCode::NO_CONTENT),
+    }
+}
+
+/// Find the projects matching a push's repo name, branch (or release tag)
+/// and, for monorepo sub-projects, the changed paths
+fn match_projects(
+    state: &SharedState,
+    provider: Provider,
+    push: &PushEvent,
+    webhook_path: &str,
+) -> Vec<ProjectConfig> {
+    let config = state.config.load();
+    let changed_files = push.changed_files.as_deref();
+    match &push.push_ref {
+        PushRef::Branch(branch) => find_matching_projects(
+            &config,
+            provider,
+            &push.repo_name,
+            push.repo_full_name.as_deref(),
+            branch,
+            webhook_path,
+            changed_files,
+        ),
+        PushRef::Tag(tag) => {
+            if push.deleted {
+                info!("Ignoring deletion of tag '{}'", tag);
+                Vec::new()
+            } else {
+                find_release_projects(
+                    &config,
+                    provider,
+                    &push.repo_name,
+                    push.repo_full_name.as_deref(),
+                    tag,
+                    webhook_path,
+                    changed_files,
+                )
+            }
+        }
+    }
+}
+
+/// Run a push through one matched project's checks (source IP, rate limit,
+/// signature, pusher, author, force push and merge filters, freeze,
+/// maintenance) and create its job
+async fn dispatch_push(
+    state: &SharedState,
+    project: &ProjectConfig,
+    push: &PushEvent,
+    headers: &HeaderMap,
+    body: &[u8],
+    client_ip: ClientIp,
+    dry_run: bool,
+) -> Response {
+    let branch_name = push.push_ref.name();
+    if let Some(allowed) = &project.allowed_ips
+        && !client_ip.allowed_by(allowed)
+    {
+        warn!(
+            "Rejecting push from {} for project '{}': not in allowed_ips",
+            client_ip, project.name
+        );
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    // check rate limits first
+    let rate_limit_sec = project.get_rate_limit();
+    let rate_limit_window = project.get_rate_limit_window();
+    let mut quota =
+        state
+            .rate_limiter
+            .lock()
+            .await
+            .check(&project.name, rate_limit_sec, rate_limit_window);
+    rate_limit::persist(state, &project.name).await;
+
+    // An urgent redeploy can skip the limit with the bypass token
+    let rate_limit_bypassed = !quota.allowed && has_bypass_token(state, headers);
+    if rate_limit_bypassed {
+        warn!(
+            "Rate limit of project '{}' exceeded; bypassed with the bypass token from {}",
+            project.name, client_ip
+        );
+        quota.retry_after = None;
+    } else if !quota.allowed {
+        warn!(
+            "Too many requests for project {:?} - {:?} requests per {:?} seconds",
+            &project.name, rate_limit_sec, rate_limit_window
+        );
+        return with_quota(StatusCode::TOO_MANY_REQUESTS, &quota);
+    }
+
+    // Per-project webhook signature (GitHub, generic), token (GitLab) or
+    // Basic auth password (Azure DevOps) validation if required. Deliveries
+    // of the GitHub App are signed with its secret.
+    let app_secret = project
+        .github_app()
+        .then(|| state.config.load().server.github_app.clone())
+        .flatten()
+        .and_then(|app| app.webhook_secret());
+    if project.needs_webhook_secret() || app_secret.is_some() {
+        let provider = project.provider();
+        let signature_opt = headers
+            .get(provider.secret_header())
+            .and_then(|v| v.to_str().ok());
+        // Legacy SHA-1 signature, only checked without a SHA-256 one
+        let sha1_signature = provider
+            .sha1_signature_header()
+            .filter(|_| project.allow_sha1_signature())
+            .and_then(|header| headers.get(header))
+            .and_then(|v| v.to_str().ok());
+        if signature_opt.is_none() && sha1_signature.is_none() {
+            error!(
+                "Project '{}' requires webhook secret, but no signature header supplied.",
+                project.name
             );
-        } else {
-            info!(
-                "Created job {} for project '{}' branch '{}'",
-                job_id, repo_name, branch_name
+            return with_quota(StatusCode::UNAUTHORIZED, &quota);
+        }
+        let mut secrets = project.webhook_secrets();
+        secrets.extend(app_secret.as_deref());
+        if secrets.is_empty() {
+            error!(
+                "Project '{}' requires webhook secret, but none was configured.",
+                project.name
+            );
+            return with_quota(StatusCode::INTERNAL_SERVER_ERROR, &quota);
+        }
+        // Any of the project's secrets will do, so they can be rotated
+        let valid = secrets
+            .into_iter()
+            .any(|secret| match (signature_opt, provider) {
+                (Some(signature), Provider::Github | Provider::Generic) => {
+                    verify_github_signature(secret, body, signature)
+                }
+                (Some(token), Provider::Gitlab) => verify_gitlab_token(secret, token),
+                (Some(authorization), Provider::AzureDevops) => {
+                    verify_basic_auth_password(secret, authorization)
+                }
+                (None, _) => sha1_signature
+                    .is_some_and(|signature| verify_github_sha1_signature(secret, body, signature)),
+            });
+        if !valid {
+            error!(
+                "Signature verification failed for project '{}'!",
+                project.name
             );
+            return with_quota(StatusCode::UNAUTHORIZED, &quota);
         }
+    }
 
-        // Broadcast job created event
-        let _ = state.job_events.send(JobEvent {
-            event_type: "created".to_string(),
-            job_id: job_id.clone(),
-            project_name: repo_name.to_string(),
-            branch: branch_name.to_string(),
-            timestamp: Utc::now().to_rfc3339(),
-        });
-
-        // Build webhook data for pipeline
-        let webhook_data = WebhookData {
-            project_name: repo_name.to_string(),
-            branch: branch_name.to_string(),
-            repo_path: project.repo_path.clone(),
-            commit_sha,
-            commit_message,
-            commit_author_name,
-            commit_author_email: payload
-                .get("head_commit")
-                .and_then(|c| c.get("author"))
-                .and_then(|a| a.get("email"))
-                .and_then(|v| v.as_str())
-                .map(String::from),
-            pusher_name: payload
-                .get("pusher")
-                .and_then(|p| p.get("name"))
-                .and_then(|v| v.as_str())
-                .map(String::from),
-            repository_url: payload
-                .get("repository")
-                .and_then(|r| r.get("html_url"))
-                .and_then(|v| v.as_str())
-                .map(String::from),
-        };
+    // A deleted branch only runs the project's teardown script
+    if push.deleted && project.branch_deleted_script.is_none() {
+        info!(
+            "Ignoring deletion of branch '{}' for project '{}' (no branch_deleted_script)",
+            branch_name, project.name
+        );
+        return with_quota(StatusCode::NO_CONTENT, &quota);
+    }
+
+    // Pushes by ignored pushers (e.g. bots) or other authors don't trigger
+    if project.ignores_pusher(push.pusher_name.as_deref()) {
+        info!(
+            "Ignoring push to '{}' by '{}' for project '{}' (ignore_pushers)",
+            branch_name,
+            push.pusher_name.as_deref().unwrap_or_default(),
+            project.name
+        );
+        return with_quota(StatusCode::NO_CONTENT, &quota);
+    }
+    if !push.deleted
+        && !project.accepts_author(
+            push.commit_author_name.as_deref(),
+            push.commit_author_email.as_deref(),
+        )
+    {
+        info!(
+            "Ignoring push to '{}' with head commit by '{}' for project '{}' (only_authors)",
+            branch_name,
+            push.commit_author_name
+                .as_deref()
+                .unwrap_or("unknown author"),
+            project.name
+        );
+        return with_quota(StatusCode::NO_CONTENT, &quota);
+    }
 
-        // Get shared state for background task
-        let shared_state = state.clone();
-
-        // Spawn a background async task to process job
-        tokio::spawn(async move {
-            // Acquire the job lock. Only one job will run at a time.
-            let _guard = shared_state.job_execution_lock.lock().await;
-
-            // Mark job as running
-            if let Err(e) = shared_state
-                .job_store
-                .update_job_status(&job_id, JobStatus::Running)
-                .await
-            {
-                error!("Failed to update job status to running: {}", e);
-                return;
+    if push.forced && project.ignore_forced() {
+        info!(
+            "Ignoring force push to '{}' for project '{}' (ignore_forced)",
+            branch_name, project.name
+        );
+        return with_quota(StatusCode::NO_CONTENT, &quota);
+    }
+    // `[skip ci]` in the head commit message: record the push without running it
+    if let Some(marker) = push
+        .commit_message
+        .as_deref()
+        .and_then(|message| project.skip_ci_marker(message))
+    {
+        info!(
+            "Commit message contains '{}'; skipping push to '{}' for project '{}'",
+            marker, branch_name, project.name
+        );
+        let status = match record_skipped_job(state, project, push, marker, dry_run).await {
+            Ok(job_id) => {
+                store_payload(state, &job_id, body).await;
+                StatusCode::OK
             }
+            Err(status) => status,
+        };
+        return with_quota(status, &quota);
+    }
 
-            // Handle dry run - skip actual execution
-            if dry_run {
+    // Deploy freeze: reject, or hold the job until the freeze ends.
+    // Dry runs and pull request checks don't deploy, so they are never frozen.
+    let mut hold_until = None;
+    if !dry_run
+        && push.pull_request.is_none()
+        && let Some((until, reason)) = project.frozen_until(Utc::now())
+    {
+        let reason = reason.map(|r| format!(" ({})", r)).unwrap_or_default();
+        match project.freeze_action() {
+            FreezeAction::Reject => {
+                warn!(
+                    "Deploy freeze for project '{}' until {}{}; rejecting push to '{}'",
+                    project.name, until, reason, branch_name
+                );
+                return with_quota(StatusCode::LOCKED, &quota);
+            }
+            FreezeAction::Queue => {
                 info!(
-                    "[DRY_RUN] Job {} - Would execute pipeline for project '{}' branch '{}'",
-                    job_id, webhook_data.project_name, webhook_data.branch
+                    "Deploy freeze for project '{}' until {}{}; holding push to '{}'",
+                    project.name, until, reason, branch_name
                 );
+                hold_until = Some(until);
+            }
+        }
+    }
 
-                let main_script = project.get_run_script_for_branch(&webhook_data.branch);
-                let now = Utc::now();
-
-                // Create simulated log entries for what would run
-                let mut sequence = 0;
-
-                // Git fetch
-                let git_fetch_log = JobLog {
-                    id: None,
-                    job_id: job_id.clone(),
-                    sequence,
-                    log_type: "git_fetch".to_string(),
-                    command: Some("git fetch origin".to_string()),
-                    started_at: now,
-                    completed_at: Some(now),
-                    duration_ms: Some(0),
-                    exit_code: Some(0),
-                    output: Some("[DRY_RUN] Skipped".to_string()),
-                    status: "skipped".to_string(),
-                };
-                let _ = shared_state.job_store.add_log(&git_fetch_log).await;
-                sequence += 1;
-
-                // Git reset
-                let git_reset_log = JobLog {
-                    id: None,
-                    job_id: job_id.clone(),
-                    sequence,
-                    log_type: "git_reset".to_string(),
-                    command: Some(format!("git reset --hard origin/{}", webhook_data.branch)),
-                    started_at: now,
-                    completed_at: Some(now),
-                    duration_ms: Some(0),
-                    exit_code: Some(0),
-                    output: Some("[DRY_RUN] Skipped".to_string()),
-                    status: "skipped".to_string(),
-                };
-                let _ = shared_state.job_store.add_log(&git_reset_log).await;
-                sequence += 1;
-
-                // Pre-script (if configured)
-                if let Some(pre_script) = &project.pre_script {
-                    let pre_log = JobLog {
-                        id: None,
-                        job_id: job_id.clone(),
-                        sequence,
-                        log_type: "pre_script".to_string(),
-                        command: Some(pre_script.clone()),
-                        started_at: now,
-                        completed_at: Some(now),
-                        duration_ms: Some(0),
-                        exit_code: Some(0),
-                        output: Some("[DRY_RUN] Skipped".to_string()),
-                        status: "skipped".to_string(),
-                    };
-                    let _ = shared_state.job_store.add_log(&pre_log).await;
-                    sequence += 1;
-                }
+    // Maintenance mode: the job is created but workers hold it (see
+    // `claim_next_queued`); this only affects the response and log
+    let in_maintenance = !dry_run
+        && matches!(
+            state.job_store.get_maintenance(&project.name).await,
+            Ok(Some(_))
+        );
+    if in_maintenance {
+        info!(
+            "Project '{}' is in maintenance mode; holding push to '{}'",
+            project.name, branch_name
+        );
+    }
 
-                // Main script
-                let main_log = JobLog {
-                    id: None,
-                    job_id: job_id.clone(),
-                    sequence,
-                    log_type: "main_script".to_string(),
-                    command: Some(main_script.to_string()),
-                    started_at: now,
-                    completed_at: Some(now),
-                    duration_ms: Some(0),
-                    exit_code: Some(0),
-                    output: Some("[DRY_RUN] Skipped".to_string()),
-                    status: "skipped".to_string(),
-                };
-                let _ = shared_state.job_store.add_log(&main_log).await;
-                sequence += 1;
-
-                // Post-success script (if configured)
-                if let Some(post_success) = &project.post_success_script {
-                    let post_log = JobLog {
-                        id: None,
-                        job_id: job_id.clone(),
-                        sequence,
-                        log_type: "post_success_script".to_string(),
-                        command: Some(post_success.clone()),
-                        started_at: now,
-                        completed_at: Some(now),
-                        duration_ms: Some(0),
-                        exit_code: Some(0),
-                        output: Some("[DRY_RUN] Skipped".to_string()),
-                        status: "skipped".to_string(),
-                    };
-                    let _ = shared_state.job_store.add_log(&post_log).await;
-                    sequence += 1;
-                }
+    let status = match create_push_job(
+        state,
+        project,
+        push,
+        dry_run,
+        hold_until,
+        rate_limit_bypassed,
+        Some(body),
+    )
+    .await
+    {
+        Ok(_) => {
+            if hold_until.is_some() || in_maintenance {
+                StatusCode::ACCEPTED
+            } else {
+                StatusCode::OK
+            }
+        }
+        Err(status) => status,
+    };
+    with_quota(status, &quota)
+}
 
-                // Post-always script (if configured)
-                if let Some(post_always) = &project.post_always_script {
-                    let post_log = JobLog {
-                        id: None,
-                        job_id: job_id.clone(),
-                        sequence,
-                        log_type: "post_always_script".to_string(),
-                        command: Some(post_always.clone()),
-                        started_at: now,
-                        completed_at: Some(now),
-                        duration_ms: Some(0),
-                        exit_code: Some(0),
-                        output: Some("[DRY_RUN] Skipped".to_string()),
-                        status: "skipped".to_string(),
-                    };
-                    let _ = shared_state.job_store.add_log(&post_log).await;
-                    let _ = sequence; // silence unused warning
-                }
+/// Keep the webhook body with the job it created (`GET /api/jobs/{id}/payload`)
+async fn store_payload(state: &SharedState, job_id: &str, body: &[u8]) {
+    if let Err(e) = state.job_store.set_job_payload(job_id, body).await {
+        error!("Failed to store webhook payload of job {}: {}", job_id, e);
+    }
+}
 
-                let dry_run_output = format!(
-                    "[DRY_RUN] Pipeline simulation for project '{}' branch '{}'\n\
-                     \n\
-                     Webhook data:\n\
-                     - Commit SHA: {}\n\
-                     - Commit message: {}\n\
-                     - Author: {}\n\
-                     \n\
-                     No actual commands were executed. See Timeline for details.",
-                    webhook_data.project_name,
-                    webhook_data.branch,
-                    webhook_data.commit_sha.as_deref().unwrap_or("(none)"),
-                    webhook_data.commit_message.as_deref().unwrap_or("(none)"),
-                    webhook_data.commit_author_name.as_deref().unwrap_or("(none)"),
-                );
+/// Returns true if the request carries the `[server]` rate limit bypass token
+fn has_bypass_token(state: &SharedState, headers: &HeaderMap) -> bool {
+    let Some(token) = headers
+        .get(rate_limit::BYPASS_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return false;
+    };
+    let config = state.config.load();
+    let Some(expected) = &config.server.rate_limit_bypass_token_sha256 else {
+        warn!(
+            "{} sent but no rate limit bypass token is configured",
+            rate_limit::BYPASS_TOKEN_HEADER
+        );
+        return false;
+    };
+    let valid = rate_limit::bypass_token_matches(expected, token);
+    if !valid {
+        warn!("Invalid {}", rate_limit::BYPASS_TOKEN_HEADER);
+    }
+    valid
+}
 
-                // Broadcast running event
-                let _ = shared_state.job_events.send(JobEvent {
-                    event_type: "running".to_string(),
-                    job_id: job_id.clone(),
-                    project_name: webhook_data.project_name.clone(),
-                    branch: webhook_data.branch.clone(),
-                    timestamp: Utc::now().to_rfc3339(),
-                });
-
-                // Mark as success with dry run output
-                if let Err(e) = shared_state
-                    .job_store
-                    .complete_job(&job_id, JobStatus::Success, Some(dry_run_output), None, Utc::now())
-                    .await
-                {
-                    error!("[DRY_RUN] Failed to mark job as success: {}", e);
-                }
+/// Attach the project's remaining rate limit quota to a webhook response
+fn with_quota(status: StatusCode, quota: &RateLimitStatus) -> Response {
+    let mut response = status.into_response();
+    let headers = response.headers_mut();
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(quota.limit));
+    headers.insert("X-RateLimit-Remaining", HeaderValue::from(quota.remaining));
+    headers.insert(
+        "X-RateLimit-Reset",
+        HeaderValue::from(quota.reset_after.as_secs_f64().ceil() as u64),
+    );
+    if let Some(retry_after) = quota.retry_after {
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+        );
+    }
+    response
+}
 
-                info!("[DRY_RUN] Job {} completed successfully.", job_id);
-                let _ = shared_state.job_events.send(JobEvent {
-                    event_type: "success".to_string(),
-                    job_id: job_id.clone(),
-                    project_name: webhook_data.project_name.clone(),
-                    branch: webhook_data.branch.clone(),
-                    timestamp: Utc::now().to_rfc3339(),
-                });
+/// Create and enqueue a job for a push payload that matched `project`.
+/// Shared by the webhook handler and the simulation endpoint.
+pub(crate) async fn create_push_job(
+    state: &SharedState,
+    project: &ProjectConfig,
+    push: &PushEvent,
+    dry_run: bool,
+    hold_until: Option<DateTime<Utc>>,
+    rate_limit_bypassed: bool,
+    payload: Option<&[u8]>,
+) -> Result<String, StatusCode> {
+    let branch_name = push.push_ref.name();
+    let webhook_data = push.webhook_data(project);
+
+    // Create a new job with webhook data
+    let mut job = push_job(project, branch_name, &webhook_data, dry_run);
+    job.rate_limit_bypassed = rate_limit_bypassed;
+    if webhook_data.branch_deleted {
+        job.commit_message = Some(format!("Branch '{}' deleted", branch_name));
+    }
+    let job_id = job.id.clone();
 
-                return;
-            }
+    // Add job to store
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!("Failed to create job in database: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    // Stored before the job is queued, so its scripts can read it
+    if let Some(body) = payload {
+        store_payload(state, &job_id, body).await;
+    }
+    // Linked before the job is queued, so queuing it marks the request
+    // as processed
+    if let Ok(intake_id) = INTAKE_ID.try_with(|id| *id)
+        && let Err(e) = state.job_store.set_job_intake(&job_id, intake_id).await
+    {
+        error!("Failed to link job {} to its webhook: {}", job_id, e);
+    }
 
-            info!(
-                "Job {} - Push event for project '{}' branch '{}'. Starting job pipeline.",
-                job_id, webhook_data.project_name, webhook_data.branch
-            );
+    if dry_run {
+        info!(
+            "[DRY_RUN] Created job {} for project '{}' branch '{}'",
+            job_id, project.name, branch_name
+        );
+    } else {
+        info!(
+            "Created job {} for project '{}' branch '{}'",
+            job_id, project.name, branch_name
+        );
+    }
 
-            // Broadcast job running event
-            let _ = shared_state.job_events.send(JobEvent {
-                event_type: "running".to_string(),
-                job_id: job_id.clone(),
-                project_name: webhook_data.project_name.clone(),
-                branch: webhook_data.branch.clone(),
-                timestamp: Utc::now().to_rfc3339(),
-            });
+    // Broadcast job created event
+    state.job_events.send(JobEvent {
+        event_type: "created".to_string(),
+        job_id: job_id.clone(),
+        project_name: project.name.clone(),
+        branch: branch_name.to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    // The queued config snapshot runs the release script as the main script
+    let mut project = project.clone();
+    if webhook_data.tag.is_some()
+        && let Some(script) = project.release_script.clone()
+    {
+        project.run_script = script;
+        project.steps = None;
+        project.branch_scripts = None;
+    }
+    // ...and pull request checks `pr_script`, deploying nothing: no release
+    // directory and no mirror pushes
+    if webhook_data.pull_request.is_some()
+        && let Some(script) = project.pr_script.clone()
+    {
+        project.run_script = script;
+        project.steps = None;
+        project.branch_scripts = None;
+        project.release = None;
+        project.mirrors = None;
+    }
+    // ...and branch deletions the teardown script, on a branch that is gone
+    if webhook_data.branch_deleted
+        && let Some(script) = project.branch_deleted_script.clone()
+    {
+        project.run_script = script;
+        project.steps = None;
+        project.branch_scripts = None;
+        project.release = None;
+        project.mirrors = None;
+    }
 
-            // Run the complete pipeline with hooks
-            match run_job_pipeline(
-                &project,
-                &webhook_data,
-                &shared_state.job_store,
+    // Persist the job in the queue; a dispatcher worker will pick it up
+    if let Err(e) = state
+        .dispatcher
+        .enqueue(
+            &state.job_store,
+            &job_id,
+            &project,
+            &webhook_data,
+            hold_until,
+        )
+        .await
+    {
+        error!("Failed to enqueue job {}: {}", job_id, e);
+        let _ = state
+            .job_store
+            .complete_job(
                 &job_id,
-                shared_state.log_chunks.clone(),
+                JobStatus::Failed,
+                None,
+                Some(e.to_string()),
+                Utc::now(),
             )
-            .await
-            {
-                Ok(output) => {
-                    info!("Job {} completed successfully.", job_id);
-                    if let Err(e) = shared_state
-                        .job_store
-                        .complete_job(&job_id, JobStatus::Success, Some(output), None, Utc::now())
-                        .await
-                    {
-                        error!("Failed to mark job as success: {}", e);
-                    }
-                    let _ = shared_state.job_events.send(JobEvent {
-                        event_type: "success".to_string(),
-                        job_id: job_id.clone(),
-                        project_name: webhook_data.project_name.clone(),
-                        branch: webhook_data.branch.clone(),
-                        timestamp: Utc::now().to_rfc3339(),
-                    });
-                }
-                Err(e) => {
-                    error!("Job {} failed: {}", job_id, e);
-                    if let Err(db_err) = shared_state
-                        .job_store
-                        .complete_job(
-                            &job_id,
-                            JobStatus::Failed,
-                            None,
-                            Some(e.to_string()),
-                            Utc::now(),
-                        )
-                        .await
-                    {
-                        error!("Failed to mark job as failed: {}", db_err);
-                    }
-                    let _ = shared_state.job_events.send(JobEvent {
-                        event_type: "failed".to_string(),
-                        job_id: job_id.clone(),
-                        project_name: webhook_data.project_name.clone(),
-                        branch: webhook_data.branch.clone(),
+            .await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // Only the newest queued push of a branch needs to run. A push that may
+    // still be skipped (`only_merge_commits`) doesn't supersede others.
+    if !dry_run && project.cancel_superseded() && !webhook_data.require_merge_commit {
+        match state.job_store.cancel_superseded_jobs(&job).await {
+            Ok(cancelled) => {
+                for id in cancelled {
+                    info!("Cancelled job {}, superseded by job {}", id, job_id);
+                    state.job_events.send(JobEvent {
+                        event_type: "cancelled".to_string(),
+                        job_id: id,
+                        project_name: project.name.clone(),
+                        branch: branch_name.to_string(),
                         timestamp: Utc::now().to_rfc3339(),
                     });
                 }
             }
-        });
+            Err(e) => error!("Failed to cancel jobs superseded by {}: {}", job_id, e),
+        }
+    }
 
-        StatusCode::OK
-    } else {
-        warn!(
-            "No matching project for repo '{}' and branch '{}', skipping.",
-            repo_name, branch_name
-        );
-        StatusCode::NO_CONTENT
+    Ok(job_id)
+}
+
+/// Record a push whose head commit message carries a skip marker as a
+/// `skipped` job, so it shows up in the history without running
+async fn record_skipped_job(
+    state: &SharedState,
+    project: &ProjectConfig,
+    push: &PushEvent,
+    marker: &str,
+    dry_run: bool,
+) -> Result<String, StatusCode> {
+    let branch_name = push.push_ref.name();
+    let webhook_data = push.webhook_data(project);
+    let job = push_job(project, branch_name, &webhook_data, dry_run);
+    let job_id = job.id.clone();
+
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!("Failed to create job in database: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let output = format!("Skipped: commit message contains '{}'", marker);
+    if let Err(e) = state
+        .job_store
+        .complete_job(&job_id, JobStatus::Skipped, Some(output), None, Utc::now())
+        .await
+    {
+        error!("Failed to complete skipped job {}: {}", job_id, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+
+    state.job_events.send(JobEvent {
+        event_type: "skipped".to_string(),
+        job_id: job_id.clone(),
+        project_name: project.name.clone(),
+        branch: branch_name.to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    Ok(job_id)
+}
+
+/// Build the job for a push that matched `project`
+fn push_job(
+    project: &ProjectConfig,
+    branch_name: &str,
+    webhook_data: &WebhookData,
+    dry_run: bool,
+) -> Job {
+    let mut job = if dry_run {
+        Job::from_webhook_dry_run(
+            project.name.clone(),
+            branch_name.to_string(),
+            webhook_data.commit_sha.clone(),
+            webhook_data.commit_message.clone(),
+            webhook_data.commit_author_name.clone(),
+        )
+    } else {
+        Job::from_webhook(
+            project.name.clone(),
+            branch_name.to_string(),
+            webhook_data.commit_sha.clone(),
+            webhook_data.commit_message.clone(),
+            webhook_data.commit_author_name.clone(),
+        )
+    };
+    job.tag = webhook_data.tag.clone();
+    job.pull_request = webhook_data.pull_request.as_ref().map(|pr| pr.number);
+    job
 }
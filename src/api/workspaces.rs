@@ -0,0 +1,63 @@
+//! Job workspace endpoint: remove a workspace kept after its job ended
+
+use axum::{
+    Json,
+    extract::{Path, State as AxumState},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use tracing::info;
+
+use crate::SharedState;
+use crate::workspace;
+
+/// DELETE /api/jobs/{id}/workspace - Remove the workspace kept for a job
+pub async fn delete_job_workspace(
+    AxumState(state): AxumState<SharedState>,
+    Path(id): Path<String>,
+) -> Response {
+    let job = match state.job_store.get_job(&id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Job not found".to_string()),
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    if state.dispatcher.is_running(&id) {
+        return error_response(
+            StatusCode::CONFLICT,
+            "Job is running; its workspace is removed when it ends".to_string(),
+        );
+    }
+
+    let (path, repo_path) = {
+        let config = state.config.load();
+        let repo_path = config
+            .project
+            .iter()
+            .find(|p| p.name == job.project_name)
+            .map(|p| p.repo_path.clone());
+        let path = workspace::job_path(config.server.workspace_dir(), &job.project_name, &id);
+        (path, repo_path)
+    };
+    let removal = {
+        let path = path.clone();
+        let id = id.clone();
+        move || workspace::remove(&path, repo_path.as_deref(), &id)
+    };
+    let removed = tokio::task::spawn_blocking(removal)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()).into())
+        .and_then(|removed| removed);
+    match removed {
+        Ok(true) => {
+            info!("Removed workspace of job {}", id);
+            Json(json!({ "job_id": id, "removed": path.display().to_string() })).into_response()
+        }
+        Ok(false) => error_response(StatusCode::NOT_FOUND, "Job has no workspace".to_string()),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+fn error_response(status: StatusCode, error: String) -> Response {
+    (status, Json(json!({"error": error}))).into_response()
+}
@@ -0,0 +1,116 @@
+//! Build artifacts (`artifacts`)
+//!
+//! After a successful main script, the files of the script directory matching
+//! the project's `artifacts` globs are copied to
+//! `<[server] artifacts_dir>/<job id>/`, under their path relative to the
+//! script directory, and recorded with the job. They can be downloaded after
+//! later jobs have rebuilt the checkout, until the job is deleted.
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+use crate::error::{CicdError, Result};
+use crate::path_filter::{self, PathFilter};
+
+/// Directory artifacts are stored in, relative to the working directory,
+/// unless `[server] artifacts_dir` is set
+pub const DEFAULT_ARTIFACTS_DIR: &str = "cicd_artifacts";
+
+/// A file kept from a job
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Artifact {
+    /// Path relative to the script directory, e.g. `dist/app.js`
+    pub name: String,
+    /// Size in bytes
+    pub size: i64,
+}
+
+/// Directory holding the artifacts of job `job_id`
+pub fn job_dir(artifacts_dir: &str, job_id: &str) -> PathBuf {
+    Path::new(artifacts_dir).join(job_id)
+}
+
+/// Copy the files below `work_dir` matching `patterns` to `dest`, keeping
+/// their relative paths. `.git` and symlinks are skipped, so a script can't
+/// make the server copy files from outside its directory.
+pub fn collect(patterns: &PathFilter, work_dir: &Path, dest: &Path) -> Result<Vec<Artifact>> {
+    let mut artifacts = Vec::new();
+    let mut dirs = vec![String::new()];
+    while let Some(dir) = dirs.pop() {
+        let path = work_dir.join(&dir);
+        let entries = fs::read_dir(&path).map_err(|e| artifact_error("list", &path, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| artifact_error("list", &path, e))?;
+            let file_type = entry
+                .file_type()
+                .map_err(|e| artifact_error("inspect", &entry.path(), e))?;
+            // Names that aren't UTF-8 can't be matched or downloaded
+            let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let name = match dir.as_str() {
+                "" => file_name,
+                dir => format!("{}/{}", dir, file_name),
+            };
+            if file_type.is_dir() {
+                if name != ".git" {
+                    dirs.push(name);
+                }
+            } else if file_type.is_file() && patterns.matches(&name) {
+                let target = dest.join(&name);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| artifact_error("create", parent, e))?;
+                }
+                let size = fs::copy(entry.path(), &target)
+                    .map_err(|e| artifact_error("copy", &entry.path(), e))?;
+                artifacts.push(Artifact {
+                    name,
+                    size: size as i64,
+                });
+            }
+        }
+    }
+    artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(artifacts)
+}
+
+/// Step output listing the collected files, and the globs that matched none
+pub fn report(patterns: &PathFilter, artifacts: &[Artifact]) -> String {
+    let mut report = format!("Collected {} artifact(s)\n", artifacts.len());
+    for artifact in artifacts {
+        report.push_str(&format!("  {} ({} bytes)\n", artifact.name, artifact.size));
+    }
+    for pattern in patterns.patterns() {
+        let pattern_name = pattern.trim_start_matches('/');
+        if !artifacts
+            .iter()
+            .any(|artifact| path_filter::glob_matches(pattern_name, &artifact.name))
+        {
+            report.push_str(&format!("Warning: no file matches '{}'\n", pattern));
+        }
+    }
+    report
+}
+
+/// Remove the artifacts of the given jobs
+pub fn remove(artifacts_dir: &str, job_ids: &[String]) {
+    for job_id in job_ids {
+        let dir = job_dir(artifacts_dir, job_id);
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => error!("Failed to remove artifacts {}: {}", dir.display(), e),
+        }
+    }
+}
+
+fn artifact_error(operation: &str, path: &Path, e: io::Error) -> CicdError {
+    CicdError::IoError(io::Error::new(
+        e.kind(),
+        format!("Failed to {} {}: {}", operation, path.display(), e),
+    ))
+}
@@ -0,0 +1,96 @@
+//! Optional HTTP Basic authentication for the UI and API.
+//!
+//! Webhook paths are never covered: GitHub can't send basic-auth credentials,
+//! and webhook requests are protected by their HMAC signature instead.
+
+use axum::{
+    extract::{Request, State as AxumState},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::SharedState;
+
+/// `[server.basic_auth]` credentials
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    /// Hex-encoded SHA-256 of the password (`printf '%s' 'password' | sha256sum`)
+    pub password_sha256: String,
+}
+
+impl BasicAuthConfig {
+    /// Returns true if the `Authorization` header value carries these credentials
+    pub fn verify(&self, authorization: &str) -> bool {
+        let Some(encoded) = authorization.strip_prefix("Basic ") else {
+            return false;
+        };
+        let Ok(decoded) = STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+        let Ok(credentials) = String::from_utf8(decoded) else {
+            return false;
+        };
+        let Some((username, password)) = credentials.split_once(':') else {
+            return false;
+        };
+        let Ok(expected) = hex::decode(self.password_sha256.trim()) else {
+            warn!("basic_auth.password_sha256 is not valid hex; rejecting all logins");
+            return false;
+        };
+
+        let digest = Sha256::digest(password.as_bytes());
+        // Evaluate both checks so timing doesn't reveal which one failed
+        let username_ok = constant_time_eq(username.as_bytes(), self.username.as_bytes());
+        let password_ok = constant_time_eq(&digest, &expected);
+        username_ok & password_ok
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Middleware requiring basic auth on everything except webhook paths,
+/// when `[server.basic_auth]` is configured
+pub async fn require_basic_auth(
+    AxumState(state): AxumState<SharedState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let config = state.config.load();
+    let Some(auth) = &config.server.basic_auth else {
+        return next.run(req).await;
+    };
+    if config.is_webhook_path(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| auth.verify(v));
+    if authorized {
+        return next.run(req).await;
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(
+            header::WWW_AUTHENTICATE,
+            "Basic realm=\"simple_git_cicd\", charset=\"UTF-8\"",
+        )],
+        "Unauthorized",
+    )
+        .into_response()
+}
@@ -0,0 +1,308 @@
+//! Directories kept between a project's jobs (`[[project.caches]]`)
+//!
+//! Dependency directories like `node_modules` or `~/.cargo/registry` take
+//! long to rebuild. After a successful main script, each cache's directory
+//! is copied to `<[server] cache_dir>/<project>/<name>`. Before the
+//! pre-script of a later job, a cache is copied back if its directory
+//! doesn't exist, e.g. in a fresh release directory. Caches over their size
+//! limit aren't saved.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CicdError, Result};
+
+/// Directory caches are stored in, relative to the working directory,
+/// unless `[server] cache_dir` is set
+pub const DEFAULT_CACHE_DIR: &str = "cicd_cache";
+
+/// Largest cache saved, by default
+const DEFAULT_MAX_SIZE_MB: u64 = 1024;
+
+/// A `[[project.caches]]` entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheConfig {
+    /// Name the cache is stored and purged under, e.g. `cargo`
+    pub name: String,
+    /// Directory to cache: relative to the script directory, absolute, or
+    /// in the scripts' home directory with `~/`
+    pub path: String,
+    /// Caches larger than this many MB aren't saved (default: 1024)
+    pub max_size_mb: Option<u64>,
+}
+
+impl CacheConfig {
+    pub fn max_size(&self) -> u64 {
+        self.max_size_mb.unwrap_or(DEFAULT_MAX_SIZE_MB) * 1024 * 1024
+    }
+
+    /// The cached directory, for scripts running in `work_dir` with `home`
+    /// as their home directory
+    pub fn target(&self, work_dir: &Path, home: Option<&str>) -> PathBuf {
+        match (self.path.strip_prefix("~/"), home) {
+            (Some(rest), Some(home)) => Path::new(home).join(rest),
+            _ => work_dir.join(&self.path),
+        }
+    }
+}
+
+/// Returns an error if a project's caches can't be stored side by side
+pub fn validate(project: &str, caches: &[CacheConfig]) -> std::result::Result<(), String> {
+    for (i, cache) in caches.iter().enumerate() {
+        if cache.name.is_empty()
+            || cache.name.starts_with('.')
+            || cache.name.contains(['/', '\\', '\0'])
+        {
+            return Err(format!(
+                "Project '{}' has an invalid cache name '{}'",
+                project, cache.name
+            ));
+        }
+        if cache.path.trim().is_empty() {
+            return Err(format!(
+                "Project '{}': cache '{}' needs a path",
+                project, cache.name
+            ));
+        }
+        if caches[..i].iter().any(|other| other.name == cache.name) {
+            return Err(format!(
+                "Project '{}' has two caches named '{}'",
+                project, cache.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Directory holding the caches of `project`
+pub fn project_dir(cache_dir: &str, project: &str) -> PathBuf {
+    Path::new(cache_dir).join(project)
+}
+
+/// The caches of one job: where each one's directory is and where it's
+/// stored
+#[derive(Debug, Clone)]
+pub struct JobCaches {
+    caches: Vec<(CacheConfig, PathBuf)>,
+    dir: PathBuf,
+    /// User and group restored files are given to, for scripts running as
+    /// another user
+    owner: Option<(u32, u32)>,
+}
+
+impl JobCaches {
+    pub fn new(
+        caches: &[CacheConfig],
+        dir: PathBuf,
+        work_dir: &Path,
+        home: Option<&str>,
+        owner: Option<(u32, u32)>,
+    ) -> Self {
+        Self {
+            caches: caches
+                .iter()
+                .map(|cache| (cache.clone(), cache.target(work_dir, home)))
+                .collect(),
+            dir,
+            owner,
+        }
+    }
+
+    /// Copy back the stored caches whose directories don't exist
+    pub fn restore(&self) -> Result<String> {
+        let mut report = String::new();
+        for (cache, target) in &self.caches {
+            let stored = self.dir.join(&cache.name);
+            let line = if !stored.is_dir() {
+                format!("{}: nothing cached yet\n", cache.name)
+            } else if target.symlink_metadata().is_ok() {
+                format!("{}: {} exists, kept\n", cache.name, target.display())
+            } else {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| cache_error("create", parent, e))?;
+                }
+                copy_tree(&stored, target, self.owner)?;
+                format!(
+                    "{}: restored {} ({})\n",
+                    cache.name,
+                    target.display(),
+                    format_size(dir_size(target)?)
+                )
+            };
+            report.push_str(&line);
+        }
+        Ok(report)
+    }
+
+    /// Store the caches' directories, replacing what was stored before
+    pub fn save(&self) -> Result<String> {
+        let mut report = String::new();
+        for (cache, target) in &self.caches {
+            if !target.is_dir() {
+                report.push_str(&format!(
+                    "{}: {} doesn't exist, not saved\n",
+                    cache.name,
+                    target.display()
+                ));
+                continue;
+            }
+            let size = dir_size(target)?;
+            if size > cache.max_size() {
+                report.push_str(&format!(
+                    "{}: {} is larger than max_size_mb ({} MB), not saved\n",
+                    cache.name,
+                    format_size(size),
+                    cache.max_size() / (1024 * 1024)
+                ));
+                continue;
+            }
+            // Copied next to the old cache first, so a failed copy doesn't
+            // leave a partial cache behind
+            let stored = self.dir.join(&cache.name);
+            let staging = self.dir.join(format!(".{}.tmp", cache.name));
+            remove_dir(&staging)?;
+            fs::create_dir_all(&self.dir).map_err(|e| cache_error("create", &self.dir, e))?;
+            copy_tree(target, &staging, None)?;
+            remove_dir(&stored)?;
+            fs::rename(&staging, &stored).map_err(|e| cache_error("replace", &stored, e))?;
+            report.push_str(&format!(
+                "{}: saved {} ({})\n",
+                cache.name,
+                target.display(),
+                format_size(size)
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// A stored cache of a project
+#[derive(Debug, Serialize)]
+pub struct StoredCache {
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// List the stored caches of a project
+pub fn list(dir: &Path) -> Result<Vec<StoredCache>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(cache_error("list", dir, e)),
+    };
+    let mut caches = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| cache_error("list", dir, e))?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        // Skip caches being saved
+        if name.starts_with('.') || !entry.path().is_dir() {
+            continue;
+        }
+        let size = dir_size(&entry.path())?;
+        caches.push(StoredCache { name, size });
+    }
+    caches.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(caches)
+}
+
+/// Delete a stored cache; returns false if there was none
+pub fn purge(dir: &Path, name: &str) -> Result<bool> {
+    let stored = dir.join(name);
+    let existed = stored.is_dir();
+    remove_dir(&stored)?;
+    Ok(existed)
+}
+
+/// Copy the directory `from` to `to`, keeping symlinks and permissions, and
+/// give the copies to `owner` if set
+fn copy_tree(from: &Path, to: &Path, owner: Option<(u32, u32)>) -> Result<()> {
+    fs::create_dir(to).map_err(|e| cache_error("create", to, e))?;
+    let permissions = fs::metadata(from)
+        .map_err(|e| cache_error("inspect", from, e))?
+        .permissions();
+    fs::set_permissions(to, permissions).map_err(|e| cache_error("set permissions of", to, e))?;
+    chown(to, owner)?;
+    for entry in fs::read_dir(from).map_err(|e| cache_error("list", from, e))? {
+        let entry = entry.map_err(|e| cache_error("list", from, e))?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let file_type = entry
+            .file_type()
+            .map_err(|e| cache_error("inspect", &source, e))?;
+        if file_type.is_dir() {
+            copy_tree(&source, &target, owner)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&source, &target)?;
+            chown(&target, owner)?;
+        } else if file_type.is_file() {
+            fs::copy(&source, &target).map_err(|e| cache_error("copy", &source, e))?;
+            chown(&target, owner)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    let link = fs::read_link(from).map_err(|e| cache_error("read", from, e))?;
+    std::os::unix::fs::symlink(link, to).map_err(|e| cache_error("create", to, e))
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(_from: &Path, _to: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn chown(path: &Path, owner: Option<(u32, u32)>) -> Result<()> {
+    match owner {
+        Some((uid, gid)) => std::os::unix::fs::lchown(path, Some(uid), Some(gid))
+            .map_err(|e| cache_error("change the owner of", path, e)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn chown(_path: &Path, _owner: Option<(u32, u32)>) -> Result<()> {
+    Ok(())
+}
+
+/// Total size of the files below `dir`, not following symlinks
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir).map_err(|e| cache_error("list", dir, e))? {
+        let entry = entry.map_err(|e| cache_error("list", dir, e))?;
+        let metadata = entry
+            .metadata()
+            .map_err(|e| cache_error("inspect", &entry.path(), e))?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+fn remove_dir(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(cache_error("remove", dir, e)),
+        _ => Ok(()),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn cache_error(operation: &str, path: &Path, e: io::Error) -> CicdError {
+    CicdError::IoError(io::Error::new(
+        e.kind(),
+        format!("Failed to {} {}: {}", operation, path.display(), e),
+    ))
+}
@@ -0,0 +1,298 @@
+//! Typed async client for the server's REST and SSE API
+//!
+//! Enabled with the `client` feature, so other Rust services and CLIs can
+//! trigger and watch jobs without hand-writing HTTP calls:
+//!
+//! ```no_run
+//! use simple_git_cicd::api::projects::SimulateRequest;
+//! use simple_git_cicd::api::stream::Delivery;
+//! use simple_git_cicd::client::CicdClient;
+//!
+//! # async fn run() -> simple_git_cicd::error::Result<()> {
+//! let client = CicdClient::new("http://localhost:8888");
+//! let request = SimulateRequest {
+//!     branch: "main".to_string(),
+//!     sha: None,
+//!     message: None,
+//!     dry_run: false,
+//!     params: Default::default(),
+//! };
+//! let job = client.trigger("my-app", &request).await?;
+//! let mut logs = client.follow_logs(Some(&job.job_id)).await?;
+//! while let Some(Delivery::Event(chunk)) = logs.next().await? {
+//!     print!("{}", chunk.chunk);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::api::jobs::{CancelResponse, JobsQuery, JobsResponse};
+use crate::api::projects::SimulateRequest;
+use crate::api::rollback::RollbackRequest;
+use crate::api::stream::{Delivery, JobEvent, LogChunkEvent};
+use crate::db::store::JobLog;
+use crate::error::{CicdError, Result};
+use crate::job::Job;
+
+/// A job created through the API (simulated push, rollback, resume or retry)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedJob {
+    pub job_id: String,
+    pub project: String,
+    pub branch: String,
+    pub commit_sha: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JobLogsResponse {
+    logs: Vec<JobLog>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Client for one server, e.g. `http://localhost:8888` or
+/// `https://example.com/cicd` when it is mounted under a base path
+#[derive(Debug, Clone)]
+pub struct CicdClient {
+    http: reqwest::Client,
+    base_url: String,
+    basic_auth: Option<(String, String)>,
+}
+
+impl CicdClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured HTTP client (timeouts, proxies, TLS settings)
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            basic_auth: None,
+        }
+    }
+
+    /// Authenticate against `[server.basic_auth]`
+    pub fn with_basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// GET /api/jobs - List jobs, newest first
+    pub async fn list_jobs(&self, query: &JobsQuery) -> Result<JobsResponse> {
+        let request = self.request(Method::GET, "/api/jobs").query(query);
+        json(send(request).await?).await
+    }
+
+    /// GET /api/jobs/{id} - A single job, including its environment and changelog
+    pub async fn get_job(&self, id: &str) -> Result<Job> {
+        let request = self.request(Method::GET, &format!("/api/jobs/{}", id));
+        json(send(request).await?).await
+    }
+
+    /// GET /api/jobs/{id}/logs - The steps of a job with their output
+    pub async fn job_logs(&self, id: &str) -> Result<Vec<JobLog>> {
+        let request = self.request(Method::GET, &format!("/api/jobs/{}/logs", id));
+        let response: JobLogsResponse = json(send(request).await?).await?;
+        Ok(response.logs)
+    }
+
+    /// POST /api/projects/{name}/simulate - Start a job as if a push happened
+    pub async fn trigger(&self, project: &str, request: &SimulateRequest) -> Result<CreatedJob> {
+        let path = format!("/api/projects/{}/simulate", project);
+        json(send(self.request(Method::POST, &path).json(request)).await?).await
+    }
+
+    /// POST /api/projects/{name}/rollback - Redeploy a previous successful commit
+    pub async fn rollback(&self, project: &str, request: &RollbackRequest) -> Result<CreatedJob> {
+        let path = format!("/api/projects/{}/rollback", project);
+        json(send(self.request(Method::POST, &path).json(request)).await?).await
+    }
+
+    /// POST /api/jobs/{id}/resume - Re-run a failed job from its failed step
+    pub async fn resume(&self, id: &str) -> Result<CreatedJob> {
+        let path = format!("/api/jobs/{}/resume", id);
+        json(send(self.request(Method::POST, &path)).await?).await
+    }
+
+    /// POST /api/jobs/{id}/retry - Run a failed job's pipeline again
+    pub async fn retry(&self, id: &str) -> Result<CreatedJob> {
+        let path = format!("/api/jobs/{}/retry", id);
+        json(send(self.request(Method::POST, &path)).await?).await
+    }
+
+    /// POST /api/jobs/{id}/cancel - Cancel a queued job or stop a running one
+    pub async fn cancel(&self, id: &str) -> Result<CancelResponse> {
+        let path = format!("/api/jobs/{}/cancel", id);
+        json(send(self.request(Method::POST, &path)).await?).await
+    }
+
+    /// GET /api/stream/jobs - Follow job state changes as they happen
+    pub async fn follow_jobs(&self) -> Result<EventStream<JobEvent>> {
+        let response = send(self.request(Method::GET, "/api/stream/jobs")).await?;
+        Ok(EventStream::new(response, None))
+    }
+
+    /// GET /api/stream/logs - Follow live output of running jobs, optionally
+    /// only of one job. The stream doesn't end when the job does; watch
+    /// [`follow_jobs`](Self::follow_jobs) or poll [`get_job`](Self::get_job)
+    /// to know when it finished.
+    pub async fn follow_logs(&self, job_id: Option<&str>) -> Result<EventStream<LogChunkEvent>> {
+        let response = send(self.request(Method::GET, "/api/stream/logs")).await?;
+        Ok(EventStream::new(response, job_id.map(String::from)))
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        match &self.basic_auth {
+            Some((username, password)) => request.basic_auth(username, Some(password)),
+            None => request,
+        }
+    }
+}
+
+/// Send a request; error responses become errors carrying the server's message
+async fn send(request: RequestBuilder) -> Result<Response> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| CicdError::ApiRequestFailed(e.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let url = response.url().path().to_string();
+    let message = match response.bytes().await {
+        Ok(body) => serde_json::from_slice::<ErrorResponse>(&body)
+            .map(|e| e.error)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&body).into_owned()),
+        Err(e) => e.to_string(),
+    };
+    Err(CicdError::ApiRequestFailed(format!(
+        "{} returned {}: {}",
+        url, status, message
+    )))
+}
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| CicdError::ApiRequestFailed(e.to_string()))?;
+    serde_json::from_slice(&body)
+        .map_err(|e| CicdError::ApiRequestFailed(format!("Unexpected response: {}", e)))
+}
+
+/// Events read from one of the server's SSE streams
+pub struct EventStream<T> {
+    response: Response,
+    buffer: String,
+    /// Only deliver events of this job
+    job_id: Option<String>,
+    _event: PhantomData<T>,
+}
+
+/// Events that belong to a job
+pub trait JobScoped {
+    fn job_id(&self) -> &str;
+}
+
+impl JobScoped for JobEvent {
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
+impl JobScoped for LogChunkEvent {
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
+impl<T: DeserializeOwned + JobScoped> EventStream<T> {
+    fn new(response: Response, job_id: Option<String>) -> Self {
+        Self {
+            response,
+            buffer: String::new(),
+            job_id,
+            _event: PhantomData,
+        }
+    }
+
+    /// Wait for the next event. `Delivery::Dropped` reports events the server
+    /// skipped because this client fell behind; `None` means the server
+    /// closed the stream.
+    pub async fn next(&mut self) -> Result<Option<Delivery<T>>> {
+        loop {
+            while let Some(end) = self.buffer.find("\n\n") {
+                let message: String = self.buffer.drain(..end + 2).collect();
+                if let Some(delivery) = self.parse(&message)? {
+                    return Ok(Some(delivery));
+                }
+            }
+
+            let chunk = self
+                .response
+                .chunk()
+                .await
+                .map_err(|e| CicdError::ApiRequestFailed(e.to_string()))?;
+            match chunk {
+                Some(chunk) => self
+                    .buffer
+                    .push_str(&String::from_utf8_lossy(&chunk).replace("\r\n", "\n")),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Parse one SSE message; comments (keep-alives) and other jobs' events
+    /// are skipped
+    fn parse(&self, message: &str) -> Result<Option<Delivery<T>>> {
+        let mut event = "message";
+        let mut data = String::new();
+        for line in message.lines() {
+            if let Some(value) = line.strip_prefix("event:") {
+                event = value.trim();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(value.strip_prefix(' ').unwrap_or(value));
+            }
+        }
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        if event == "dropped" {
+            #[derive(Deserialize)]
+            struct Dropped {
+                dropped: u64,
+            }
+            let dropped: Dropped = serde_json::from_str(&data)
+                .map_err(|e| CicdError::ApiRequestFailed(format!("Unexpected event: {}", e)))?;
+            return Ok(Some(Delivery::Dropped(dropped.dropped)));
+        }
+        let item: T = serde_json::from_str(&data)
+            .map_err(|e| CicdError::ApiRequestFailed(format!("Unexpected event: {}", e)))?;
+        if self.job_id.as_deref().is_some_and(|id| id != item.job_id()) {
+            return Ok(None);
+        }
+        Ok(Some(Delivery::Event(item)))
+    }
+}
@@ -0,0 +1,80 @@
+//! Restricted execution (`[server] allowed_commands`, `denied_env`)
+//!
+//! Operators can limit what configured pipelines run, whoever wrote their
+//! config. With `allowed_commands`, a script's program must be listed: a bare
+//! name allows the program called by that name (looked up in `PATH`), a path
+//! or glob like `/srv/*/deploy.sh` allows programs at matching absolute
+//! paths, relative ones being resolved against the script's directory. With
+//! `denied_env`, a script isn't started if its environment sets one of the
+//! listed variables (globs like `LD_*`). Both are checked right before a
+//! script is spawned. The policy limits what is started, not what it does:
+//! in shell mode the program is the shell, which can run anything.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{CicdError, Result};
+use crate::executor::Process;
+use crate::path_filter::PathFilter;
+
+/// The `[server]` limits on what pipeline scripts start
+#[derive(Debug, Clone, Default)]
+pub struct CommandPolicy {
+    pub allowed_commands: Option<PathFilter>,
+    pub denied_env: Option<PathFilter>,
+}
+
+impl CommandPolicy {
+    /// Returns an error if `process` may not be started
+    pub fn check(&self, process: &Process<'_>) -> Result<()> {
+        self.check_program(process.program, process.dir)?;
+        if let Some(denied) = &self.denied_env
+            && let Some((key, _)) = process.env.iter().find(|(key, _)| denied.matches(key))
+        {
+            return Err(CicdError::ScriptExecutionFailed(format!(
+                "Environment variable '{}' is denied by [server] denied_env",
+                key
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns an error if `program`, started in `dir`, isn't allowed
+    pub fn check_program(&self, program: &str, dir: &str) -> Result<()> {
+        let Some(allowed) = &self.allowed_commands else {
+            return Ok(());
+        };
+        let matched = if program.contains('/') {
+            // Patterns are stored without their leading `/`
+            let path = Path::new(dir).join(program);
+            let path = normalize(&std::path::absolute(&path).unwrap_or(path));
+            path.strip_prefix("/")
+                .is_ok_and(|path| path.to_str().is_some_and(|path| allowed.matches(path)))
+        } else {
+            allowed.matches(program)
+        };
+        if matched {
+            Ok(())
+        } else {
+            Err(CicdError::ScriptExecutionFailed(format!(
+                "'{}' is not in [server] allowed_commands",
+                program
+            )))
+        }
+    }
+}
+
+/// `path` with `.` and `..` resolved, without following symlinks, so
+/// `scripts/../../bin/sh` can't pass for a script below `scripts`
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
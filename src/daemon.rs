@@ -0,0 +1,111 @@
+//! Running as a classic Unix daemon (`--daemon`, `--pid-file`) for hosts
+//! managed by simple init scripts instead of systemd.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Detach from the terminal: fork twice, start a new session and point
+/// stdin/stdout/stderr at /dev/null. Must be called before any threads
+/// (including the tokio runtime) are started.
+///
+/// The working directory is left unchanged so relative config/database
+/// paths keep working.
+pub fn daemonize() -> io::Result<()> {
+    // SAFETY: called while the process is still single-threaded; the child
+    // only continues with async-signal-safe setup before returning to Rust.
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Second fork so the daemon can never reacquire a controlling terminal
+        match libc::fork() {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+
+        let dev_null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+        if dev_null == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+            if libc::dup2(dev_null, fd) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if dev_null > libc::STDERR_FILENO {
+            libc::close(dev_null);
+        }
+    }
+    Ok(())
+}
+
+/// PID file that is removed again when dropped
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current PID to `path`. Fails if the file names a process
+    /// that is still running; stale files are replaced.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Ok(contents) = fs::read_to_string(path)
+            && let Ok(pid) = contents.trim().parse::<libc::pid_t>()
+            && pid > 0
+            && process_alive(pid)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "PID file {} belongs to running process {}",
+                    path.display(),
+                    pid
+                ),
+            ));
+        }
+
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Wait for SIGTERM or SIGINT
+pub async fn wait_for_shutdown_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = sigterm.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+fn process_alive(pid: libc::pid_t) -> bool {
+    // SAFETY: signal 0 only checks for existence/permission
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
@@ -1,8 +1,11 @@
+use crate::artifacts::Artifact;
 use crate::error::CicdError;
-use crate::job::{Job, JobStatus};
+use crate::job::{Job, JobStatus, MAX_PAYLOAD_SIZE};
+use crate::output_diff::OutputDiff;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, SqlitePool};
+use std::collections::BTreeMap;
 
 /// Represents a structured log entry for a specific step in a job
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +21,81 @@ pub struct JobLog {
     pub exit_code: Option<i32>,
     pub output: Option<String>,
     pub status: String, // running, success, failed
+    /// Signal that ended the step's script, e.g. `SIGTERM` when it was stopped
+    #[serde(default)]
+    pub signal: Option<String>,
+    /// What the step's script wrote to stdout (`output` has both streams)
+    #[serde(default)]
+    pub stdout: Option<String>,
+    /// What the step's script wrote to stderr
+    #[serde(default)]
+    pub stderr: Option<String>,
+    /// The step's output was cut to stay within `max_step_output_bytes`
+    #[serde(default)]
+    pub output_truncated: bool,
+}
+
+/// A job waiting in (or claimed from) the persistent queue
+#[derive(Debug, Clone, FromRow)]
+pub struct QueuedJob {
+    pub job_id: String,
+    pub project_config: String,
+    pub webhook_data: String,
+    pub priority: i64,
+    pub enqueued_at: String,
+}
+
+/// Maintenance mode state of a project
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ProjectMaintenance {
+    pub project_name: String,
+    pub enabled_at: String,
+    pub reason: Option<String>,
+}
+
+/// Persisted state of a rate limit token bucket
+#[derive(Debug, Clone, FromRow)]
+pub struct RateLimitBucket {
+    pub key: String,
+    pub tokens: f64,
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+    pub updated_at: String,
+}
+
+/// A webhook request persisted before processing (see `record_intake`)
+#[derive(Debug, Clone, FromRow)]
+pub struct WebhookIntake {
+    pub id: i64,
+    pub received_at: String,
+    pub uri: String,
+    /// JSON list of `[name, value]` pairs
+    pub headers: String,
+    pub body: Vec<u8>,
+    pub client_ip: Option<String>,
+    /// Comma-separated IDs of the jobs already queued for the request
+    pub job_ids: Option<String>,
+}
+
+/// A handled webhook delivery (see `record_delivery`). The request is
+/// missing for deliveries recorded by older versions.
+#[derive(Debug, Clone, FromRow)]
+pub struct WebhookDelivery {
+    pub delivery_id: String,
+    pub received_at: String,
+    pub uri: Option<String>,
+    /// JSON list of `[name, value]` pairs
+    pub headers: Option<String>,
+    pub body: Option<Vec<u8>>,
+    pub client_ip: Option<String>,
+}
+
+/// Aggregated execution counts for a single step type
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct StepFailureCount {
+    pub log_type: String,
+    pub total: i64,
+    pub failed: i64,
 }
 
 // Helper struct to map DB row to JobLog struct
@@ -34,6 +112,10 @@ struct JobLogRow {
     exit_code: Option<i32>,
     output: Option<String>,
     status: String,
+    signal: Option<String>,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    output_truncated: Option<bool>,
 }
 
 impl From<JobLogRow> for JobLog {
@@ -60,6 +142,10 @@ impl From<JobLogRow> for JobLog {
             exit_code: row.exit_code,
             output: row.output,
             status: row.status,
+            signal: row.signal,
+            stdout: row.stdout,
+            stderr: row.stderr,
+            output_truncated: row.output_truncated.unwrap_or(false),
         }
     }
 }
@@ -86,9 +172,10 @@ impl SqlJobStore {
             INSERT INTO jobs (
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, created_at, dry_run
+                started_at, created_at, dry_run, rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&job.id)
@@ -101,6 +188,12 @@ impl SqlJobStore {
         .bind(job.started_at.to_rfc3339())
         .bind(Utc::now().to_rfc3339())
         .bind(job.dry_run)
+        .bind(&job.rollback_of)
+        .bind(&job.tag)
+        .bind(&job.resumed_from)
+        .bind(&job.retried_from)
+        .bind(job.pull_request)
+        .bind(job.rate_limit_bypassed)
         .execute(&self.pool)
         .await
         .map_err(|e| CicdError::DatabaseError(format!("Failed to create job: {}", e)))?;
@@ -124,6 +217,19 @@ impl SqlJobStore {
         Ok(())
     }
 
+    /// Mark a queued job as running. Returns false if it isn't queued
+    /// anymore because it was cancelled while waiting.
+    pub async fn start_job(&self, id: &str) -> Result<bool, CicdError> {
+        let result =
+            sqlx::query("UPDATE jobs SET status = 'running' WHERE id = ? AND status = 'queued'")
+                .bind(id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| CicdError::DatabaseError(format!("Failed to start job: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Complete a job (success or failure)
     pub async fn complete_job(
         &self,
@@ -175,6 +281,365 @@ impl SqlJobStore {
         Ok(())
     }
 
+    /// Flag a job's output as truncated
+    pub async fn mark_output_truncated(&self, id: &str) -> Result<(), CicdError> {
+        sqlx::query("UPDATE jobs SET output_truncated = 1 WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to mark output truncated: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Set or clear a job's pinned flag. Returns false if the job doesn't exist.
+    pub async fn set_job_pinned(&self, id: &str, pinned: bool) -> Result<bool, CicdError> {
+        let result = sqlx::query("UPDATE jobs SET pinned = ? WHERE id = ?")
+            .bind(pinned)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to update pin: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Store the (masked) environment passed to a job's scripts
+    pub async fn set_env_snapshot(
+        &self,
+        id: &str,
+        env: &BTreeMap<String, String>,
+    ) -> Result<(), CicdError> {
+        let json = serde_json::to_string(env).map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to serialize environment: {}", e))
+        })?;
+        sqlx::query("UPDATE jobs SET env_snapshot = ? WHERE id = ?")
+            .bind(json)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to store environment snapshot: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Store the webhook body that created a job, cut off at
+    /// `MAX_PAYLOAD_SIZE`
+    pub async fn set_job_payload(&self, id: &str, body: &[u8]) -> Result<(), CicdError> {
+        let truncated = body.len() > MAX_PAYLOAD_SIZE;
+        let payload = &body[..body.len().min(MAX_PAYLOAD_SIZE)];
+        sqlx::query("UPDATE jobs SET payload = ?, payload_truncated = ? WHERE id = ?")
+            .bind(payload)
+            .bind(truncated)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to store webhook payload: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Store the webhook body of job `from` with job `to` as well, e.g. for
+    /// a retry
+    pub async fn copy_job_payload(&self, from: &str, to: &str) -> Result<(), CicdError> {
+        sqlx::query(
+            "UPDATE jobs SET (payload, payload_truncated) = \
+             (SELECT payload, payload_truncated FROM jobs WHERE id = ?) WHERE id = ?",
+        )
+        .bind(from)
+        .bind(to)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to store webhook payload: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Get the webhook body stored with a job and whether it was cut off.
+    /// None if the job doesn't exist; the body is None for jobs not created
+    /// by a webhook.
+    pub async fn get_job_payload(
+        &self,
+        id: &str,
+    ) -> Result<Option<(Option<Vec<u8>>, bool)>, CicdError> {
+        sqlx::query_as::<_, (Option<Vec<u8>>, bool)>(
+            "SELECT payload, payload_truncated FROM jobs WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to get webhook payload: {}", e)))
+    }
+
+    /// Store the pipeline inputs a job was queued with, for resuming it
+    pub async fn set_pipeline_snapshot(
+        &self,
+        id: &str,
+        project_config: &str,
+        webhook_data: &str,
+    ) -> Result<(), CicdError> {
+        sqlx::query("UPDATE jobs SET project_config = ?, webhook_data = ? WHERE id = ?")
+            .bind(project_config)
+            .bind(webhook_data)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to store pipeline snapshot: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// The project config and webhook data (JSON) a job was queued with.
+    /// `None` for jobs created before snapshots were recorded.
+    pub async fn get_pipeline_snapshot(
+        &self,
+        id: &str,
+    ) -> Result<Option<(String, String)>, CicdError> {
+        let row: Option<(Option<String>, Option<String>)> =
+            sqlx::query_as("SELECT project_config, webhook_data FROM jobs WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| {
+                    CicdError::DatabaseError(format!("Failed to get pipeline snapshot: {}", e))
+                })?;
+
+        Ok(row.and_then(|(config, data)| config.zip(data)))
+    }
+
+    /// Store the commits a job deploys since the last successful deploy
+    pub async fn set_changelog(&self, id: &str, changelog: &str) -> Result<(), CicdError> {
+        sqlx::query("UPDATE jobs SET changelog = ? WHERE id = ?")
+            .bind(changelog)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to store changelog: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Record a file collected from a job
+    pub async fn add_artifact(&self, job_id: &str, artifact: &Artifact) -> Result<(), CicdError> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO artifacts (job_id, name, size, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(job_id)
+        .bind(&artifact.name)
+        .bind(artifact.size)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to record artifact: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// List the files collected from a job, by name
+    pub async fn get_artifacts(&self, job_id: &str) -> Result<Vec<Artifact>, CicdError> {
+        sqlx::query_as("SELECT name, size FROM artifacts WHERE job_id = ? ORDER BY name ASC")
+            .bind(job_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch artifacts: {}", e)))
+    }
+
+    /// Get a file collected from a job by its name
+    pub async fn get_artifact(
+        &self,
+        job_id: &str,
+        name: &str,
+    ) -> Result<Option<Artifact>, CicdError> {
+        sqlx::query_as("SELECT name, size FROM artifacts WHERE job_id = ? AND name = ?")
+            .bind(job_id)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch artifact: {}", e)))
+    }
+
+    /// Store a job's output diff against its previous run
+    pub async fn set_output_diff(&self, id: &str, diff: &OutputDiff) -> Result<(), CicdError> {
+        let json = serde_json::to_string(diff).map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to serialize output diff: {}", e))
+        })?;
+        sqlx::query("UPDATE jobs SET output_diff = ? WHERE id = ?")
+            .bind(json)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to store output diff: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Delete finished, unpinned jobs completed before `cutoff`, returning
+    /// their IDs. Logs, artifact records and queue entries are removed by
+    /// cascade.
+    pub async fn prune_jobs_completed_before(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<String>, CicdError> {
+        sqlx::query_scalar(
+            r#"
+            DELETE FROM jobs
+            WHERE pinned = 0
+              AND status IN ('success', 'failed', 'skipped', 'cancelled', 'timed_out')
+              AND completed_at IS NOT NULL
+              AND completed_at < ?
+            RETURNING id
+            "#,
+        )
+        .bind(cutoff.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to prune jobs: {}", e)))
+    }
+
+    /// Record a webhook delivery ID and its request. Returns false if it was
+    /// already recorded, i.e. the request is a redelivery.
+    pub async fn record_delivery(
+        &self,
+        delivery_id: &str,
+        uri: &str,
+        headers: &str,
+        body: &[u8],
+        client_ip: Option<&str>,
+    ) -> Result<bool, CicdError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (delivery_id, received_at, uri, headers, body, client_ip)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(delivery_id) DO NOTHING
+            "#,
+        )
+        .bind(delivery_id)
+        .bind(Utc::now().to_rfc3339())
+        .bind(uri)
+        .bind(headers)
+        .bind(body)
+        .bind(client_ip)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to record delivery: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get a recorded webhook delivery
+    pub async fn get_delivery(
+        &self,
+        delivery_id: &str,
+    ) -> Result<Option<WebhookDelivery>, CicdError> {
+        sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT delivery_id, received_at, uri, headers, body, client_ip
+            FROM webhook_deliveries
+            WHERE delivery_id = ?
+            "#,
+        )
+        .bind(delivery_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to get delivery: {}", e)))
+    }
+
+    /// Forget a recorded webhook delivery, so a redelivery is handled again
+    pub async fn forget_delivery(&self, delivery_id: &str) -> Result<(), CicdError> {
+        sqlx::query("DELETE FROM webhook_deliveries WHERE delivery_id = ?")
+            .bind(delivery_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to forget delivery: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Delete webhook delivery IDs received before `cutoff`
+    pub async fn prune_deliveries_before(&self, cutoff: DateTime<Utc>) -> Result<u64, CicdError> {
+        let result = sqlx::query("DELETE FROM webhook_deliveries WHERE received_at < ?")
+            .bind(cutoff.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to prune deliveries: {}", e)))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Persist a webhook request before it is processed. Returns the
+    /// record's ID, to delete it with `delete_intake` once processed.
+    pub async fn record_intake(
+        &self,
+        uri: &str,
+        headers: &str,
+        body: &[u8],
+        client_ip: Option<&str>,
+    ) -> Result<i64, CicdError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO webhook_intake (received_at, uri, headers, body, client_ip)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(uri)
+        .bind(headers)
+        .bind(body)
+        .bind(client_ip)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to record webhook: {}", e)))?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Link a job to the persisted webhook request it was created for.
+    /// Queuing the job then records it with the request (see `enqueue_job`).
+    pub async fn set_job_intake(&self, id: &str, intake_id: i64) -> Result<(), CicdError> {
+        sqlx::query("UPDATE jobs SET intake_id = ? WHERE id = ?")
+            .bind(intake_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to link job to its webhook: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Delete a processed webhook request
+    pub async fn delete_intake(&self, id: i64) -> Result<(), CicdError> {
+        sqlx::query("DELETE FROM webhook_intake WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to delete webhook record: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Webhook requests that weren't fully processed, oldest first
+    pub async fn get_pending_intake(&self) -> Result<Vec<WebhookIntake>, CicdError> {
+        sqlx::query_as::<_, WebhookIntake>(
+            "SELECT id, received_at, uri, headers, body, client_ip, job_ids \
+             FROM webhook_intake ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to get unprocessed webhooks: {}", e)))
+    }
+
     /// Get a job by ID
     pub async fn get_job(&self, id: &str) -> Result<Option<Job>, CicdError> {
         let row = sqlx::query_as::<_, JobRow>(
@@ -182,7 +647,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed, env_snapshot, output_diff, changelog
             FROM jobs
             WHERE id = ?
             "#,
@@ -202,7 +669,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             FROM jobs
             ORDER BY created_at DESC
             LIMIT ?
@@ -227,7 +696,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             FROM jobs
             WHERE project_name = ?
             ORDER BY created_at DESC
@@ -243,6 +714,31 @@ impl SqlJobStore {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    /// Get a project's tag-triggered jobs, newest first
+    pub async fn get_tag_jobs(&self, project: &str, limit: i64) -> Result<Vec<Job>, CicdError> {
+        let rows = sqlx::query_as::<_, JobRow>(
+            r#"
+            SELECT
+                id, project_name, branch, status,
+                commit_sha, commit_message, commit_author_name,
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
+            FROM jobs
+            WHERE project_name = ? AND tag IS NOT NULL
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(project)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch tag jobs: {}", e)))?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     /// Add a log entry for a job step, returns the inserted ID
     pub async fn add_log(&self, log: &JobLog) -> Result<i64, CicdError> {
         let result = sqlx::query(
@@ -250,9 +746,9 @@ impl SqlJobStore {
             INSERT INTO job_logs (
                 job_id, sequence, log_type, command,
                 started_at, completed_at, duration_ms,
-                exit_code, output, status
+                exit_code, output, status, signal
             )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&log.job_id)
@@ -265,6 +761,7 @@ impl SqlJobStore {
         .bind(log.exit_code)
         .bind(&log.output)
         .bind(&log.status)
+        .bind(&log.signal)
         .execute(&self.pool)
         .await
         .map_err(|e| CicdError::DatabaseError(format!("Failed to add job log: {}", e)))?;
@@ -302,58 +799,725 @@ impl SqlJobStore {
         Ok(())
     }
 
-    /// Get logs for a job
-    pub async fn get_job_logs(&self, job_id: &str) -> Result<Vec<JobLog>, CicdError> {
-        let rows = sqlx::query_as::<_, JobLogRow>(
-            "SELECT * FROM job_logs WHERE job_id = ? ORDER BY sequence ASC",
-        )
-        .bind(job_id)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch job logs: {}", e)))?;
+    /// Record the signal that ended a step's script
+    pub async fn set_log_signal(&self, id: i64, signal: &str) -> Result<(), CicdError> {
+        sqlx::query("UPDATE job_logs SET signal = ? WHERE id = ?")
+            .bind(signal)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to update job log: {}", e)))?;
 
-        Ok(rows.into_iter().map(|r| r.into()).collect())
+        Ok(())
     }
 
-    /// Count queued jobs
-    pub async fn get_queued_count(&self) -> Result<i64, CicdError> {
-        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM jobs WHERE status = 'queued'")
-            .fetch_one(&self.pool)
+    /// Record a step's output as written, before ANSI escape sequences were
+    /// stripped from it
+    pub async fn set_log_raw_output(&self, id: i64, raw_output: &str) -> Result<(), CicdError> {
+        sqlx::query("UPDATE job_logs SET raw_output = ? WHERE id = ?")
+            .bind(raw_output)
+            .bind(id)
+            .execute(&self.pool)
             .await
-            .map_err(|e| CicdError::DatabaseError(format!("Failed to count queued jobs: {}", e)))?;
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to update job log: {}", e)))?;
 
-        Ok(count.0)
+        Ok(())
     }
 
-    /// Get the currently running job (if any)
-    pub async fn get_current_job(&self) -> Result<Option<Job>, CicdError> {
-        let row = sqlx::query_as::<_, JobRow>(
-            r#"
-            SELECT
-                id, project_name, branch, status,
-                commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
-            FROM jobs
-            WHERE status = 'running'
-            LIMIT 1
-            "#,
+    /// Record what a step's script wrote to stdout and stderr, and whether
+    /// its output was cut
+    pub async fn set_log_streams(
+        &self,
+        id: i64,
+        stdout: &str,
+        stderr: &str,
+        truncated: bool,
+    ) -> Result<(), CicdError> {
+        sqlx::query(
+            "UPDATE job_logs SET stdout = ?, stderr = ?, output_truncated = ? WHERE id = ?",
         )
-        .fetch_optional(&self.pool)
+        .bind(stdout)
+        .bind(stderr)
+        .bind(truncated)
+        .bind(id)
+        .execute(&self.pool)
         .await
-        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch current job: {}", e)))?;
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to update job log: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Close the log entries of a job's steps that are still running because
+    /// the job was stopped: they get `status`, and `note` as a last line of
+    /// output
+    pub async fn stop_running_logs(
+        &self,
+        job_id: &str,
+        status: &str,
+        note: &str,
+        completed_at: DateTime<Utc>,
+    ) -> Result<(), CicdError> {
+        let running: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, started_at FROM job_logs WHERE job_id = ? AND status = 'running'",
+        )
+        .bind(job_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch running logs: {}", e)))?;
+
+        for (id, started_at) in running {
+            let duration_ms = DateTime::parse_from_rfc3339(&started_at)
+                .map(|dt| (completed_at - dt.with_timezone(&Utc)).num_milliseconds())
+                .unwrap_or(0);
+            sqlx::query(
+                r#"
+                UPDATE job_logs
+                SET completed_at = ?, duration_ms = ?, exit_code = -1,
+                    output = COALESCE(NULLIF(output, '') || char(10), '') || ?, status = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(completed_at.to_rfc3339())
+            .bind(duration_ms)
+            .bind(note)
+            .bind(status)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to update job log: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Append streamed output to a running log entry
+    pub async fn append_log_output(&self, id: i64, chunk: &str) -> Result<(), CicdError> {
+        sqlx::query("UPDATE job_logs SET output = COALESCE(output, '') || ? WHERE id = ?")
+            .bind(chunk)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to append job log output: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Get logs for a job
+    pub async fn get_job_logs(&self, job_id: &str) -> Result<Vec<JobLog>, CicdError> {
+        let rows = sqlx::query_as::<_, JobLogRow>(
+            "SELECT * FROM job_logs WHERE job_id = ? ORDER BY sequence ASC",
+        )
+        .bind(job_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch job logs: {}", e)))?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// List a job's steps (id, sequence, log_type, command) without their output
+    pub async fn get_job_log_steps(
+        &self,
+        job_id: &str,
+    ) -> Result<Vec<(i64, i32, String, Option<String>)>, CicdError> {
+        sqlx::query_as(
+            "SELECT id, sequence, log_type, command FROM job_logs WHERE job_id = ? ORDER BY sequence ASC",
+        )
+        .bind(job_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch job log steps: {}", e)))
+    }
+
+    /// Returns true if a job with this ID exists
+    pub async fn job_exists(&self, id: &str) -> Result<bool, CicdError> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM jobs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to look up job: {}", e)))?;
+
+        Ok(row.is_some())
+    }
+
+    /// Read up to `len` bytes of a job's output starting at byte `offset`.
+    /// Returns an empty chunk past the end of the output.
+    pub async fn read_job_output_chunk(
+        &self,
+        id: &str,
+        offset: i64,
+        len: i64,
+    ) -> Result<Vec<u8>, CicdError> {
+        let row: Option<(Vec<u8>,)> = sqlx::query_as(
+            "SELECT substr(CAST(COALESCE(output, '') AS BLOB), ?, ?) FROM jobs WHERE id = ?",
+        )
+        .bind(offset + 1)
+        .bind(len)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to read job output: {}", e)))?;
+
+        Ok(row.map(|r| r.0).unwrap_or_default())
+    }
+
+    /// Read up to `len` bytes of a step's output starting at byte `offset`;
+    /// with `raw`, its output as written if it was stored apart (see
+    /// `set_log_raw_output`). Returns an empty chunk past the end of the output.
+    pub async fn read_log_output_chunk(
+        &self,
+        id: i64,
+        offset: i64,
+        len: i64,
+        raw: bool,
+    ) -> Result<Vec<u8>, CicdError> {
+        let query = if raw {
+            "SELECT substr(CAST(COALESCE(raw_output, output, '') AS BLOB), ?, ?) FROM job_logs WHERE id = ?"
+        } else {
+            "SELECT substr(CAST(COALESCE(output, '') AS BLOB), ?, ?) FROM job_logs WHERE id = ?"
+        };
+        let row: Option<(Vec<u8>,)> = sqlx::query_as(query)
+            .bind(offset + 1)
+            .bind(len)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to read job log output: {}", e))
+            })?;
+
+        Ok(row.map(|r| r.0).unwrap_or_default())
+    }
+
+    /// Get per-step execution and failure counts over a project's most recent jobs
+    /// (dry runs excluded)
+    pub async fn get_step_failure_counts(
+        &self,
+        project: &str,
+        limit: i64,
+    ) -> Result<Vec<StepFailureCount>, CicdError> {
+        sqlx::query_as::<_, StepFailureCount>(
+            r#"
+            SELECT
+                l.log_type AS log_type,
+                COUNT(*) AS total,
+                SUM(CASE WHEN l.status = 'failed' THEN 1 ELSE 0 END) AS failed
+            FROM job_logs l
+            JOIN (
+                SELECT id FROM jobs
+                WHERE project_name = ? AND dry_run = 0
+                ORDER BY created_at DESC
+                LIMIT ?
+            ) j ON l.job_id = j.id
+            GROUP BY l.log_type
+            ORDER BY failed DESC, l.log_type ASC
+            "#,
+        )
+        .bind(project)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch step counts: {}", e)))
+    }
+
+    /// Get the first failed (or timed out) step of each of a project's most
+    /// recent jobs (dry runs excluded), as `(job_id, log_type)` pairs
+    pub async fn get_failed_steps(
+        &self,
+        project: &str,
+        limit: i64,
+    ) -> Result<Vec<(String, String)>, CicdError> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT l.job_id, l.log_type
+            FROM job_logs l
+            JOIN (
+                SELECT id FROM jobs
+                WHERE project_name = ? AND dry_run = 0
+                ORDER BY created_at DESC
+                LIMIT ?
+            ) j ON l.job_id = j.id
+            WHERE l.status IN ('failed', 'timed_out')
+            ORDER BY l.job_id, l.sequence ASC
+            "#,
+        )
+        .bind(project)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch failed steps: {}", e)))?;
+
+        let mut first = Vec::<(String, String)>::new();
+        for (job_id, log_type) in rows {
+            if first.last().is_none_or(|(last, _)| *last != job_id) {
+                first.push((job_id, log_type));
+            }
+        }
+        Ok(first)
+    }
+
+    /// Add a job to the persistent queue. A job created for a persisted
+    /// webhook request (see `set_job_intake`) is recorded with the request in
+    /// the same transaction, so the request isn't processed again after a
+    /// restart once the job is queued.
+    pub async fn enqueue_job(
+        &self,
+        job_id: &str,
+        project_config: &str,
+        webhook_data: &str,
+        priority: i64,
+        not_before: Option<DateTime<Utc>>,
+    ) -> Result<(), CicdError> {
+        let db_error =
+            |e: sqlx::Error| CicdError::DatabaseError(format!("Failed to enqueue job: {}", e));
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO job_queue (
+                job_id, project_config, webhook_data, priority, enqueued_at, not_before
+            )
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(job_id)
+        .bind(project_config)
+        .bind(webhook_data)
+        .bind(priority)
+        .bind(Utc::now().to_rfc3339())
+        .bind(not_before.map(|t| t.to_rfc3339()))
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        sqlx::query(
+            r#"
+            UPDATE webhook_intake
+            SET job_ids = COALESCE(job_ids || ',', '') || ?
+            WHERE id = (SELECT intake_id FROM jobs WHERE id = ?)
+            "#,
+        )
+        .bind(job_id)
+        .bind(job_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(())
+    }
+
+    /// Atomically claim the next unclaimed queue entry that is not held back
+    /// by `not_before`, its project's maintenance mode or a claimed entry
+    /// sharing its checkout or `concurrency_group` (highest priority, oldest
+    /// first). Dry runs are never held by maintenance mode.
+    pub async fn claim_next_queued(&self) -> Result<Option<QueuedJob>, CicdError> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query_as::<_, QueuedJob>(
+            r#"
+            UPDATE job_queue
+            SET claimed_at = ?
+            WHERE job_id = (
+                SELECT q.job_id FROM job_queue q
+                WHERE q.claimed_at IS NULL
+                  AND (q.not_before IS NULL OR q.not_before <= ?)
+                  AND q.job_id NOT IN (
+                      SELECT j.id FROM jobs j
+                      JOIN project_maintenance m ON m.project_name = j.project_name
+                      WHERE j.dry_run = 0
+                  )
+                  AND NOT EXISTS (
+                      SELECT 1 FROM job_queue c
+                      WHERE c.claimed_at IS NOT NULL
+                        AND (
+                            json_extract(c.webhook_data, '$.repo_path')
+                                = json_extract(q.webhook_data, '$.repo_path')
+                            OR json_extract(c.project_config, '$.concurrency_group')
+                                = json_extract(q.project_config, '$.concurrency_group')
+                        )
+                  )
+                ORDER BY q.priority DESC, q.enqueued_at ASC
+                LIMIT 1
+            )
+            RETURNING job_id, project_config, webhook_data, priority, enqueued_at
+            "#,
+        )
+        .bind(&now)
+        .bind(&now)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to claim queued job: {}", e)))
+    }
+
+    /// Put a project into maintenance mode. Returns false if it already was
+    /// (the original start time and reason are kept).
+    pub async fn enable_maintenance(
+        &self,
+        project_name: &str,
+        reason: Option<&str>,
+    ) -> Result<bool, CicdError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO project_maintenance (project_name, enabled_at, reason)
+            VALUES (?, ?, ?)
+            ON CONFLICT(project_name) DO NOTHING
+            "#,
+        )
+        .bind(project_name)
+        .bind(Utc::now().to_rfc3339())
+        .bind(reason)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to enable maintenance: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Take a project out of maintenance mode, recording when (see
+    /// `get_consecutive_failures`). Returns false if it wasn't in it.
+    pub async fn disable_maintenance(&self, project_name: &str) -> Result<bool, CicdError> {
+        let db_error = |e: sqlx::Error| {
+            CicdError::DatabaseError(format!("Failed to disable maintenance: {}", e))
+        };
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let result = sqlx::query("DELETE FROM project_maintenance WHERE project_name = ?")
+            .bind(project_name)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO project_maintenance_lifted (project_name, lifted_at)
+            VALUES (?, ?)
+            ON CONFLICT(project_name) DO UPDATE SET lifted_at = excluded.lifted_at
+            "#,
+        )
+        .bind(project_name)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        Ok(true)
+    }
+
+    /// Maintenance state of a project, if it is in maintenance mode
+    pub async fn get_maintenance(
+        &self,
+        project_name: &str,
+    ) -> Result<Option<ProjectMaintenance>, CicdError> {
+        sqlx::query_as::<_, ProjectMaintenance>(
+            "SELECT project_name, enabled_at, reason FROM project_maintenance WHERE project_name = ?",
+        )
+        .bind(project_name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to get maintenance state: {}", e)))
+    }
+
+    /// Number of queued jobs of a project held by maintenance mode
+    pub async fn get_held_job_count(&self, project_name: &str) -> Result<i64, CicdError> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM job_queue q
+            JOIN jobs j ON j.id = q.job_id
+            WHERE q.claimed_at IS NULL AND j.dry_run = 0 AND j.project_name = ?
+            "#,
+        )
+        .bind(project_name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to count held jobs: {}", e)))?;
+
+        Ok(count.0)
+    }
+
+    /// Number of non-dry-run jobs of a project that failed or timed out since
+    /// its last successful one, or since its maintenance mode was last lifted
+    /// if that was later, not counting pull request checks
+    pub async fn get_consecutive_failures(&self, project_name: &str) -> Result<i64, CicdError> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM jobs
+            WHERE project_name = ? AND dry_run = 0 AND status IN ('failed', 'timed_out')
+              AND pull_request IS NULL
+              AND completed_at > MAX(
+                  COALESCE((
+                      SELECT MAX(completed_at) FROM jobs
+                      WHERE project_name = ? AND dry_run = 0 AND status = 'success'
+                        AND pull_request IS NULL
+                  ), ''),
+                  COALESCE((
+                      SELECT lifted_at FROM project_maintenance_lifted
+                      WHERE project_name = ?
+                  ), '')
+              )
+            "#,
+        )
+        .bind(project_name)
+        .bind(project_name)
+        .bind(project_name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to count consecutive failures: {}", e))
+        })?;
+
+        Ok(count.0)
+    }
+
+    /// Insert or replace a rate limit bucket
+    pub async fn save_rate_limit_bucket(&self, bucket: &RateLimitBucket) -> Result<(), CicdError> {
+        sqlx::query(
+            r#"
+            INSERT INTO rate_limit_buckets (key, tokens, capacity, refill_per_sec, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(key) DO UPDATE SET
+                tokens = excluded.tokens,
+                capacity = excluded.capacity,
+                refill_per_sec = excluded.refill_per_sec,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&bucket.key)
+        .bind(bucket.tokens)
+        .bind(bucket.capacity)
+        .bind(bucket.refill_per_sec)
+        .bind(&bucket.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to save rate limit bucket: {}", e))
+        })?;
+
+        Ok(())
+    }
+
+    /// All persisted rate limit buckets
+    pub async fn get_rate_limit_buckets(&self) -> Result<Vec<RateLimitBucket>, CicdError> {
+        sqlx::query_as::<_, RateLimitBucket>(
+            "SELECT key, tokens, capacity, refill_per_sec, updated_at FROM rate_limit_buckets",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to get rate limit buckets: {}", e)))
+    }
+
+    /// Delete a persisted rate limit bucket
+    pub async fn delete_rate_limit_bucket(&self, key: &str) -> Result<(), CicdError> {
+        sqlx::query("DELETE FROM rate_limit_buckets WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to delete rate limit bucket: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Remove a finished job from the queue
+    pub async fn remove_from_queue(&self, job_id: &str) -> Result<(), CicdError> {
+        sqlx::query("DELETE FROM job_queue WHERE job_id = ?")
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to remove job from queue: {}", e))
+            })?;
+
+        Ok(())
+    }
+
+    /// Cancel a job that hasn't started yet, removing it from the queue.
+    /// Returns false if it isn't queued (anymore).
+    pub async fn cancel_queued_job(&self, id: &str, reason: &str) -> Result<bool, CicdError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'cancelled', error = ?, completed_at = ?
+            WHERE id = ? AND status = 'queued'
+            "#,
+        )
+        .bind(reason)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to cancel job: {}", e)))?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // A worker that already claimed the entry skips the job
+        sqlx::query("DELETE FROM job_queue WHERE job_id = ? AND claimed_at IS NULL")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to remove job from queue: {}", e))
+            })?;
+
+        Ok(true)
+    }
+
+    /// Cancel the queued jobs `job` supersedes: older jobs for the same
+    /// project and branch (or tag, or pull request) that haven't started yet.
+    /// Dry runs, rollbacks, resumed and retried jobs are kept. Unclaimed queue
+    /// entries are removed; a worker that already claimed one skips it.
+    /// Returns the ids of the cancelled jobs.
+    pub async fn cancel_superseded_jobs(&self, job: &Job) -> Result<Vec<String>, CicdError> {
+        let cancelled: Vec<(String,)> = sqlx::query_as(
+            r#"
+            UPDATE jobs
+            SET status = 'cancelled',
+                error = ?,
+                completed_at = ?
+            WHERE status = 'queued'
+              AND project_name = ? AND branch = ?
+              AND tag IS ? AND pull_request IS ?
+              AND dry_run = 0 AND rollback_of IS NULL AND resumed_from IS NULL
+              AND retried_from IS NULL
+              AND id != ? AND created_at <= (SELECT created_at FROM jobs WHERE id = ?)
+            RETURNING id
+            "#,
+        )
+        .bind(format!("Superseded by job {}", job.id))
+        .bind(Utc::now().to_rfc3339())
+        .bind(&job.project_name)
+        .bind(&job.branch)
+        .bind(&job.tag)
+        .bind(job.pull_request)
+        .bind(&job.id)
+        .bind(&job.id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to cancel superseded jobs: {}", e))
+        })?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM job_queue
+            WHERE claimed_at IS NULL
+              AND job_id IN (SELECT id FROM jobs WHERE status = 'cancelled')
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to remove cancelled jobs from queue: {}", e))
+        })?;
+
+        Ok(cancelled.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Clean up after an unclean shutdown: jobs that were running are marked
+    /// failed and dropped from the queue; unclaimed queue entries stay queued.
+    /// Jobs created but not queued yet are marked failed too; their webhook
+    /// is processed again (see `get_pending_intake`). Returns the number of
+    /// interrupted jobs.
+    pub async fn recover_interrupted_jobs(&self) -> Result<u64, CicdError> {
+        let now = Utc::now().to_rfc3339();
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'failed',
+                error = 'Interrupted by server restart',
+                completed_at = ?
+            WHERE status = 'running'
+            "#,
+        )
+        .bind(&now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to recover interrupted jobs: {}", e))
+        })?;
+
+        sqlx::query("DELETE FROM job_queue WHERE claimed_at IS NOT NULL")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to clear claimed queue entries: {}", e))
+            })?;
+
+        // Jobs created but never queued; their webhook is processed again
+        // from the intake table
+        let unqueued = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = 'failed',
+                error = 'Interrupted by server restart before it was queued',
+                completed_at = ?
+            WHERE status = 'queued'
+              AND id NOT IN (SELECT job_id FROM job_queue)
+            "#,
+        )
+        .bind(&now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to recover unqueued jobs: {}", e)))?;
+
+        Ok(result.rows_affected() + unqueued.rows_affected())
+    }
+
+    /// Count queued jobs
+    pub async fn get_queued_count(&self) -> Result<i64, CicdError> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM jobs WHERE status = 'queued'")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| CicdError::DatabaseError(format!("Failed to count queued jobs: {}", e)))?;
+
+        Ok(count.0)
+    }
+
+    /// Count running jobs
+    pub async fn get_running_count(&self) -> Result<i64, CicdError> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM jobs WHERE status = 'running'")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                CicdError::DatabaseError(format!("Failed to count running jobs: {}", e))
+            })?;
+
+        Ok(count.0)
+    }
+
+    /// Get the currently running job (if any)
+    pub async fn get_current_job(&self) -> Result<Option<Job>, CicdError> {
+        let row = sqlx::query_as::<_, JobRow>(
+            r#"
+            SELECT
+                id, project_name, branch, status,
+                commit_sha, commit_message, commit_author_name,
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
+            FROM jobs
+            WHERE status = 'running'
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch current job: {}", e)))?;
 
         Ok(row.map(|r| r.into()))
     }
 
-    /// Count completed jobs (success + failed)
+    /// Count completed jobs (success, failed or timed out)
     pub async fn get_completed_count(&self) -> Result<i64, CicdError> {
-        let count: (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM jobs WHERE status IN ('success', 'failed')")
-                .fetch_one(&self.pool)
-                .await
-                .map_err(|e| {
-                    CicdError::DatabaseError(format!("Failed to count completed jobs: {}", e))
-                })?;
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM jobs WHERE status IN ('success', 'failed', 'timed_out')",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to count completed jobs: {}", e)))?;
 
         Ok(count.0)
     }
@@ -369,6 +1533,9 @@ impl SqlJobStore {
             JobStatus::Running => "running",
             JobStatus::Success => "success",
             JobStatus::Failed => "failed",
+            JobStatus::Skipped => "skipped",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::TimedOut => "timed_out",
         };
 
         let rows = sqlx::query_as::<_, JobRow>(
@@ -376,7 +1543,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             FROM jobs
             WHERE status = ?
             ORDER BY created_at DESC
@@ -392,6 +1561,97 @@ impl SqlJobStore {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    /// Get the most recent successful, non-dry-run job on a branch, optionally
+    /// skipping jobs that built `exclude_sha`
+    pub async fn get_last_successful_job(
+        &self,
+        project: &str,
+        branch: &str,
+        exclude_sha: Option<&str>,
+    ) -> Result<Option<Job>, CicdError> {
+        let row = sqlx::query_as::<_, JobRow>(
+            r#"
+            SELECT
+                id, project_name, branch, status,
+                commit_sha, commit_message, commit_author_name,
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
+            FROM jobs
+            WHERE project_name = ? AND branch = ?
+              AND status = 'success' AND dry_run = 0
+              AND commit_sha IS NOT NULL
+              AND (? IS NULL OR commit_sha != ?)
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(project)
+        .bind(branch)
+        .bind(exclude_sha)
+        .bind(exclude_sha)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to fetch last successful job: {}", e))
+        })?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    /// Get the completed, non-dry-run job that ran before `id` on the same
+    /// project and branch
+    pub async fn get_previous_run(&self, id: &str) -> Result<Option<Job>, CicdError> {
+        let row = sqlx::query_as::<_, JobRow>(
+            r#"
+            SELECT
+                j.id, j.project_name, j.branch, j.status,
+                j.commit_sha, j.commit_message, j.commit_author_name,
+                j.started_at, j.completed_at, j.output, j.output_truncated, j.error, j.dry_run,
+                j.pinned, j.rollback_of, j.tag, j.resumed_from, j.retried_from, j.pull_request,
+                j.rate_limit_bypassed
+            FROM jobs j
+            JOIN jobs current ON current.id = ?
+            WHERE j.project_name = current.project_name AND j.branch = current.branch
+              AND j.id != current.id
+              AND j.status IN ('success', 'failed', 'timed_out') AND j.dry_run = 0
+              AND j.created_at <= current.created_at
+            ORDER BY j.created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch previous run: {}", e)))?;
+
+        Ok(row.map(|r| r.into()))
+    }
+
+    /// Get pinned jobs
+    pub async fn get_pinned_jobs(&self, limit: i64) -> Result<Vec<Job>, CicdError> {
+        let rows = sqlx::query_as::<_, JobRow>(
+            r#"
+            SELECT
+                id, project_name, branch, status,
+                commit_sha, commit_message, commit_author_name,
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
+            FROM jobs
+            WHERE pinned = 1
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| CicdError::DatabaseError(format!("Failed to fetch pinned jobs: {}", e)))?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     /// Get jobs by project and branch
     pub async fn get_jobs_by_branch(
         &self,
@@ -404,7 +1664,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             FROM jobs
             WHERE project_name = ? AND branch = ?
             ORDER BY created_at DESC
@@ -432,7 +1694,9 @@ impl SqlJobStore {
             SELECT
                 id, project_name, branch, status,
                 commit_sha, commit_message, commit_author_name,
-                started_at, completed_at, output, output_truncated, error, dry_run
+                started_at, completed_at, output, output_truncated, error, dry_run, pinned,
+                rollback_of, tag, resumed_from, retried_from, pull_request,
+                rate_limit_bypassed
             FROM jobs
             WHERE branch = ?
             ORDER BY created_at DESC
@@ -465,6 +1729,22 @@ struct JobRow {
     output_truncated: Option<bool>,
     error: Option<String>,
     dry_run: Option<bool>,
+    pinned: Option<bool>,
+    rollback_of: Option<String>,
+    tag: Option<String>,
+    resumed_from: Option<String>,
+    retried_from: Option<String>,
+    pull_request: Option<i64>,
+    rate_limit_bypassed: Option<bool>,
+    /// Only selected for single-job lookups
+    #[sqlx(default)]
+    env_snapshot: Option<String>,
+    /// Only selected for single-job lookups
+    #[sqlx(default)]
+    output_diff: Option<String>,
+    /// Only selected for single-job lookups
+    #[sqlx(default)]
+    changelog: Option<String>,
 }
 
 impl From<JobRow> for Job {
@@ -474,6 +1754,9 @@ impl From<JobRow> for Job {
             "running" => JobStatus::Running,
             "success" => JobStatus::Success,
             "failed" => JobStatus::Failed,
+            "skipped" => JobStatus::Skipped,
+            "cancelled" => JobStatus::Cancelled,
+            "timed_out" => JobStatus::TimedOut,
             _ => JobStatus::Failed, // Default fallback
         };
 
@@ -502,6 +1785,20 @@ impl From<JobRow> for Job {
             output_truncated: row.output_truncated.unwrap_or(false),
             error: row.error,
             dry_run: row.dry_run.unwrap_or(false),
+            pinned: row.pinned.unwrap_or(false),
+            rollback_of: row.rollback_of,
+            tag: row.tag,
+            resumed_from: row.resumed_from,
+            retried_from: row.retried_from,
+            pull_request: row.pull_request,
+            rate_limit_bypassed: row.rate_limit_bypassed.unwrap_or(false),
+            env: row
+                .env_snapshot
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            output_diff: row
+                .output_diff
+                .and_then(|json| serde_json::from_str(&json).ok()),
+            changelog: row.changelog,
         }
     }
 }
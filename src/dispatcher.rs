@@ -0,0 +1,637 @@
+//! Job dispatcher: persistent queue plus a scheduler that executes jobs
+//!
+//! Webhook handlers only create the job record and enqueue it. The scheduler
+//! claims queued entries from the `job_queue` table and runs each pipeline
+//! in its own task, so queued work survives restarts and execution is
+//! decoupled from HTTP requests. Up to `max_concurrent_jobs` jobs run at the
+//! same time, but never two sharing a checkout (`repo_path`) or a
+//! `concurrency_group`.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{Notify, Semaphore};
+use tracing::{error, info, warn};
+
+use crate::api::stream::JobEvent;
+use crate::db::store::{JobLog, QueuedJob, SqlJobStore};
+use crate::dry_run;
+use crate::error::{CicdError, Result};
+use crate::github_app;
+use crate::job::JobStatus;
+use crate::output_diff;
+use crate::pushgateway;
+use crate::stop::{StopReason, StopSignal};
+use crate::utils::{PipelineOptions, run_job_pipeline};
+use crate::webhook::WebhookData;
+use crate::workspace::{self, Workspace};
+use crate::{ProjectConfig, SharedState};
+
+/// Jobs run at the same time by default
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 1;
+
+/// How often the idle scheduler re-checks the queue even without a
+/// notification
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time a stopped pipeline gets on top of its scripts' kill grace period to
+/// wind down before it is abandoned
+const STOP_MARGIN: Duration = Duration::from_secs(5);
+
+/// Hands queued jobs to the scheduler
+pub struct Dispatcher {
+    notify: Notify,
+    /// One permit per job that may run at the same time
+    slots: Arc<Semaphore>,
+    max_concurrent_jobs: usize,
+    /// Stop signals of the jobs being executed, by job id
+    running: Mutex<HashMap<String, StopSignal>>,
+}
+
+impl Dispatcher {
+    pub fn new(max_concurrent_jobs: usize) -> Self {
+        let max_concurrent_jobs = max_concurrent_jobs.max(1);
+        Self {
+            notify: Notify::new(),
+            slots: Arc::new(Semaphore::new(max_concurrent_jobs)),
+            max_concurrent_jobs,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Number of jobs this dispatcher runs at the same time
+    pub fn max_concurrent_jobs(&self) -> usize {
+        self.max_concurrent_jobs
+    }
+
+    /// Persist a job in the queue and wake the scheduler. With `hold_until`,
+    /// the job isn't picked up before that time.
+    pub async fn enqueue(
+        &self,
+        job_store: &SqlJobStore,
+        job_id: &str,
+        project: &ProjectConfig,
+        webhook_data: &WebhookData,
+        hold_until: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let project_json = serde_json::to_string(&project.snapshot()).map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to serialize project config: {}", e))
+        })?;
+        let webhook_json = serde_json::to_string(webhook_data).map_err(|e| {
+            CicdError::DatabaseError(format!("Failed to serialize webhook data: {}", e))
+        })?;
+        job_store
+            .set_pipeline_snapshot(job_id, &project_json, &webhook_json)
+            .await?;
+        job_store
+            .enqueue_job(job_id, &project_json, &webhook_json, 0, hold_until)
+            .await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Wake the idle scheduler, e.g. after held jobs were released
+    pub fn wake_all(&self) {
+        self.notify.notify_waiters();
+    }
+
+    /// Stop a job being executed; its running script gets `SIGTERM`,
+    /// then `SIGKILL` along with the processes it started once the
+    /// project's kill grace period is over. Returns false if the job isn't
+    /// running.
+    pub fn cancel(&self, job_id: &str) -> bool {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        match running.get(job_id) {
+            Some(stop) => {
+                stop.stop(StopReason::Cancelled);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if the job is being executed
+    pub fn is_running(&self, job_id: &str) -> bool {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(job_id)
+    }
+
+    /// Register a job as executing until the returned handle is dropped
+    fn register(&self, job_id: &str, stop: StopSignal) -> RunningJob<'_> {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(job_id.to_string(), stop);
+        RunningJob {
+            dispatcher: self,
+            job_id: job_id.to_string(),
+        }
+    }
+}
+
+/// A job being executed, cancellable through `Dispatcher::cancel`
+struct RunningJob<'a> {
+    dispatcher: &'a Dispatcher,
+    job_id: String,
+}
+
+impl Drop for RunningJob<'_> {
+    fn drop(&mut self) {
+        self.dispatcher
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.job_id);
+    }
+}
+
+/// Spawn the dispatcher's scheduler task
+pub fn spawn_scheduler(state: SharedState) {
+    tokio::spawn(async move { scheduler_loop(state).await });
+}
+
+/// Claim queued jobs while a slot is free, running each in its own task
+async fn scheduler_loop(state: SharedState) {
+    info!(
+        "Scheduler started, running up to {} job(s) at a time",
+        state.dispatcher.max_concurrent_jobs()
+    );
+    loop {
+        let Ok(slot) = state.dispatcher.slots.clone().acquire_owned().await else {
+            return;
+        };
+        match state.job_store.claim_next_queued().await {
+            Ok(Some(queued)) => {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let job_id = queued.job_id.clone();
+                    execute_job(&state, queued).await;
+                    if let Err(e) = state.job_store.remove_from_queue(&job_id).await {
+                        error!("Failed to remove job {} from queue: {}", job_id, e);
+                    }
+                    drop(slot);
+                    // Jobs waiting for the checkout can run now
+                    state.dispatcher.notify.notify_one();
+                });
+            }
+            Ok(None) => {
+                drop(slot);
+                let _ =
+                    tokio::time::timeout(IDLE_POLL_INTERVAL, state.dispatcher.notify.notified())
+                        .await;
+            }
+            Err(e) => {
+                drop(slot);
+                error!("Scheduler failed to claim a job: {}", e);
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Run a claimed job to completion (or simulate it for dry runs)
+async fn execute_job(state: &SharedState, queued: QueuedJob) {
+    let job_id = queued.job_id;
+    let mut project: ProjectConfig = match serde_json::from_str(&queued.project_config) {
+        Ok(p) => p,
+        Err(e) => {
+            fail_unrunnable(
+                state,
+                &job_id,
+                format!("Invalid queued project config: {}", e),
+            )
+            .await;
+            return;
+        }
+    };
+    let webhook_data: WebhookData = match serde_json::from_str(&queued.webhook_data) {
+        Ok(w) => w,
+        Err(e) => {
+            fail_unrunnable(
+                state,
+                &job_id,
+                format!("Invalid queued webhook data: {}", e),
+            )
+            .await;
+            return;
+        }
+    };
+    // Literal secrets aren't stored with the job; they come from the live
+    // config like the `[server]` settings below
+    let restored = {
+        let config = state.config.load();
+        let current = config.project.iter().find(|p| p.name == project.name);
+        project.restore_secrets(current)
+    };
+    if let Err(e) = restored {
+        fail_unrunnable(state, &job_id, e.to_string()).await;
+        return;
+    }
+    let dry_run = match state.job_store.get_job(&job_id).await {
+        Ok(Some(job)) => job.dry_run,
+        Ok(None) => {
+            warn!("Queued job {} no longer exists, skipping", job_id);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to load queued job {}: {}", job_id, e);
+            return;
+        }
+    };
+
+    // Shell mode, ANSI handling, the artifact, cache and workspace
+    // directories and the command policy come from the live `[server]`
+    // settings
+    let (shell, ansi, artifacts_dir, cache_dir, workspace_dir, workspace, command_policy) = {
+        let config = state.config.load();
+        let workspace = project.workspace.as_ref().map(|workspace| {
+            Workspace::new(
+                workspace,
+                config.server.workspace_dir(),
+                &project.name,
+                &webhook_data.repo_path,
+                &job_id,
+            )
+        });
+        (
+            project.shell(&config.server),
+            project.ansi_output(&config.server),
+            config.server.artifacts_dir().to_string(),
+            config.server.cache_dir().to_string(),
+            config.server.workspace_dir().to_string(),
+            workspace,
+            config.server.command_policy(),
+        )
+    };
+
+    // Cancellable from here on; dry runs finish right away
+    let stop = StopSignal::new(project.kill_grace());
+    let _running = (!dry_run).then(|| state.dispatcher.register(&job_id, stop.clone()));
+
+    // Mark job as running, unless a newer push superseded it while it
+    // was queued
+    match state.job_store.start_job(&job_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            info!("Job {} was cancelled before it started, skipping", job_id);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to update job status to running: {}", e);
+            return;
+        }
+    }
+
+    // Handle dry run - validate instead of executing
+    if dry_run {
+        info!(
+            "[DRY_RUN] Job {} - Validating pipeline for project '{}' branch '{}'",
+            job_id, webhook_data.project_name, webhook_data.branch
+        );
+
+        // Broadcast running event
+        state.job_events.send(JobEvent {
+            event_type: "running".to_string(),
+            job_id: job_id.clone(),
+            project_name: webhook_data.project_name.clone(),
+            branch: webhook_data.branch.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+        });
+
+        let checks =
+            dry_run::validate(&project, &webhook_data, shell.as_deref(), &command_policy).await;
+        let now = Utc::now();
+        for (sequence, check) in checks.iter().enumerate() {
+            let log = JobLog {
+                id: None,
+                job_id: job_id.clone(),
+                sequence: sequence as i32,
+                log_type: check.log_type.clone(),
+                command: check.command.clone(),
+                started_at: now,
+                completed_at: Some(now),
+                duration_ms: Some(0),
+                exit_code: None,
+                output: Some(format!("[DRY_RUN] {}", check.message)),
+                status: if check.passed { "success" } else { "failed" }.to_string(),
+                signal: None,
+                stdout: None,
+                stderr: None,
+                output_truncated: false,
+            };
+            let _ = state.job_store.add_log(&log).await;
+        }
+
+        let failed = checks.iter().filter(|c| !c.passed).count();
+        let (status, error, event_type) = if failed == 0 {
+            (JobStatus::Success, None, "success")
+        } else {
+            (
+                JobStatus::Failed,
+                Some(format!(
+                    "Dry run validation failed: {} check(s) failed",
+                    failed
+                )),
+                "failed",
+            )
+        };
+        if let Err(e) = state
+            .job_store
+            .complete_job(
+                &job_id,
+                status,
+                Some(dry_run::report(&checks)),
+                error,
+                Utc::now(),
+            )
+            .await
+        {
+            error!("[DRY_RUN] Failed to complete job: {}", e);
+        }
+
+        info!(
+            "[DRY_RUN] Job {} validated: {} of {} check(s) failed.",
+            job_id,
+            failed,
+            checks.len()
+        );
+        state.job_events.send(JobEvent {
+            event_type: event_type.to_string(),
+            job_id: job_id.clone(),
+            project_name: webhook_data.project_name.clone(),
+            branch: webhook_data.branch.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+        });
+
+        return;
+    }
+
+    info!(
+        "Job {} - Push event for project '{}' branch '{}'. Starting job pipeline.",
+        job_id, webhook_data.project_name, webhook_data.branch
+    );
+
+    // Broadcast job running event
+    state.job_events.send(JobEvent {
+        event_type: "running".to_string(),
+        job_id: job_id.clone(),
+        project_name: webhook_data.project_name.clone(),
+        branch: webhook_data.branch.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    // Run the complete pipeline with hooks, with a GitHub App token if the
+    // project uses one, until it finishes, is cancelled or times out
+    let pipeline = async {
+        let github_token = github_app::job_token(state, &project, &webhook_data).await?;
+        let options = PipelineOptions {
+            github_token,
+            shell: shell.as_deref(),
+            ansi,
+            stop: stop.clone(),
+            artifacts_dir: &artifacts_dir,
+            cache_dir: &cache_dir,
+            workspace: workspace.as_ref(),
+            command_policy: &command_policy,
+        };
+        run_job_pipeline(
+            &project,
+            &webhook_data,
+            &state.job_store,
+            &job_id,
+            state.log_chunks.clone(),
+            options,
+        )
+        .await
+    };
+    let result = run_stoppable(pipeline, &stop, project.timeout()).await;
+    match result {
+        Err(CicdError::Skipped(reason)) => {
+            info!("Job {} skipped: {}", job_id, reason);
+            let output = format!("Skipped: {}", reason);
+            if let Err(e) = state
+                .job_store
+                .complete_job(&job_id, JobStatus::Skipped, Some(output), None, Utc::now())
+                .await
+            {
+                error!("Failed to mark job as skipped: {}", e);
+            }
+            state.job_events.send(JobEvent {
+                event_type: "skipped".to_string(),
+                job_id: job_id.clone(),
+                project_name: webhook_data.project_name.clone(),
+                branch: webhook_data.branch.clone(),
+                timestamp: Utc::now().to_rfc3339(),
+            });
+        }
+        Ok(output) => {
+            info!("Job {} completed successfully.", job_id);
+            if let Err(e) = state
+                .job_store
+                .complete_job(&job_id, JobStatus::Success, Some(output), None, Utc::now())
+                .await
+            {
+                error!("Failed to mark job as success: {}", e);
+            }
+            state.job_events.send(JobEvent {
+                event_type: "success".to_string(),
+                job_id: job_id.clone(),
+                project_name: webhook_data.project_name.clone(),
+                branch: webhook_data.branch.clone(),
+                timestamp: Utc::now().to_rfc3339(),
+            });
+        }
+        Err(e) => {
+            let (status, event_type) = match e {
+                CicdError::TimedOut(_) => (JobStatus::TimedOut, "timed_out"),
+                CicdError::Cancelled(_) => (JobStatus::Cancelled, "cancelled"),
+                _ => (JobStatus::Failed, "failed"),
+            };
+            match status {
+                JobStatus::Cancelled => warn!("Job {} cancelled", job_id),
+                _ => error!("Job {} failed: {}", job_id, e),
+            }
+            if status != JobStatus::Failed
+                && let Err(db_err) = state
+                    .job_store
+                    .stop_running_logs(&job_id, event_type, &e.to_string(), Utc::now())
+                    .await
+            {
+                error!("Failed to close stopped steps: {}", db_err);
+            }
+            if let Err(db_err) = state
+                .job_store
+                .complete_job(
+                    &job_id,
+                    status.clone(),
+                    None,
+                    Some(e.to_string()),
+                    Utc::now(),
+                )
+                .await
+            {
+                error!("Failed to mark job as failed: {}", db_err);
+            }
+            state.job_events.send(JobEvent {
+                event_type: event_type.to_string(),
+                job_id: job_id.clone(),
+                project_name: webhook_data.project_name.clone(),
+                branch: webhook_data.branch.clone(),
+                timestamp: Utc::now().to_rfc3339(),
+            });
+            if status != JobStatus::Cancelled {
+                pause_after_failures(state, &project, &job_id, &webhook_data).await;
+            }
+        }
+    }
+
+    // Only the project's newest `keep` workspaces, this job's included, are
+    // kept once it ends
+    if let Some(config) = &project.workspace {
+        let clean = {
+            let state = state.clone();
+            let workspace_dir = workspace_dir.clone();
+            let project_name = project.name.clone();
+            let repo_path = webhook_data.repo_path.clone();
+            let keep = config.keep();
+            let job_id = job_id.clone();
+            move || {
+                workspace::clean(
+                    &workspace_dir,
+                    &project_name,
+                    Some(&repo_path),
+                    keep,
+                    |id| id != job_id && state.dispatcher.is_running(id),
+                )
+            }
+        };
+        match tokio::task::spawn_blocking(clean).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Failed to clean workspaces of '{}': {}", project.name, e),
+            Err(e) => error!("Failed to clean workspaces of '{}': {}", project.name, e),
+        }
+    }
+
+    output_diff::record(state, &job_id).await;
+    pushgateway::push_job_metrics(state, &job_id);
+}
+
+/// Run a job's pipeline until it finishes, or is stopped through `stop` or by
+/// running past `timeout`. A stopped pipeline terminates its running script
+/// and skips the remaining steps; one that doesn't wind down within the kill
+/// grace period (e.g. waiting for a git operation) is abandoned, which kills
+/// whatever is left of its script.
+async fn run_stoppable<F>(
+    pipeline: F,
+    stop: &StopSignal,
+    timeout: Option<Duration>,
+) -> Result<String>
+where
+    F: Future<Output = Result<String>>,
+{
+    let mut pipeline = std::pin::pin!(pipeline);
+    let deadline = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = &mut pipeline => return result,
+        _ = stop.stopped() => {}
+        _ = deadline => {
+            let seconds = timeout.unwrap_or_default().as_secs();
+            warn!("Job ran longer than {} seconds, stopping it", seconds);
+            stop.stop(StopReason::TimedOut(format!(
+                "job ran longer than {} seconds",
+                seconds
+            )));
+        }
+    }
+
+    let reason = stop.stopped().await;
+    match tokio::time::timeout(stop.grace + STOP_MARGIN, pipeline).await {
+        Ok(Err(e @ (CicdError::TimedOut(_) | CicdError::Cancelled(_)))) => Err(e),
+        Ok(_) => Err(reason.error("")),
+        Err(_) => {
+            warn!("Stopped pipeline didn't wind down in time, abandoning it");
+            Err(reason.error(""))
+        }
+    }
+}
+
+/// Put a project into maintenance mode once it reaches
+/// `max_consecutive_failures` failed jobs in a row, so a broken script isn't
+/// run again on every push. It stays paused until maintenance is lifted.
+async fn pause_after_failures(
+    state: &SharedState,
+    project: &ProjectConfig,
+    job_id: &str,
+    webhook_data: &WebhookData,
+) {
+    // Failing pull request checks don't break deploys
+    if webhook_data.pull_request.is_some() {
+        return;
+    }
+    let Some(max_failures) = project.max_consecutive_failures else {
+        return;
+    };
+    let failures = match state
+        .job_store
+        .get_consecutive_failures(&project.name)
+        .await
+    {
+        Ok(failures) => failures,
+        Err(e) => {
+            error!(
+                "Failed to count failures of project '{}': {}",
+                project.name, e
+            );
+            return;
+        }
+    };
+    if failures < i64::from(max_failures.max(1)) {
+        return;
+    }
+
+    let reason = format!(
+        "Paused automatically after {} consecutive failed jobs (last: {})",
+        failures, job_id
+    );
+    match state
+        .job_store
+        .enable_maintenance(&project.name, Some(&reason))
+        .await
+    {
+        Ok(true) => {
+            error!(
+                "Project '{}' paused after {} consecutive failed jobs; lift maintenance mode to re-enable it",
+                project.name, failures
+            );
+            state.job_events.send(JobEvent {
+                event_type: "paused".to_string(),
+                job_id: job_id.to_string(),
+                project_name: webhook_data.project_name.clone(),
+                branch: webhook_data.branch.clone(),
+                timestamp: Utc::now().to_rfc3339(),
+            });
+        }
+        Ok(false) => {}
+        Err(e) => error!("Failed to pause project '{}': {}", project.name, e),
+    }
+}
+
+/// Mark a queued job that can't be executed as failed
+async fn fail_unrunnable(state: &SharedState, job_id: &str, error: String) {
+    error!("Job {} cannot run: {}", job_id, error);
+    if let Err(e) = state
+        .job_store
+        .complete_job(job_id, JobStatus::Failed, None, Some(error), Utc::now())
+        .await
+    {
+        error!("Failed to mark job as failed: {}", e);
+    }
+}
@@ -0,0 +1,315 @@
+//! Dry-run validation: checks that a job could run, without running it
+//!
+//! Each check corresponds to a pipeline step (`git_fetch`, `main_script`, ...)
+//! and is recorded as that step's log entry, giving a per-step pass/fail report.
+
+use std::path::{Path, PathBuf};
+
+use crate::ProjectConfig;
+use crate::command_policy::CommandPolicy;
+use crate::executor::ExecutorConfig;
+use crate::git;
+use crate::release::ReleaseConfig;
+use crate::utils::split_command;
+use crate::webhook::WebhookData;
+
+/// Result of validating one pipeline step
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub log_type: String,
+    pub command: Option<String>,
+    pub passed: bool,
+    pub message: String,
+}
+
+impl Check {
+    fn new(log_type: &str, command: Option<String>, result: Result<String, String>) -> Self {
+        let (passed, message) = match result {
+            Ok(message) => (true, message),
+            Err(message) => (false, message),
+        };
+        Self {
+            log_type: log_type.to_string(),
+            command,
+            passed,
+            message,
+        }
+    }
+}
+
+/// Validate everything the pipeline would touch for this job
+pub async fn validate(
+    project: &ProjectConfig,
+    webhook_data: &WebhookData,
+    shell: Option<&str>,
+    policy: &CommandPolicy,
+) -> Vec<Check> {
+    let repo_path = webhook_data.repo_path.clone();
+    // A pull request's head branch may only exist in a fork
+    let branch = match &webhook_data.pull_request {
+        Some(pr) => pr.base_branch.clone(),
+        None => webhook_data.branch.clone(),
+    };
+    let tag = webhook_data.tag.clone();
+
+    let mut checks = {
+        let project = project.clone();
+        tokio::task::spawn_blocking(move || {
+            validate_git(&project, &repo_path, &branch, tag.as_deref())
+        })
+        .await
+        .unwrap_or_else(|e| {
+            vec![Check::new(
+                "git_fetch",
+                None,
+                Err(format!("Validation task failed: {}", e)),
+            )]
+        })
+    };
+
+    if let Some(release) = &project.release {
+        checks.push(Check::new(
+            "release_prepare",
+            Some(format!("prepare {}", release.releases_dir().display())),
+            check_release(release),
+        ));
+    }
+
+    let script_dir = project.script_dir(&webhook_data.repo_path);
+    // In shell mode, only the shell can be checked before the script runs,
+    // and in a container only the container runtime
+    let runtime = match &project.executor {
+        Some(ExecutorConfig::Container(container)) => Some(container.runtime()),
+        _ => None,
+    };
+    let check = |log_type: &str, script: &str, dir: &str| {
+        let result =
+            check_allowed(policy, shell.unwrap_or(script), dir).and_then(|()| match runtime {
+                Some(runtime) => check_script(runtime, &script_dir),
+                None => check_script(shell.unwrap_or(script), dir),
+            });
+        Check::new(log_type, Some(script.to_string()), result)
+    };
+    if let Some(script) = &project.pre_script {
+        checks.push(check("pre_script", script, &script_dir));
+    }
+    for step in project.main_steps(&webhook_data.branch) {
+        checks.push(check(&step.name, &step.command, &step.dir(&script_dir)));
+    }
+    let hooks = [
+        ("post_success", project.post_success_script.as_deref()),
+        ("post_failure", project.post_failure_script.as_deref()),
+        ("post_script", project.post_script.as_deref()),
+        ("post_always", project.post_always_script.as_deref()),
+    ];
+    for (log_type, script) in hooks {
+        if let Some(script) = script {
+            checks.push(check(log_type, script, &script_dir));
+        }
+    }
+
+    for mirror in project.mirrors.iter().flatten() {
+        let command = format!("git push {} {}", mirror.url, mirror.refspec(webhook_data));
+        let result = {
+            let mirror = mirror.clone();
+            let repo_path = webhook_data.repo_path.clone();
+            tokio::task::spawn_blocking(move || mirror.check(&repo_path))
+                .await
+                .map_err(|e| format!("Validation task failed: {}", e))
+                .and_then(|r| r.map_err(|e| e.to_string()))
+        }
+        .map(|_| format!("{} accepts pushes", mirror.url));
+        checks.push(Check::new("mirror", Some(command), result));
+    }
+    checks
+}
+
+/// Human-readable summary of all checks, used as the job output
+pub fn report(checks: &[Check]) -> String {
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    let mut output = String::from("[DRY_RUN] Validation report\n\n");
+    for check in checks {
+        output.push_str(&format!(
+            "[{}] {}: {}\n",
+            if check.passed { "PASS" } else { "FAIL" },
+            check.log_type,
+            check.message
+        ));
+    }
+    output.push('\n');
+    if failed == 0 {
+        output.push_str("All checks passed. No commands were executed.\n");
+    } else {
+        output.push_str(&format!(
+            "{} of {} check(s) failed. No commands were executed.\n",
+            failed,
+            checks.len()
+        ));
+    }
+    output
+}
+
+fn validate_git(
+    project: &ProjectConfig,
+    repo_path: &str,
+    branch: &str,
+    tag: Option<&str>,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if let Err(e) = git::open(repo_path) {
+        checks.push(Check::new(
+            "git_fetch",
+            Some("git fetch".to_string()),
+            Err(format!("'{}' is not a git repository: {}", repo_path, e)),
+        ));
+        return checks;
+    }
+
+    // Remote reachability (and credentials) via ls-remote
+    let remote_refs = git::ls_remote(repo_path, "origin");
+    checks.push(Check::new(
+        "git_fetch",
+        Some("git ls-remote origin".to_string()),
+        match &remote_refs {
+            Ok(refs) => Ok(format!("Remote 'origin' reachable ({} refs)", refs.len())),
+            Err(e) => Err(format!("Remote 'origin' not reachable: {}", e)),
+        },
+    ));
+
+    if let Some(tag) = tag {
+        checks.push(check_tag(repo_path, tag, &remote_refs));
+        return checks;
+    }
+
+    // Branch availability: on the remote if it answered, else the last fetched state
+    let (log_type, command) = if project.should_reset_to_remote() {
+        ("git_reset", format!("git reset --hard origin/{}", branch))
+    } else {
+        ("git_switch", format!("git switch {}", branch))
+    };
+    let head_ref = format!("refs/heads/{}", branch);
+    let result = match &remote_refs {
+        Ok(refs) if refs.contains(&head_ref) => Ok(format!("Branch '{}' exists on origin", branch)),
+        Ok(_) => Err(format!("Branch '{}' not found on origin", branch)),
+        Err(_) => match git::rev_parse(repo_path, &format!("origin/{}", branch)) {
+            Ok(sha) => Ok(format!(
+                "origin/{} known locally at {} (remote not checked)",
+                branch,
+                &sha[..7.min(sha.len())]
+            )),
+            Err(_) => Err(format!("origin/{} not found locally", branch)),
+        },
+    };
+    checks.push(Check::new(log_type, Some(command), result));
+
+    checks
+}
+
+/// Release tag availability: on the remote if it answered, else locally
+fn check_tag(repo_path: &str, tag: &str, remote_refs: &crate::error::Result<Vec<String>>) -> Check {
+    let tag_ref = format!("refs/tags/{}", tag);
+    let result = match remote_refs {
+        Ok(refs) if refs.contains(&tag_ref) => Ok(format!("Tag '{}' exists on origin", tag)),
+        Ok(_) => Err(format!("Tag '{}' not found on origin", tag)),
+        Err(_) => match git::rev_parse(repo_path, &tag_ref) {
+            Ok(_) => Ok(format!("Tag '{}' known locally (remote not checked)", tag)),
+            Err(_) => Err(format!("Tag '{}' not found locally", tag)),
+        },
+    };
+    Check::new(
+        "git_reset",
+        Some(format!("git reset --hard {}", tag_ref)),
+        result,
+    )
+}
+
+/// Check that the deploy root exists (or can be created) and that `current`,
+/// if present, is a symlink that can be switched
+fn check_release(release: &ReleaseConfig) -> Result<String, String> {
+    let dir = Path::new(&release.dir);
+    if !dir.is_dir() {
+        return match dir.parent() {
+            Some(parent) if parent.is_dir() => Ok(format!(
+                "{} will be created on the first deploy",
+                dir.display()
+            )),
+            _ => Err(format!(
+                "Neither {} nor its parent directory exists",
+                dir.display()
+            )),
+        };
+    }
+    let current = release.current_link();
+    match std::fs::symlink_metadata(&current) {
+        Ok(meta) if !meta.file_type().is_symlink() => {
+            Err(format!("{} exists and is not a symlink", current.display()))
+        }
+        Ok(_) => Ok(format!(
+            "{} is a symlink to {}",
+            current.display(),
+            release
+                .current_release()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        )),
+        Err(_) => Ok(format!(
+            "{} exists; {} will be created on the first deploy",
+            dir.display(),
+            current.display()
+        )),
+    }
+}
+
+/// Check that a script parses and its command resolves to an executable,
+/// the same way the pipeline will spawn it (relative paths from the script
+/// directory).
+fn check_script(script: &str, script_dir: &str) -> Result<String, String> {
+    let argv = split_command(script)?;
+    let command = argv[0].as_str();
+
+    let path = if command.contains('/') {
+        let path = Path::new(script_dir).join(command);
+        if !path.exists() {
+            return Err(format!("'{}' does not exist in {}", command, script_dir));
+        }
+        path
+    } else {
+        find_in_path(command).ok_or_else(|| format!("'{}' not found in PATH", command))?
+    };
+
+    if !path.is_file() {
+        return Err(format!("'{}' is not a file", path.display()));
+    }
+    if !is_executable(&path) {
+        return Err(format!("'{}' is not executable", path.display()));
+    }
+    Ok(format!("'{}' resolves to {}", command, path.display()))
+}
+
+/// Check that `[server] allowed_commands` lets the script's program start
+fn check_allowed(policy: &CommandPolicy, script: &str, dir: &str) -> Result<(), String> {
+    let argv = split_command(script)?;
+    policy
+        .check_program(&argv[0], dir)
+        .map_err(|e| e.to_string())
+}
+
+fn find_in_path(command: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(command))
+        .find(|path| path.is_file())
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,297 @@
+//! Environment-only configuration: a single project defined through
+//! `CICD_CONFIG_*` environment variables instead of a TOML file. Job scripts
+//! get other `CICD_*` variables (`CICD_PROJECT_NAME`, ...), never ones with
+//! this prefix, so a server started by a job doesn't pick them up as config.
+//!
+//! The variables are collected into a TOML table and deserialized like the
+//! file config, so defaults and validation stay the same.
+
+use toml::{Table, Value};
+
+use crate::error::{CicdError, Result};
+use crate::{CICDConfig, ProjectConfig, ServerConfig};
+
+/// Setting this variable switches to environment-only configuration
+pub const PROJECT_NAME_VAR: &str = "CICD_CONFIG_PROJECT_NAME";
+
+/// How an environment variable's value is interpreted
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    Bool,
+    Int,
+    /// Comma-separated list, e.g. `main,staging`
+    List,
+    /// Comma-separated `key=value` pairs, e.g. `main=./prod.sh,dev=./dev.sh`
+    Map,
+}
+
+/// Environment variable -> project config key
+const PROJECT_VARS: &[(&str, &str, Kind)] = &[
+    (PROJECT_NAME_VAR, "name", Kind::Str),
+    ("CICD_CONFIG_REPO_PATH", "repo_path", Kind::Str),
+    ("CICD_CONFIG_REPO_FULL_NAME", "repo_full_name", Kind::Str),
+    ("CICD_CONFIG_BRANCHES", "branches", Kind::List),
+    ("CICD_CONFIG_RUN_SCRIPT", "run_script", Kind::Str),
+    ("CICD_CONFIG_SHELL", "shell", Kind::Bool),
+    ("CICD_CONFIG_BRANCH_SCRIPTS", "branch_scripts", Kind::Map),
+    ("CICD_CONFIG_ROLLBACK_SCRIPT", "rollback_script", Kind::Str),
+    ("CICD_CONFIG_SEMVER_RELEASES", "semver_releases", Kind::Bool),
+    ("CICD_CONFIG_TAGS", "tags", Kind::List),
+    ("CICD_CONFIG_ON_RELEASE", "on_release", Kind::Bool),
+    ("CICD_CONFIG_ON_PULL_REQUEST", "on_pull_request", Kind::Bool),
+    ("CICD_CONFIG_PR_SCRIPT", "pr_script", Kind::Str),
+    (
+        "CICD_CONFIG_BRANCH_DELETED_SCRIPT",
+        "branch_deleted_script",
+        Kind::Str,
+    ),
+    ("CICD_CONFIG_RELEASE_SCRIPT", "release_script", Kind::Str),
+    ("CICD_CONFIG_PROVIDER", "provider", Kind::Str),
+    (
+        "CICD_CONFIG_SKIP_CI_PATTERNS",
+        "skip_ci_patterns",
+        Kind::List,
+    ),
+    ("CICD_CONFIG_IGNORE_PUSHERS", "ignore_pushers", Kind::List),
+    ("CICD_CONFIG_ONLY_AUTHORS", "only_authors", Kind::List),
+    ("CICD_CONFIG_IGNORE_FORCED", "ignore_forced", Kind::Bool),
+    (
+        "CICD_CONFIG_ONLY_MERGE_COMMITS",
+        "only_merge_commits",
+        Kind::Bool,
+    ),
+    (
+        "CICD_CONFIG_WITH_WEBHOOK_SECRET",
+        "with_webhook_secret",
+        Kind::Bool,
+    ),
+    (
+        "CICD_CONFIG_ALLOW_SHA1_SIGNATURE",
+        "allow_sha1_signature",
+        Kind::Bool,
+    ),
+    ("CICD_CONFIG_GITHUB_APP", "github_app", Kind::Bool),
+    ("CICD_CONFIG_WEBHOOK_PATH", "webhook_path", Kind::Str),
+    ("CICD_CONFIG_ALLOWED_IPS", "allowed_ips", Kind::List),
+    ("CICD_CONFIG_MATCH_PATHS", "match_paths", Kind::List),
+    ("CICD_CONFIG_WORKING_DIR", "working_dir", Kind::Str),
+    ("CICD_CONFIG_ENV_FILE", "env_file", Kind::Str),
+    ("CICD_CONFIG_RUN_AS_USER", "run_as_user", Kind::Str),
+    ("CICD_CONFIG_NICE", "nice", Kind::Int),
+    ("CICD_CONFIG_IO_PRIORITY", "io_priority", Kind::Str),
+    ("CICD_CONFIG_RESET_TO_REMOTE", "reset_to_remote", Kind::Bool),
+    ("CICD_CONFIG_CHECKOUT_COMMIT", "checkout_commit", Kind::Bool),
+    (
+        "CICD_CONFIG_CANCEL_SUPERSEDED",
+        "cancel_superseded",
+        Kind::Bool,
+    ),
+    (
+        "CICD_CONFIG_CONCURRENCY_GROUP",
+        "concurrency_group",
+        Kind::Str,
+    ),
+    ("CICD_CONFIG_ARTIFACTS", "artifacts", Kind::List),
+    ("CICD_CONFIG_PRE_SCRIPT", "pre_script", Kind::Str),
+    ("CICD_CONFIG_POST_SCRIPT", "post_script", Kind::Str),
+    (
+        "CICD_CONFIG_POST_SUCCESS_SCRIPT",
+        "post_success_script",
+        Kind::Str,
+    ),
+    (
+        "CICD_CONFIG_POST_FAILURE_SCRIPT",
+        "post_failure_script",
+        Kind::Str,
+    ),
+    (
+        "CICD_CONFIG_POST_ALWAYS_SCRIPT",
+        "post_always_script",
+        Kind::Str,
+    ),
+    (
+        "CICD_CONFIG_PRE_SCRIPT_ALLOW_FAILURE",
+        "pre_script_allow_failure",
+        Kind::Bool,
+    ),
+    ("CICD_CONFIG_SCHEDULE", "schedule", Kind::Str),
+    ("CICD_CONFIG_SCHEDULE_BRANCH", "schedule_branch", Kind::Str),
+    (
+        "CICD_CONFIG_MAX_CONSECUTIVE_FAILURES",
+        "max_consecutive_failures",
+        Kind::Int,
+    ),
+    ("CICD_CONFIG_TIMEOUT_SECONDS", "timeout_seconds", Kind::Int),
+    (
+        "CICD_CONFIG_GIT_TIMEOUT_SECONDS",
+        "git_timeout_seconds",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_PRE_SCRIPT_TIMEOUT_SECONDS",
+        "pre_script_timeout_seconds",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_SCRIPT_TIMEOUT_SECONDS",
+        "script_timeout_seconds",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_POST_SCRIPT_TIMEOUT_SECONDS",
+        "post_script_timeout_seconds",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_KILL_GRACE_SECONDS",
+        "kill_grace_seconds",
+        Kind::Int,
+    ),
+    ("CICD_CONFIG_RETRY_COUNT", "retry_count", Kind::Int),
+    (
+        "CICD_CONFIG_RETRY_DELAY_SECONDS",
+        "retry_delay_seconds",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_MAX_STEP_OUTPUT_BYTES",
+        "max_step_output_bytes",
+        Kind::Int,
+    ),
+    ("CICD_CONFIG_STRIP_ANSI", "strip_ansi", Kind::Bool),
+    (
+        "CICD_CONFIG_KEEP_ANSI_IN_DOWNLOAD",
+        "keep_ansi_in_download",
+        Kind::Bool,
+    ),
+    (
+        "CICD_CONFIG_RATE_LIMIT_REQUESTS",
+        "rate_limit_requests",
+        Kind::Int,
+    ),
+    (
+        "CICD_CONFIG_RATE_LIMIT_WINDOW_SECONDS",
+        "rate_limit_window_seconds",
+        Kind::Int,
+    ),
+];
+
+/// The webhook secret, and further comma-separated ones. They aren't mapped
+/// to `webhook_secret(s)`, which are serialized with the config.
+const WEBHOOK_SECRET_VARS: [&str; 2] =
+    ["CICD_CONFIG_WEBHOOK_SECRET", "CICD_CONFIG_WEBHOOK_SECRETS"];
+
+/// Environment variable -> `[server]` config key
+const SERVER_VARS: &[(&str, &str, Kind)] = &[("CICD_CONFIG_BASE_PATH", "base_path", Kind::Str)];
+
+/// Names of all configuration variables. These are removed from job script
+/// environments so values like `CICD_CONFIG_WEBHOOK_SECRET` don't leak into
+/// scripts.
+pub fn variable_names() -> impl Iterator<Item = &'static str> {
+    PROJECT_VARS
+        .iter()
+        .chain(SERVER_VARS)
+        .map(|(var, ..)| *var)
+        .chain(WEBHOOK_SECRET_VARS)
+}
+
+/// Returns true if the project should be configured from the environment
+pub fn is_enabled() -> bool {
+    std::env::var(PROJECT_NAME_VAR).is_ok_and(|v| !v.trim().is_empty())
+}
+
+/// Build the configuration from `CICD_CONFIG_*` environment variables
+pub fn load() -> Result<CICDConfig> {
+    let project = collect(PROJECT_VARS)?;
+    let missing: Vec<&str> = [
+        "CICD_CONFIG_REPO_PATH",
+        "CICD_CONFIG_BRANCHES",
+        "CICD_CONFIG_RUN_SCRIPT",
+    ]
+    .into_iter()
+    .filter(|var| std::env::var(var).is_err())
+    .collect();
+    if !missing.is_empty() {
+        return Err(CicdError::ConfigError(format!(
+            "{} is set but required variables are missing: {}",
+            PROJECT_NAME_VAR,
+            missing.join(", ")
+        )));
+    }
+
+    let mut project: ProjectConfig = Value::Table(project).try_into().map_err(|e| {
+        CicdError::ConfigError(format!("Invalid project environment variables: {}", e))
+    })?;
+    let [secret_var, secrets_var] = WEBHOOK_SECRET_VARS;
+    project
+        .resolved_webhook_secrets
+        .extend(std::env::var(secret_var));
+    if let Ok(secrets) = std::env::var(secrets_var) {
+        project.resolved_webhook_secrets.extend(
+            secrets
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+        );
+    }
+    let server: ServerConfig = Value::Table(collect(SERVER_VARS)?)
+        .try_into()
+        .map_err(|e| {
+            CicdError::ConfigError(format!("Invalid server environment variables: {}", e))
+        })?;
+
+    let config = CICDConfig {
+        server,
+        env: None,
+        templates: None,
+        project: vec![project],
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+fn collect(vars: &[(&str, &str, Kind)]) -> Result<Table> {
+    let mut table = Table::new();
+    for (var, key, kind) in vars {
+        if let Ok(raw) = std::env::var(var) {
+            table.insert(key.to_string(), parse(var, &raw, *kind)?);
+        }
+    }
+    Ok(table)
+}
+
+fn parse(var: &str, raw: &str, kind: Kind) -> Result<Value> {
+    let invalid = |expected: &str| {
+        CicdError::ConfigError(format!(
+            "Invalid value for {}: '{}' (expected {})",
+            var, raw, expected
+        ))
+    };
+    let items = || raw.split(',').map(str::trim).filter(|s| !s.is_empty());
+
+    Ok(match kind {
+        Kind::Str => Value::String(raw.to_string()),
+        Kind::Bool => match raw.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => Value::Boolean(true),
+            "false" | "0" | "no" => Value::Boolean(false),
+            _ => return Err(invalid("true or false")),
+        },
+        Kind::Int => Value::Integer(raw.trim().parse().map_err(|_| invalid("an integer"))?),
+        Kind::List => Value::Array(items().map(|s| Value::String(s.to_string())).collect()),
+        Kind::Map => {
+            let mut map = Table::new();
+            for item in items() {
+                let (key, value) = item
+                    .split_once('=')
+                    .ok_or_else(|| invalid("key=value pairs"))?;
+                map.insert(
+                    key.trim().to_string(),
+                    Value::String(value.trim().to_string()),
+                );
+            }
+            Value::Table(map)
+        }
+    })
+}
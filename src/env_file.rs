@@ -0,0 +1,96 @@
+//! Environment variables from a project's `env_file`
+//!
+//! A dotenv-style file, e.g. with machine-specific settings kept next to the
+//! checkout instead of in the central config. Each line is `NAME=value`,
+//! optionally prefixed with `export`; blank lines and lines starting with `#`
+//! are skipped. Values may be single-quoted (taken literally) or
+//! double-quoted (with `\n`, `\t`, `\"` and `\\` escapes); unquoted values
+//! are trimmed and end at ` #`.
+
+use std::path::Path;
+
+use crate::error::{CicdError, Result};
+
+/// Read and parse the env file at `path`
+pub async fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let contents = tokio::fs::read_to_string(path).await.map_err(|e| {
+        CicdError::ConfigError(format!("Failed to read env_file {}: {}", path.display(), e))
+    })?;
+    parse(&contents)
+        .map_err(|e| CicdError::ConfigError(format!("Invalid env_file {}: {}", path.display(), e)))
+}
+
+/// Parse the variables of an env file, in file order
+pub fn parse(contents: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected NAME=value", index + 1));
+        };
+        let name = name.trim();
+        if !is_valid_name(name) {
+            return Err(format!(
+                "line {}: invalid variable name '{}'",
+                index + 1,
+                name
+            ));
+        }
+        if name.starts_with("CICD_") {
+            return Err(format!(
+                "line {}: {} is reserved for the runner",
+                index + 1,
+                name
+            ));
+        }
+        let value = parse_value(value.trim())
+            .ok_or_else(|| format!("line {}: unterminated quote", index + 1))?;
+        vars.push((name.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// Names are letters, digits and `_`, not starting with a digit
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns `None` for a quoted value without its closing quote
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        return rest.find('\'').map(|end| rest[..end].to_string());
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(parsed),
+                '\\' => match chars.next()? {
+                    'n' => parsed.push('\n'),
+                    't' => parsed.push('\t'),
+                    c @ ('"' | '\\') => parsed.push(c),
+                    c => {
+                        parsed.push('\\');
+                        parsed.push(c);
+                    }
+                },
+                c => parsed.push(c),
+            }
+        }
+        return None;
+    }
+    let value = value.find(" #").map_or(value, |end| &value[..end]);
+    Some(value.trim_end().to_string())
+}
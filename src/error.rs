@@ -9,6 +9,9 @@ pub enum CicdError {
     #[error("Script execution failed: {0}")]
     ScriptExecutionFailed(String),
 
+    #[error("Release step failed: {0}")]
+    ReleaseFailed(String),
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
@@ -23,6 +26,19 @@ pub enum CicdError {
 
     #[error("Database error: {0}")]
     DatabaseError(String),
+
+    #[error("API request failed: {0}")]
+    ApiRequestFailed(String),
+
+    #[error("Timed out: {0}")]
+    TimedOut(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    /// The job turned out to have nothing to do (it ends as `skipped`)
+    #[error("Skipped: {0}")]
+    Skipped(String),
 }
 
 /// Helper type for Results that use CicdError
@@ -0,0 +1,155 @@
+//! Where pipeline scripts run (`[project.executor]`)
+//!
+//! By default scripts run as processes on the server. With the container
+//! executor, each script runs in a fresh container of the configured image
+//! (`docker run --rm`, or `podman`), with the checkout mounted at the same
+//! path, so paths in the job's environment work unchanged. The pipeline reads
+//! the output of whatever process an executor starts and stops it the same
+//! way, so an executor only decides how a script is started.
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::env_config;
+use crate::priority::Priority;
+use crate::run_as::RunAs;
+
+/// `[project.executor]` settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ExecutorConfig {
+    /// Processes on the server (default)
+    #[default]
+    Host,
+    /// A fresh container for each script
+    Container(ContainerConfig),
+}
+
+/// Settings of the container executor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContainerConfig {
+    /// Image the scripts run in, e.g. `node:20`
+    pub image: String,
+    /// Container CLI: `docker` (default), `podman`, or a path to either
+    pub runtime: Option<String>,
+    /// Extra options for `<runtime> run`, e.g. `["--network", "host"]`
+    pub run_args: Option<Vec<String>>,
+}
+
+impl ContainerConfig {
+    pub fn runtime(&self) -> &str {
+        self.runtime.as_deref().unwrap_or("docker")
+    }
+}
+
+impl ExecutorConfig {
+    /// The executor for one job's scripts, run as `user` if given and with
+    /// `priority`. `mounts` are the host paths they work with (checkout,
+    /// release directory, files named in their environment).
+    pub fn executor(
+        &self,
+        mounts: Vec<String>,
+        user: Option<RunAs>,
+        priority: Priority,
+    ) -> Box<dyn Executor> {
+        match self {
+            ExecutorConfig::Host => Box::new(HostExecutor { user, priority }),
+            ExecutorConfig::Container(config) => Box::new(ContainerExecutor {
+                config: config.clone(),
+                mounts,
+                user,
+                priority,
+            }),
+        }
+    }
+}
+
+/// A script to start: its program and arguments, the directory it runs in
+/// and its environment variables
+#[derive(Debug)]
+pub struct Process<'a> {
+    pub program: &'a str,
+    pub args: &'a [String],
+    pub dir: &'a str,
+    pub env: Vec<(&'a str, &'a str)>,
+}
+
+/// Starts the processes of a job's scripts
+pub trait Executor: std::fmt::Debug + Send + Sync {
+    /// The command starting `process`; the pipeline adds its pipes
+    fn command(&self, process: &Process<'_>) -> Command;
+}
+
+/// Runs scripts as processes on the server
+#[derive(Debug)]
+pub struct HostExecutor {
+    user: Option<RunAs>,
+    priority: Priority,
+}
+
+impl Executor for HostExecutor {
+    fn command(&self, process: &Process<'_>) -> Command {
+        let mut env = Vec::new();
+        if let Some(user) = &self.user {
+            env.extend(user.env());
+        }
+        env.extend(process.env.iter().copied());
+        let mut cmd = match &self.user {
+            Some(user) => {
+                let names: Vec<&str> = env.iter().map(|(key, _)| *key).collect();
+                user.command(process.program, &names)
+            }
+            None => Command::new(process.program),
+        };
+        self.priority.apply(&mut cmd);
+        if let Some(user) = &self.user {
+            user.switch_user(&mut cmd);
+        }
+        for var in env_config::variable_names() {
+            cmd.env_remove(var);
+        }
+        cmd.current_dir(process.dir).args(process.args).envs(env);
+        cmd
+    }
+}
+
+/// Runs each script in a fresh container (`<runtime> run --rm`)
+#[derive(Debug)]
+pub struct ContainerExecutor {
+    config: ContainerConfig,
+    mounts: Vec<String>,
+    user: Option<RunAs>,
+    /// Set for the runtime's process; containers started by a daemon
+    /// (docker) don't inherit it
+    priority: Priority,
+}
+
+impl Executor for ContainerExecutor {
+    fn command(&self, process: &Process<'_>) -> Command {
+        let mut cmd = Command::new(self.config.runtime());
+        self.priority.apply(&mut cmd);
+        for var in env_config::variable_names() {
+            cmd.env_remove(var);
+        }
+        // The runtime forwards the SIGTERM of a stopped script to the
+        // container, where `--init` passes it on to the script's processes
+        cmd.args(["run", "--rm", "--init", "--workdir", process.dir]);
+        // Files written to the mounts belong to the user, not to root
+        if let Some(user) = &self.user {
+            cmd.arg("--user").arg(format!("{}:{}", user.uid, user.gid));
+        }
+        for mount in &self.mounts {
+            cmd.arg("--volume").arg(format!("{0}:{0}", mount));
+        }
+        // Values go through the runtime's environment rather than its
+        // arguments, so secrets don't show up in the process list
+        for (key, value) in &process.env {
+            cmd.arg("--env").arg(key).env(key, value);
+        }
+        cmd.args(self.config.run_args.iter().flatten())
+            .arg(&self.config.image)
+            .arg(process.program)
+            .args(process.args);
+        cmd
+    }
+}
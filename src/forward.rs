@@ -0,0 +1,89 @@
+//! Forwarding of webhooks no project handles
+//!
+//! With `[server] forward_unmatched_to`, webhooks that trigger no project
+//! (and events other than pushes) are proxied to another URL with their
+//! original headers and body, so this server can sit in front of other
+//! webhook consumers on the same endpoint. The upstream response is passed
+//! back to the sender.
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::SharedState;
+
+/// Time the upstream has to answer a forwarded webhook
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Hop-by-hop and connection-specific headers, which aren't forwarded
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "connection",
+    "keep-alive",
+    "transfer-encoding",
+    "te",
+    "trailer",
+    "upgrade",
+    "proxy-authorization",
+    "proxy-connection",
+];
+
+/// Forward an unmatched webhook if `forward_unmatched_to` is set, otherwise
+/// answer `204 No Content`
+pub async fn unmatched(state: &SharedState, headers: &HeaderMap, body: &[u8]) -> Response {
+    let Some(url) = state.config.load().server.forward_unmatched_to.clone() else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    forward(state, &url, headers, body).await
+}
+
+async fn forward(state: &SharedState, url: &str, headers: &HeaderMap, body: &[u8]) -> Response {
+    let mut forwarded = HeaderMap::new();
+    for (name, value) in headers {
+        if !SKIPPED_HEADERS.contains(&name.as_str()) {
+            forwarded.append(name, value.clone());
+        }
+    }
+
+    let result = state
+        .http_client
+        .post(url)
+        .headers(forwarded)
+        .body(body.to_vec())
+        .timeout(FORWARD_TIMEOUT)
+        .send()
+        .await;
+    let upstream = match result {
+        Ok(upstream) => upstream,
+        Err(e) => {
+            warn!("Failed to forward unmatched webhook to {}: {}", url, e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    let status = upstream.status();
+    info!("Forwarded unmatched webhook to {} ({})", url, status);
+    let content_type = upstream.headers().get(header::CONTENT_TYPE).cloned();
+    let body = match upstream.bytes().await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to read response of {}: {}", url, e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() =
+        StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    if let Some(content_type) = content_type
+        && let Ok(value) = HeaderValue::from_bytes(content_type.as_bytes())
+    {
+        response.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    response
+}
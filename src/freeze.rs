@@ -0,0 +1,159 @@
+//! Deploy freeze windows: periods in which webhook-triggered jobs are
+//! rejected or held in the queue until the freeze ends.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Upper bound on back-to-back windows followed when computing a freeze's end
+const MAX_CHAINED_WINDOWS: usize = 32;
+
+/// What happens to a push that arrives during a freeze
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FreezeAction {
+    /// Refuse the webhook (default)
+    #[default]
+    Reject,
+    /// Create the job but hold it in the queue until the freeze ends
+    Queue,
+}
+
+/// A freeze window: either an explicit range or a weekly recurring window
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum FreezeWindow {
+    /// Explicit range, e.g. a holiday freeze (RFC 3339 timestamps)
+    Range {
+        #[serde(deserialize_with = "deserialize_timestamp")]
+        from: DateTime<Utc>,
+        #[serde(deserialize_with = "deserialize_timestamp")]
+        until: DateTime<Utc>,
+        reason: Option<String>,
+    },
+    /// Weekly window in the server's local time, e.g. Friday 16:00 to 23:59.
+    /// `end` before `start` spans midnight; `end` equal to `start` is a full day.
+    Weekly {
+        /// Days the window starts on (default: every day)
+        #[serde(default)]
+        days: Vec<Weekday>,
+        start: TimeOfDay,
+        end: TimeOfDay,
+        reason: Option<String>,
+    },
+}
+
+impl FreezeWindow {
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Range { reason, .. } | Self::Weekly { reason, .. } => reason.as_deref(),
+        }
+    }
+
+    /// If `now` falls inside this window, returns when the window ends
+    fn active_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Range { from, until, .. } => (*from <= now && now < *until).then_some(*until),
+            Self::Weekly {
+                days, start, end, ..
+            } => {
+                let local = now.with_timezone(&Local);
+                let today = local.date_naive();
+                let starts_on = |date: NaiveDate| days.is_empty() || days.contains(&date.weekday());
+
+                // A window started today or yesterday may still be running
+                for start_date in [today, today - Duration::days(1)] {
+                    if !starts_on(start_date) {
+                        continue;
+                    }
+                    let end_date = if end.0 > start.0 {
+                        start_date
+                    } else {
+                        start_date + Duration::days(1)
+                    };
+                    let (Some(window_start), Some(window_end)) =
+                        (local_time(start_date, start.0), local_time(end_date, end.0))
+                    else {
+                        continue;
+                    };
+                    if window_start <= now && now < window_end {
+                        return Some(window_end);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// If `now` is inside any window, returns when the freeze ends (following
+/// back-to-back windows) and the reason of the window active now.
+pub fn frozen_until(
+    windows: &[FreezeWindow],
+    now: DateTime<Utc>,
+) -> Option<(DateTime<Utc>, Option<String>)> {
+    let (first, mut until) = windows
+        .iter()
+        .find_map(|w| w.active_until(now).map(|end| (w, end)))?;
+    let reason = first.reason().map(String::from);
+
+    for _ in 0..MAX_CHAINED_WINDOWS {
+        match windows.iter().filter_map(|w| w.active_until(until)).max() {
+            Some(next) if next > until => until = next,
+            _ => break,
+        }
+    }
+    Some((until, reason))
+}
+
+/// Resolve a local date and time to UTC (earliest match across DST changes)
+fn local_time(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Time of day written as `HH:MM` (or `HH:MM:SS`) in the config
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.format("%H:%M:%S").to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        NaiveTime::parse_from_str(&s, "%H:%M")
+            .or_else(|_| NaiveTime::parse_from_str(&s, "%H:%M:%S"))
+            .map(TimeOfDay)
+            .map_err(|_| serde::de::Error::custom(format!("invalid time '{}', expected HH:MM", s)))
+    }
+}
+
+/// Accept both quoted RFC 3339 strings and bare TOML datetimes
+/// (`from = 2026-12-20T00:00:00Z`), which need an explicit offset
+fn deserialize_timestamp<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<DateTime<Utc>, D::Error> {
+    let raw = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(s) => s,
+        toml::Value::Datetime(dt) => dt.to_string(),
+        other => {
+            return Err(serde::de::Error::custom(format!(
+                "invalid timestamp {}, expected RFC 3339",
+                other
+            )));
+        }
+    };
+    DateTime::parse_from_rfc3339(&raw)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| {
+            serde::de::Error::custom(format!(
+                "invalid timestamp '{}', expected RFC 3339 with an offset",
+                raw
+            ))
+        })
+}
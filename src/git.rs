@@ -0,0 +1,671 @@
+//! Git operations backed by libgit2 (no system `git` binary required)
+//!
+//! All functions here are blocking; call them through `run_blocking` from async code.
+
+use git2::{
+    AutotagOption, BranchType, Cred, CredentialType, Direction, FetchOptions, Oid, PushOptions,
+    RemoteCallbacks, Repository, ResetType, Sort, WorktreeAddOptions, WorktreePruneOptions,
+    build::CheckoutBuilder,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::{CicdError, Result};
+
+/// Callback receiving human-readable progress lines during network operations
+pub type ProgressFn = Box<dyn FnMut(&str) + Send>;
+
+/// Aborts a running network operation, e.g. a fetch that ran past its
+/// timeout. libgit2 is asked to stop at its next callback (progress,
+/// credentials, push negotiation), which makes the operation fail.
+#[derive(Debug, Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How long a network read may wait for data before the operation fails, so
+/// a connection that stalls without calling back (and can't be cancelled)
+/// doesn't keep an operation running indefinitely
+pub const SERVER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Apply `SERVER_TIMEOUT` to libgit2's network streams. Modifies a libgit2
+/// global, so it must be called before any other thread is started.
+pub fn init_server_timeout() {
+    // SAFETY: called from `main` before the runtime's threads exist
+    unsafe {
+        let _ = git2::opts::set_server_timeout_in_milliseconds(SERVER_TIMEOUT.as_millis() as i32);
+    }
+}
+
+/// Maximum credential attempts before giving up (libgit2 retries indefinitely otherwise)
+const MAX_CREDENTIAL_ATTEMPTS: usize = 4;
+
+/// Convert a libgit2 error into a structured `GitOperationFailed`
+pub fn git_error(operation: &str, e: git2::Error) -> CicdError {
+    CicdError::GitOperationFailed {
+        operation: operation.to_string(),
+        message: format!(
+            "{} (class: {:?}, code: {:?})",
+            e.message(),
+            e.class(),
+            e.code()
+        ),
+    }
+}
+
+/// Run a blocking git operation on the blocking thread pool
+pub async fn run_blocking<T, F>(operation: &str, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| CicdError::GitOperationFailed {
+            operation: operation.to_string(),
+            message: format!("Git task panicked or was cancelled: {}", e),
+        })?
+}
+
+/// Open the repository at `repo_path`
+pub fn open(repo_path: &str) -> Result<Repository> {
+    Repository::open(Path::new(repo_path)).map_err(|e| git_error("open repository", e))
+}
+
+/// Explicit credentials for a remote, used instead of the default lookup
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    /// Overrides the user name from the URL
+    pub username: Option<String>,
+    /// Password or access token for HTTPS, together with `username`
+    pub password: Option<String>,
+    /// Private key for SSH
+    pub ssh_key: Option<PathBuf>,
+}
+
+/// Build remote callbacks with credential lookup (`credentials` if given,
+/// then ssh-agent, default keys, git credential helpers) and progress
+/// reporting. The operation is aborted once `cancel` is set.
+fn remote_callbacks<'a>(
+    repo: &'a Repository,
+    progress: &'a RefCell<Option<ProgressFn>>,
+    updated: &'a mut Vec<String>,
+    credentials: Option<&'a Credentials>,
+    cancel: &'a Cancel,
+) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut attempts = 0;
+
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        if cancel.is_cancelled() {
+            return Err(git2::Error::from_str("cancelled"));
+        }
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str(
+                "authentication failed: no usable credentials",
+            ));
+        }
+        let username = credentials
+            .and_then(|c| c.username.as_deref())
+            .or(username_from_url)
+            .unwrap_or("git");
+
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if let Some(key) = credentials.and_then(|c| c.ssh_key.as_deref()) {
+                return Cred::ssh_key(username, None, key, None);
+            }
+            if attempts == 1 {
+                return Cred::ssh_key_from_agent(username);
+            }
+            let home = std::env::var("HOME").unwrap_or_default();
+            for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+                let path = Path::new(&home).join(".ssh").join(key);
+                if path.exists() {
+                    return Cred::ssh_key(username, None, &path, None);
+                }
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(password) = credentials.and_then(|c| c.password.as_deref()) {
+                return Cred::userpass_plaintext(username, password);
+            }
+            let config = repo.config()?;
+            return Cred::credential_helper(&config, url, username_from_url);
+        }
+        Cred::default()
+    });
+
+    let mut last_bucket = None;
+
+    callbacks.transfer_progress(move |stats| {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        let total = stats.total_objects();
+        if let Some(percent) = (stats.received_objects() * 100).checked_div(total) {
+            // Report every 10% so large fetches don't flood the log stream
+            if last_bucket != Some(percent / 10) {
+                last_bucket = Some(percent / 10);
+                if let Some(cb) = progress.borrow_mut().as_mut() {
+                    cb(&format!(
+                        "Receiving objects: {}% ({}/{}), {} KiB\n",
+                        percent,
+                        stats.received_objects(),
+                        total,
+                        stats.received_bytes() / 1024
+                    ));
+                }
+            }
+        }
+        true
+    });
+
+    callbacks.sideband_progress(move |data| {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        if let Some(cb) = progress.borrow_mut().as_mut() {
+            cb(&String::from_utf8_lossy(data));
+        }
+        true
+    });
+
+    callbacks.update_tips(move |refname, old, new| {
+        if old.is_zero() {
+            updated.push(format!(" * [new ref]  {} -> {}", short_oid(new), refname));
+        } else {
+            updated.push(format!(
+                "   {}..{}  {}",
+                short_oid(old),
+                short_oid(new),
+                refname
+            ));
+        }
+        true
+    });
+
+    callbacks
+}
+
+fn short_oid(oid: Oid) -> String {
+    oid.to_string().chars().take(7).collect()
+}
+
+/// Fetch from `remote_name` using its configured refspecs, or `refspecs` if
+/// not empty. Returns a summary of updated refs.
+pub fn fetch(
+    repo_path: &str,
+    remote_name: &str,
+    refspecs: &[&str],
+    progress: Option<ProgressFn>,
+    cancel: &Cancel,
+) -> Result<String> {
+    let operation = format!("git fetch {}", remote_name);
+    let repo = open(repo_path)?;
+    let mut remote = repo
+        .find_remote(remote_name)
+        .map_err(|e| git_error(&operation, e))?;
+    let url = remote.url().unwrap_or("").to_string();
+
+    let progress = RefCell::new(progress);
+    let mut updated = Vec::new();
+    {
+        let callbacks = remote_callbacks(&repo, &progress, &mut updated, None, cancel);
+        let mut opts = FetchOptions::new();
+        opts.remote_callbacks(callbacks)
+            .download_tags(AutotagOption::Auto);
+        remote
+            .fetch(refspecs, Some(&mut opts), None)
+            .map_err(|e| git_error(&operation, e))?;
+    }
+
+    let stats = remote.stats();
+    let mut output = format!("From {}\n", url);
+    if stats.received_objects() > 0 {
+        output.push_str(&format!(
+            "Received {} objects ({} KiB)\n",
+            stats.received_objects(),
+            stats.received_bytes() / 1024
+        ));
+    }
+    for line in updated {
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Number of parents of the commit `spec` names (two or more for merges)
+pub fn parent_count(repo_path: &str, spec: &str) -> Result<usize> {
+    let operation = format!("git rev-list --parents -n 1 {}", spec);
+    let repo = open(repo_path)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|target| target.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(commit.parent_count())
+}
+
+/// URL of the remote `remote_name` (like `git remote get-url`)
+pub fn remote_url(repo_path: &str, remote_name: &str) -> Result<String> {
+    let operation = format!("git remote get-url {}", remote_name);
+    let repo = open(repo_path)?;
+    let remote = repo
+        .find_remote(remote_name)
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(remote.url().unwrap_or("").to_string())
+}
+
+/// List the references advertised by `remote_name` without fetching
+/// (like `git ls-remote`). Returns the full reference names.
+pub fn ls_remote(repo_path: &str, remote_name: &str) -> Result<Vec<String>> {
+    let operation = format!("git ls-remote {}", remote_name);
+    let repo = open(repo_path)?;
+    let mut remote = repo
+        .find_remote(remote_name)
+        .map_err(|e| git_error(&operation, e))?;
+
+    let progress = RefCell::new(None);
+    let mut updated = Vec::new();
+    let cancel = Cancel::default();
+    let callbacks = remote_callbacks(&repo, &progress, &mut updated, None, &cancel);
+    let connection = remote
+        .connect_auth(Direction::Fetch, Some(callbacks), None)
+        .map_err(|e| git_error(&operation, e))?;
+    let refs = connection
+        .list()
+        .map_err(|e| git_error(&operation, e))?
+        .iter()
+        .map(|head| head.name().to_string())
+        .collect();
+    Ok(refs)
+}
+
+/// Push `refspec` (e.g. `+refs/remotes/origin/main:refs/heads/main`) to `url`
+/// through an anonymous remote, leaving the repository's remotes untouched.
+/// Returns a summary of the updated refs.
+pub fn push_url(
+    repo_path: &str,
+    url: &str,
+    refspec: &str,
+    credentials: &Credentials,
+    cancel: &Cancel,
+) -> Result<String> {
+    let operation = format!("git push {}", url);
+    let repo = open(repo_path)?;
+    let mut remote = repo
+        .remote_anonymous(url)
+        .map_err(|e| git_error(&operation, e))?;
+
+    let progress = RefCell::new(None);
+    let mut updated = Vec::new();
+    let pushed = RefCell::new(Vec::new());
+    let rejected = RefCell::new(Vec::new());
+    {
+        let mut callbacks =
+            remote_callbacks(&repo, &progress, &mut updated, Some(credentials), cancel);
+        callbacks.push_negotiation(|updates| {
+            if cancel.is_cancelled() {
+                return Err(git2::Error::from_str("cancelled"));
+            }
+            for update in updates.iter().filter(|u| u.src() != u.dst()) {
+                let dst = update.dst_refname().unwrap_or("?");
+                let line = if update.src().is_zero() {
+                    format!(" * [new ref]  {} -> {}", short_oid(update.dst()), dst)
+                } else {
+                    format!(
+                        "   {}..{}  {}",
+                        short_oid(update.src()),
+                        short_oid(update.dst()),
+                        dst
+                    )
+                };
+                pushed.borrow_mut().push(line);
+            }
+            Ok(())
+        });
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(message) = status {
+                rejected
+                    .borrow_mut()
+                    .push(format!("{} ({})", refname, message));
+            }
+            Ok(())
+        });
+        let mut opts = PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        remote
+            .push(&[refspec], Some(&mut opts))
+            .map_err(|e| git_error(&operation, e))?;
+    }
+
+    let rejected = rejected.into_inner();
+    if !rejected.is_empty() {
+        return Err(CicdError::GitOperationFailed {
+            operation,
+            message: format!("rejected: {}", rejected.join(", ")),
+        });
+    }
+    let mut output = format!("To {}\n", url);
+    let pushed = pushed.into_inner();
+    if pushed.is_empty() {
+        output.push_str("Everything up-to-date\n");
+    }
+    for line in pushed {
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Check that `url` accepts a push connection with `credentials`, without
+/// pushing anything
+pub fn check_push_access(repo_path: &str, url: &str, credentials: &Credentials) -> Result<()> {
+    let operation = format!("connect {}", url);
+    let repo = open(repo_path)?;
+    let mut remote = repo
+        .remote_anonymous(url)
+        .map_err(|e| git_error(&operation, e))?;
+
+    let progress = RefCell::new(None);
+    let mut updated = Vec::new();
+    let cancel = Cancel::default();
+    let callbacks = remote_callbacks(&repo, &progress, &mut updated, Some(credentials), &cancel);
+    remote
+        .connect_auth(Direction::Push, Some(callbacks), None)
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(())
+}
+
+/// Hard reset the working tree and HEAD to `spec` (e.g. `origin/main`)
+pub fn reset_hard(repo_path: &str, spec: &str) -> Result<String> {
+    let operation = format!("git reset --hard {}", spec);
+    let repo = open(repo_path)?;
+    let target = repo
+        .revparse_single(spec)
+        .map_err(|e| git_error(&operation, e))?;
+    repo.reset(&target, ResetType::Hard, None)
+        .map_err(|e| git_error(&operation, e))?;
+
+    let commit = target
+        .peel_to_commit()
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(format!(
+        "HEAD is now at {} {}\n",
+        short_oid(commit.id()),
+        commit.summary().ok().flatten().unwrap_or("")
+    ))
+}
+
+/// Check out a commit with a detached HEAD, discarding local changes
+pub fn checkout_detached(repo_path: &str, spec: &str) -> Result<String> {
+    let operation = format!("git checkout --force --detach {}", spec);
+    let repo = open(repo_path)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|target| target.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))
+        .map_err(|e| git_error(&operation, e))?;
+    repo.set_head_detached(commit.id())
+        .map_err(|e| git_error(&operation, e))?;
+
+    Ok(format!(
+        "HEAD is now at {} {}\n",
+        short_oid(commit.id()),
+        commit.summary().ok().flatten().unwrap_or("")
+    ))
+}
+
+/// Add a worktree of `repo_path` named `name` at `path`, with a detached
+/// HEAD at `spec`, like `git worktree add --detach <path> <spec>`
+pub fn add_worktree(repo_path: &str, name: &str, path: &Path, spec: &str) -> Result<String> {
+    let operation = format!("git worktree add --detach {} {}", path.display(), spec);
+    let repo = open(repo_path)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|target| target.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+
+    // libgit2 only adds worktrees on a branch: a temporary one is used and
+    // deleted once the worktree's HEAD is detached
+    let mut branch = repo
+        .branch(&format!("cicd-worktree-{}", name), &commit, true)
+        .map_err(|e| git_error(&operation, e))?;
+    let mut options = WorktreeAddOptions::new();
+    options.reference(Some(branch.get()));
+    let added = repo
+        .worktree(name, path, Some(&options))
+        .and_then(|_| Repository::open(path))
+        .and_then(|worktree| worktree.set_head_detached(commit.id()));
+    let deleted = branch.delete();
+    added.and(deleted).map_err(|e| git_error(&operation, e))?;
+
+    Ok(format!(
+        "HEAD is now at {} {}\n",
+        short_oid(commit.id()),
+        commit.summary().ok().flatten().unwrap_or("")
+    ))
+}
+
+/// Remove the worktree `name` of `repo_path` along with its files
+pub fn remove_worktree(repo_path: &str, name: &str) -> Result<()> {
+    let operation = format!("git worktree remove --force {}", name);
+    let repo = open(repo_path)?;
+    let Ok(worktree) = repo.find_worktree(name) else {
+        return Ok(());
+    };
+    let mut options = WorktreePruneOptions::new();
+    options.valid(true).locked(true).working_tree(true);
+    worktree
+        .prune(Some(&mut options))
+        .map_err(|e| git_error(&operation, e))
+}
+
+/// Create a repository at `path` with the remote branches and tags of
+/// `repo_path` and a detached HEAD at `spec`, like a clone of the checkout.
+/// Its `origin` is the origin of `repo_path`.
+pub fn clone_local(repo_path: &str, path: &Path, spec: &str) -> Result<String> {
+    let operation = format!("git clone {} {}", repo_path, path.display());
+    let source = open(repo_path)?;
+    let repo = Repository::init(path).map_err(|e| git_error(&operation, e))?;
+    let source_url = source.path().display().to_string();
+    repo.remote_anonymous(&source_url)
+        .and_then(|mut remote| {
+            remote.fetch(
+                &[
+                    "+refs/remotes/origin/*:refs/remotes/origin/*",
+                    "+refs/tags/*:refs/tags/*",
+                ],
+                None,
+                None,
+            )
+        })
+        .map_err(|e| git_error(&operation, e))?;
+    if let Ok(origin) = source.find_remote("origin")
+        && let Ok(url) = origin.url()
+    {
+        repo.remote("origin", url)
+            .map_err(|e| git_error(&operation, e))?;
+    }
+    checkout_detached(&path.display().to_string(), spec)
+}
+
+/// Switch to a local branch, creating it from `origin/<branch>` if missing
+pub fn switch(repo_path: &str, branch: &str) -> Result<String> {
+    let operation = format!("git switch {}", branch);
+    let repo = open(repo_path)?;
+
+    let mut output = String::new();
+    if repo.find_branch(branch, BranchType::Local).is_err() {
+        let remote_branch = repo
+            .find_branch(&format!("origin/{}", branch), BranchType::Remote)
+            .map_err(|e| git_error(&operation, e))?;
+        let commit = remote_branch
+            .get()
+            .peel_to_commit()
+            .map_err(|e| git_error(&operation, e))?;
+        let mut local = repo
+            .branch(branch, &commit, false)
+            .map_err(|e| git_error(&operation, e))?;
+        local
+            .set_upstream(Some(&format!("origin/{}", branch)))
+            .map_err(|e| git_error(&operation, e))?;
+        output.push_str(&format!(
+            "branch '{}' set up to track 'origin/{}'.\n",
+            branch, branch
+        ));
+    }
+
+    let refname = format!("refs/heads/{}", branch);
+    let tree = repo
+        .revparse_single(&refname)
+        .map_err(|e| git_error(&operation, e))?;
+    repo.checkout_tree(&tree, Some(CheckoutBuilder::new().safe()))
+        .map_err(|e| git_error(&operation, e))?;
+    repo.set_head(&refname)
+        .map_err(|e| git_error(&operation, e))?;
+
+    output.push_str(&format!("Switched to branch '{}'\n", branch));
+    Ok(output)
+}
+
+/// Fetch and fast-forward the current branch to its upstream.
+/// Fails if the branches have diverged (no merge commits are created).
+pub fn pull(repo_path: &str, progress: Option<ProgressFn>, cancel: &Cancel) -> Result<String> {
+    let operation = "git pull";
+    let mut output = fetch(repo_path, "origin", &[], progress, cancel)?;
+
+    let repo = open(repo_path)?;
+    let head = repo.head().map_err(|e| git_error(operation, e))?;
+    if !head.is_branch() {
+        return Err(CicdError::GitOperationFailed {
+            operation: operation.to_string(),
+            message: "HEAD is not on a branch".to_string(),
+        });
+    }
+    let branch_name = head
+        .shorthand()
+        .map_err(|e| git_error(operation, e))?
+        .to_string();
+    let upstream = repo
+        .find_branch(&branch_name, BranchType::Local)
+        .and_then(|b| b.upstream())
+        .map_err(|e| git_error(operation, e))?;
+    let upstream_commit = repo
+        .reference_to_annotated_commit(upstream.get())
+        .map_err(|e| git_error(operation, e))?;
+
+    let (analysis, _) = repo
+        .merge_analysis(&[&upstream_commit])
+        .map_err(|e| git_error(operation, e))?;
+
+    if analysis.is_up_to_date() {
+        output.push_str("Already up to date.\n");
+    } else if analysis.is_fast_forward() {
+        let target = upstream_commit.id();
+        let refname = format!("refs/heads/{}", branch_name);
+        // Update the working tree first so the checkout baseline is the old HEAD
+        let target_commit = repo
+            .find_commit(target)
+            .map_err(|e| git_error(operation, e))?;
+        repo.checkout_tree(
+            target_commit.as_object(),
+            Some(CheckoutBuilder::new().safe()),
+        )
+        .map_err(|e| git_error(operation, e))?;
+        repo.find_reference(&refname)
+            .and_then(|mut r| r.set_target(target, "pull: fast-forward").map(|_| ()))
+            .map_err(|e| git_error(operation, e))?;
+        output.push_str(&format!("Fast-forward to {}\n", short_oid(target)));
+    } else {
+        return Err(CicdError::GitOperationFailed {
+            operation: operation.to_string(),
+            message: format!(
+                "Local branch '{}' has diverged from its upstream; cannot fast-forward",
+                branch_name
+            ),
+        });
+    }
+    Ok(output)
+}
+
+/// Resolve a revision (e.g. `HEAD`, `origin/main`) to a full commit SHA
+pub fn rev_parse(repo_path: &str, spec: &str) -> Result<String> {
+    let operation = format!("git rev-parse {}", spec);
+    let repo = open(repo_path)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(commit.id().to_string())
+}
+
+/// Write the files of HEAD's tree into `target_dir` (like `git archive HEAD`
+/// extracted there), without touching the repository's index or working tree.
+/// Returns the short SHA of the exported commit.
+pub fn export_head(repo_path: &str, target_dir: &Path) -> Result<String> {
+    let operation = format!("export HEAD to {}", target_dir.display());
+    let repo = open(repo_path)?;
+    let commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout
+        .target_dir(target_dir)
+        .update_index(false)
+        .recreate_missing(true)
+        .force();
+    repo.checkout_tree(commit.as_object(), Some(&mut checkout))
+        .map_err(|e| git_error(&operation, e))?;
+    Ok(short_oid(commit.id()))
+}
+
+/// Commits reachable from HEAD but not from `since`, newest first, as
+/// `git log --oneline <since>..HEAD` lines. At most `limit` lines are returned.
+pub fn log_oneline(repo_path: &str, since: &str, limit: usize) -> Result<Vec<String>> {
+    let operation = format!("git log --oneline {}..HEAD", since);
+    let repo = open(repo_path)?;
+    let since = repo
+        .revparse_single(since)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| git_error(&operation, e))?;
+
+    let mut walk = repo.revwalk().map_err(|e| git_error(&operation, e))?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .map_err(|e| git_error(&operation, e))?;
+    walk.push_head().map_err(|e| git_error(&operation, e))?;
+    walk.hide(since.id())
+        .map_err(|e| git_error(&operation, e))?;
+
+    let mut lines = Vec::new();
+    for oid in walk.take(limit) {
+        let oid = oid.map_err(|e| git_error(&operation, e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| git_error(&operation, e))?;
+        lines.push(format!(
+            "{} {}",
+            short_oid(oid),
+            commit.summary().ok().flatten().unwrap_or("")
+        ));
+    }
+    Ok(lines)
+}
@@ -0,0 +1,309 @@
+//! GitHub App authentication
+//!
+//! With `[server.github_app]`, the server acts as a GitHub App: deliveries
+//! for projects with `github_app = true` are verified with the App's webhook
+//! secret, and their jobs get an installation access token in
+//! `CICD_GITHUB_TOKEN`, scoped to the project's repository, e.g. for cloning
+//! private repositories or posting commit statuses. Tokens are minted with a
+//! short-lived JWT signed with the App's private key, and reused until shortly
+//! before they expire.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use ring::rand::SystemRandom;
+use ring::signature::{RSA_PKCS1_SHA256, RsaKeyPair};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::error::{CicdError, Result};
+use crate::webhook::WebhookData;
+use crate::{ProjectConfig, SharedState, git};
+
+/// Time GitHub has to answer an API request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Tokens are minted again once they have less than this left, so a job
+/// never starts with one about to expire
+const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 10;
+
+/// `[server.github_app]` settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GithubAppConfig {
+    /// The App's ID, shown on its settings page
+    pub app_id: u64,
+    /// PEM file with the App's private key (as downloaded from GitHub)
+    pub private_key_path: String,
+    /// The App's webhook secret
+    pub webhook_secret: Option<String>,
+    /// Environment variable holding the App's webhook secret
+    pub webhook_secret_env: Option<String>,
+    /// Base URL of the REST API (default: `https://api.github.com`; GitHub
+    /// Enterprise Server uses `https://HOST/api/v3`)
+    pub api_url: Option<String>,
+}
+
+impl GithubAppConfig {
+    pub fn api_url(&self) -> &str {
+        self.api_url
+            .as_deref()
+            .unwrap_or("https://api.github.com")
+            .trim_end_matches('/')
+    }
+
+    /// Returns the App's webhook secret: `webhook_secret`, or the value of
+    /// the `webhook_secret_env` variable
+    pub fn webhook_secret(&self) -> Option<String> {
+        self.webhook_secret
+            .clone()
+            .or_else(|| {
+                self.webhook_secret_env
+                    .as_ref()
+                    .and_then(|var| std::env::var(var).ok())
+            })
+            .filter(|s| !s.is_empty())
+    }
+
+    /// JWT authenticating as the App, valid for nine minutes (GitHub allows
+    /// ten) and backdated a minute against clock drift
+    fn jwt(&self, now: DateTime<Utc>) -> Result<String> {
+        let pem = std::fs::read_to_string(&self.private_key_path).map_err(|e| {
+            CicdError::ConfigError(format!(
+                "Failed to read GitHub App private key {}: {}",
+                self.private_key_path, e
+            ))
+        })?;
+        let key = parse_private_key(&pem).map_err(|e| {
+            CicdError::ConfigError(format!(
+                "Invalid GitHub App private key {}: {}",
+                self.private_key_path, e
+            ))
+        })?;
+
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = json!({
+            "iat": (now - ChronoDuration::seconds(60)).timestamp(),
+            "exp": (now + ChronoDuration::seconds(540)).timestamp(),
+            "iss": self.app_id.to_string(),
+        });
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let message = format!("{}.{}", header, claims);
+
+        let mut signature = vec![0; key.public().modulus_len()];
+        key.sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            message.as_bytes(),
+            &mut signature,
+        )
+        .map_err(|_| CicdError::ConfigError("Failed to sign GitHub App JWT".to_string()))?;
+        Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+/// Parse an RSA private key in PKCS#1 (`BEGIN RSA PRIVATE KEY`, what GitHub
+/// hands out) or PKCS#8 (`BEGIN PRIVATE KEY`) PEM format
+fn parse_private_key(pem: &str) -> std::result::Result<RsaKeyPair, String> {
+    let pkcs1 = pem.contains("BEGIN RSA PRIVATE KEY");
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    let der = STANDARD
+        .decode(body)
+        .map_err(|e| format!("not a PEM file: {}", e))?;
+    let key = if pkcs1 {
+        RsaKeyPair::from_der(&der)
+    } else {
+        RsaKeyPair::from_pkcs8(&der)
+    };
+    key.map_err(|e| e.to_string())
+}
+
+/// An installation access token and when it expires
+#[derive(Debug, Clone)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Installation access tokens minted so far, by repository
+#[derive(Debug, Default)]
+pub struct TokenCache {
+    tokens: Mutex<HashMap<String, InstallationToken>>,
+}
+
+impl TokenCache {
+    /// An installation access token for `repository` (`owner/name`), minted
+    /// if there is no cached one with enough time left
+    pub async fn token(
+        &self,
+        client: &reqwest::Client,
+        config: &GithubAppConfig,
+        repository: &str,
+    ) -> Result<String> {
+        let key = format!("{}:{}", config.app_id, repository.to_lowercase());
+        let mut tokens = self.tokens.lock().await;
+        let fresh_until = Utc::now() + ChronoDuration::minutes(TOKEN_REFRESH_MARGIN_MINUTES);
+        if let Some(cached) = tokens.get(&key)
+            && cached.expires_at > fresh_until
+        {
+            return Ok(cached.token.clone());
+        }
+
+        let minted = mint_token(client, config, repository).await?;
+        info!(
+            "Minted GitHub App installation token for {} (expires {})",
+            repository, minted.expires_at
+        );
+        tokens.insert(key, minted.clone());
+        Ok(minted.token)
+    }
+}
+
+/// Look up the App's installation on `repository` and create an access
+/// token limited to that repository
+async fn mint_token(
+    client: &reqwest::Client,
+    config: &GithubAppConfig,
+    repository: &str,
+) -> Result<InstallationToken> {
+    let jwt = config.jwt(Utc::now())?;
+    let (_, name) = repository.split_once('/').unwrap_or(("", repository));
+
+    let installation: serde_json::Value = api_request(
+        client.get(format!(
+            "{}/repos/{}/installation",
+            config.api_url(),
+            repository
+        )),
+        &jwt,
+    )
+    .await?;
+    let installation_id = installation
+        .get("id")
+        .and_then(serde_json::Value::as_u64)
+        .ok_or_else(|| {
+            CicdError::ApiRequestFailed(format!(
+                "GitHub returned no installation for {}",
+                repository
+            ))
+        })?;
+
+    let token: serde_json::Value = api_request(
+        client
+            .post(format!(
+                "{}/app/installations/{}/access_tokens",
+                config.api_url(),
+                installation_id
+            ))
+            .header("Content-Type", "application/json")
+            .body(json!({ "repositories": [name] }).to_string()),
+        &jwt,
+    )
+    .await?;
+    let unexpected =
+        || CicdError::ApiRequestFailed("Unexpected response to access token request".to_string());
+    Ok(InstallationToken {
+        token: token
+            .get("token")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(unexpected)?
+            .to_string(),
+        expires_at: token
+            .get("expires_at")
+            .and_then(serde_json::Value::as_str)
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .ok_or_else(unexpected)?
+            .with_timezone(&Utc),
+    })
+}
+
+async fn api_request(request: reqwest::RequestBuilder, jwt: &str) -> Result<serde_json::Value> {
+    let response = request
+        .bearer_auth(jwt)
+        .header("Accept", "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+        .header("User-Agent", "simple_git_cicd")
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| CicdError::ApiRequestFailed(format!("GitHub API: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(CicdError::ApiRequestFailed(format!(
+            "GitHub API returned {}: {}",
+            status,
+            body.trim()
+        )));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| CicdError::ApiRequestFailed(format!("GitHub API: {}", e)))?;
+    serde_json::from_slice(&body)
+        .map_err(|e| CicdError::ApiRequestFailed(format!("Unexpected response: {}", e)))
+}
+
+/// `owner/name` of a GitHub repository URL, e.g. `https://github.com/owner/name`
+/// or `git@github.com:owner/name.git`
+fn repository_from_url(url: &str) -> Option<String> {
+    let path = url.trim_end_matches('/').trim_end_matches(".git");
+    let mut segments = path.rsplit(['/', ':']);
+    let name = segments.next().filter(|s| !s.is_empty())?;
+    let owner = segments.next().filter(|s| !s.is_empty())?;
+    Some(format!("{}/{}", owner, name))
+}
+
+/// The installation access token a job of `project` gets, if the project
+/// uses the GitHub App. The repository is `repo_full_name`, or taken from
+/// the push's repository URL or the clone's `origin` remote.
+pub async fn job_token(
+    state: &SharedState,
+    project: &ProjectConfig,
+    webhook_data: &WebhookData,
+) -> Result<Option<String>> {
+    if !project.github_app() {
+        return Ok(None);
+    }
+    let Some(config) = state.config.load().server.github_app.clone() else {
+        return Err(CicdError::ConfigError(format!(
+            "Project '{}' sets github_app, but [server.github_app] is not configured",
+            project.name
+        )));
+    };
+
+    let repository = match project.repo_full_name.clone().or_else(|| {
+        webhook_data
+            .repository_url
+            .as_deref()
+            .and_then(repository_from_url)
+    }) {
+        Some(repository) => repository,
+        None => {
+            let repo_path = webhook_data.repo_path.clone();
+            let url = git::run_blocking("git remote get-url origin", move || {
+                git::remote_url(&repo_path, "origin")
+            })
+            .await?;
+            repository_from_url(&url).ok_or_else(|| {
+                CicdError::ConfigError(format!(
+                    "Can't tell the GitHub repository of project '{}' from {}; set repo_full_name",
+                    project.name, url
+                ))
+            })?
+        }
+    };
+
+    let token = state
+        .github_tokens
+        .token(&state.http_client, &config, &repository)
+        .await?;
+    Ok(Some(token))
+}
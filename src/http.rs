@@ -0,0 +1,94 @@
+//! HTTP listener with configurable connection limits and timeouts
+//!
+//! `axum::serve` doesn't expose hyper's connection settings, so connections
+//! are accepted here and served with a configured hyper builder.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{ConnectInfo, Request};
+use axum::response::Response;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tower::{Service, ServiceExt, service_fn};
+use tracing::{debug, error};
+
+/// Connection-level settings for the listener
+#[derive(Debug, Clone)]
+pub struct ListenerSettings {
+    /// Time allowed to receive a request's headers before the connection is closed
+    pub header_read_timeout: Duration,
+    /// Whether HTTP/1.1 connections are kept open between requests
+    pub keep_alive: bool,
+    /// Maximum simultaneously open connections (`None` = unlimited)
+    pub max_connections: Option<usize>,
+}
+
+/// Accept connections on `listener` and serve `app` on each of them
+pub async fn serve<S>(listener: TcpListener, app: S, settings: ListenerSettings)
+where
+    S: Service<Request<Incoming>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let connection_limit = settings
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max)));
+
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(settings.header_read_timeout)
+        .keep_alive(settings.keep_alive);
+
+    loop {
+        // Wait for a free slot before accepting, so excess clients queue in
+        // the kernel backlog instead of holding sockets open here
+        let permit = match &connection_limit {
+            Some(limit) => match limit.clone().acquire_owned().await {
+                Ok(permit) => Some(permit),
+                Err(_) => return,
+            },
+            None => None,
+        };
+
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Usually transient (e.g. too many open files); back off briefly
+                error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        let builder = builder.clone();
+        // Handlers read the peer address from the `ConnectInfo` extension
+        let app = app.clone();
+        let service = TowerToHyperService::new(service_fn(move |mut req: Request<Incoming>| {
+            req.extensions_mut().insert(ConnectInfo(remote_addr));
+            app.clone().oneshot(req)
+        }));
+        tokio::spawn(async move {
+            if let Err(e) = builder
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Connection from {} ended with error: {}", remote_addr, e);
+            }
+            drop(permit);
+        });
+
+        if connection_limit
+            .as_ref()
+            .is_some_and(|limit| limit.available_permits() == 0)
+        {
+            debug!("Connection limit reached; new connections will wait");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Source IP allowlists for webhook requests
+//!
+//! `[server] webhook_allowed_ips` is checked for every request on a webhook
+//! path before anything else is done with it; a project's `allowed_ips` for
+//! the pushes that trigger it. Behind a reverse proxy listed in
+//! `[server] trusted_proxies`, the client address is read from the proxy's
+//! `X-Forwarded-For` (or `X-Real-IP`) header.
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::net::IpAddr;
+
+/// Client address of a webhook request (see `client_ip`), unknown if the
+/// server didn't record the peer address
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl ClientIp {
+    /// Returns true if the address is known and in `filter`
+    pub fn allowed_by(&self, filter: &IpFilter) -> bool {
+        self.0.is_some_and(|ip| filter.contains(ip))
+    }
+}
+
+impl std::fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(ip) => ip.fmt(f),
+            None => f.write_str("unknown address"),
+        }
+    }
+}
+
+/// Compiled list of addresses and CIDR ranges, written as a list of strings
+/// in the config, e.g. `["192.30.252.0/22", "2a0a:a440::/29", "10.1.2.3"]`.
+#[derive(Debug, Clone)]
+pub struct IpFilter {
+    entries: Vec<String>,
+    nets: Vec<IpNet>,
+}
+
+impl IpFilter {
+    pub fn new(entries: Vec<String>) -> Result<Self, String> {
+        let nets = entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim();
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("invalid IP address or CIDR range '{}'", entry))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries, nets })
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Returns true if `ip` is one of the addresses or in one of the ranges
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+}
+
+impl Serialize for IpFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for IpFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<String>::deserialize(deserializer)?;
+        Self::new(entries).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The address of the client that sent a request received from `peer`. If
+/// `peer` is a trusted proxy, `X-Forwarded-For` is walked from the right,
+/// past the trusted proxies, to the first address that isn't one; entries
+/// before it could have been forged by the client. Without the header,
+/// `X-Real-IP` is used.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: Option<&IpFilter>) -> IpAddr {
+    let peer = peer.to_canonical();
+    let Some(trusted) = trusted_proxies.filter(|trusted| trusted.contains(peer)) else {
+        return peer;
+    };
+
+    let forwarded: Vec<&str> = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    if forwarded.is_empty() {
+        return headers
+            .get("X-Real-IP")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok())
+            .map(|ip| ip.to_canonical())
+            .unwrap_or(peer);
+    }
+
+    let mut client = peer;
+    for entry in forwarded.iter().rev() {
+        let Ok(ip) = entry.trim().parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !trusted.contains(client) {
+            break;
+        }
+    }
+    client
+}
@@ -1,10 +1,17 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
-/// Maximum size for job output before truncation (1MB)
+use crate::output_diff::OutputDiff;
+
+/// Maximum size for job output before truncation, and the default cap on
+/// each stream of a step's output (1MB)
 pub const MAX_OUTPUT_SIZE: usize = 1024 * 1024;
 
+/// Maximum size of the webhook payload stored with a job (256KB)
+pub const MAX_PAYLOAD_SIZE: usize = 256 * 1024;
+
 /// Represents the status of a CI/CD job
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -13,6 +20,42 @@ pub enum JobStatus {
     Running,
     Success,
     Failed,
+    /// Not run: the head commit message asked to skip CI
+    Skipped,
+    /// Not run: a newer push to the same branch was queued before it started
+    Cancelled,
+    /// Stopped after running longer than the project's `timeout_seconds`
+    #[serde(rename = "timed_out")]
+    TimedOut,
+}
+
+/// Pipeline step a resumed job starts at. The steps before it succeeded in
+/// the original run and are skipped.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResumeStep {
+    PreScript,
+    MainScript,
+}
+
+impl ResumeStep {
+    /// The step a job that failed in `log_type` resumes at, if the steps
+    /// before it can be skipped
+    pub fn for_failed_step(log_type: &str) -> Option<Self> {
+        match log_type {
+            "pre_script" => Some(Self::PreScript),
+            // A release that failed to go live was discarded, so it's rebuilt
+            "main_script" | "release_activate" => Some(Self::MainScript),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreScript => "pre_script",
+            Self::MainScript => "main_script",
+        }
+    }
 }
 
 /// Represents a CI/CD job with its metadata and execution details
@@ -31,6 +74,31 @@ pub struct Job {
     pub output_truncated: bool,
     pub error: Option<String>,
     pub dry_run: bool,
+    pub pinned: bool,
+    /// For rollback jobs: the successful job whose commit is restored
+    pub rollback_of: Option<String>,
+    /// For jobs triggered by a tag push: the tag (also in `branch`)
+    pub tag: Option<String>,
+    /// For resumed jobs: the failed job whose pipeline is continued
+    pub resumed_from: Option<String>,
+    /// For retried jobs: the job whose pipeline is run again
+    pub retried_from: Option<String>,
+    /// For jobs validating a pull request: its number
+    pub pull_request: Option<i64>,
+    /// The webhook skipped the project's rate limit with the bypass token
+    pub rate_limit_bypassed: bool,
+    /// Environment passed to the job's scripts, secrets masked
+    /// (only included in single-job responses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+    /// Main-script output changes since the previous run
+    /// (only included in single-job responses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_diff: Option<OutputDiff>,
+    /// `git log --oneline` of the commits deployed since the last successful
+    /// deploy (only included in single-job responses)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
 }
 
 impl Job {
@@ -50,6 +118,16 @@ impl Job {
             output_truncated: false,
             error: None,
             dry_run: false,
+            pinned: false,
+            rollback_of: None,
+            tag: None,
+            resumed_from: None,
+            retried_from: None,
+            pull_request: None,
+            rate_limit_bypassed: false,
+            env: None,
+            output_diff: None,
+            changelog: None,
         }
     }
 
@@ -75,6 +153,16 @@ impl Job {
             output_truncated: false,
             error: None,
             dry_run: false,
+            pinned: false,
+            rollback_of: None,
+            tag: None,
+            resumed_from: None,
+            retried_from: None,
+            pull_request: None,
+            rate_limit_bypassed: false,
+            env: None,
+            output_diff: None,
+            changelog: None,
         }
     }
 
@@ -100,6 +188,16 @@ impl Job {
             output_truncated: false,
             error: None,
             dry_run: true,
+            pinned: false,
+            rollback_of: None,
+            tag: None,
+            resumed_from: None,
+            retried_from: None,
+            pull_request: None,
+            rate_limit_bypassed: false,
+            env: None,
+            output_diff: None,
+            changelog: None,
         }
     }
 
@@ -1,41 +1,495 @@
 pub mod api;
+pub mod artifacts;
+pub mod auth;
+pub mod cache;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod command_policy;
+#[cfg(unix)]
+pub mod daemon;
 pub mod db;
+pub mod dispatcher;
+pub mod dry_run;
+pub mod env_config;
+pub mod env_file;
 pub mod error;
+pub mod executor;
+pub mod forward;
+pub mod freeze;
+pub mod git;
+pub mod github_app;
+pub mod http;
+pub mod ip_filter;
 pub mod job;
+pub mod logging;
+pub mod mirror;
+pub mod output;
+pub mod output_diff;
+pub mod params;
+pub mod path_filter;
+pub mod priority;
+pub mod provider;
+pub mod pushgateway;
 pub mod rate_limit;
+pub mod release;
+pub mod retention;
+pub mod run_as;
+pub mod schedule;
+pub mod secrets;
+pub mod steps;
+pub mod stop;
+pub mod systemd;
+pub mod templates;
 pub mod ui;
 pub mod utils;
+pub mod version;
 pub mod webhook;
+pub mod workspace;
 
-use api::stream::{JobEvent, LogChunkEvent};
+use api::stream::{EventHub, JobEvent, LogChunkEvent};
+use arc_swap::ArcSwap;
 use chrono::{DateTime, Utc};
 use db::SqlJobStore;
+use dispatcher::Dispatcher;
+use logging::ThreadSafeLogManager;
 use rate_limit::RateLimiter;
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
-use tokio::sync::{Mutex, broadcast};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::info;
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CICDConfig {
+    #[serde(default)]
+    pub server: ServerConfig,
+    /// Environment variables for the scripts of all projects (`[env]`
+    /// section); a project's own `env` overrides them
+    pub env: Option<BTreeMap<String, String>>,
+    /// Project settings shared by the projects using them (`[templates]`
+    /// section), already applied to `project`
+    pub templates: Option<BTreeMap<String, toml::Table>>,
     pub project: Vec<ProjectConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// Default webhook route
+pub const DEFAULT_WEBHOOK_PATH: &str = "/webhook";
+
+/// Commit message markers that skip a push unless `skip_ci_patterns` is set
+pub const DEFAULT_SKIP_CI_PATTERNS: [&str; 2] = ["[skip ci]", "[ci skip]"];
+
+/// Normalize a route path: leading slash, no trailing slash
+fn normalize_route_path(path: &str) -> String {
+    format!("/{}", path.trim_matches('/'))
+}
+
+/// Wait before the first retry of a failed main script unless
+/// `retry_delay_seconds` is set
+pub const DEFAULT_RETRY_DELAY_SECONDS: u64 = 10;
+
+/// A `*_timeout_seconds` setting as a duration; unset or 0 means no limit
+pub(crate) fn timeout(seconds: Option<u64>) -> Option<Duration> {
+    seconds.filter(|&secs| secs > 0).map(Duration::from_secs)
+}
+
+impl CICDConfig {
+    /// Parse a config file, applying `[templates]` to the projects using them
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        if !table.contains_key("templates") {
+            // Parsed directly, errors point at the offending line
+            return toml::from_str(contents).map_err(|e| e.to_string());
+        }
+        templates::apply(&mut table)?;
+        Self::deserialize(table).map_err(|e| e.to_string())
+    }
+
+    /// Read the secrets projects reference by environment variable
+    /// (`webhook_secret_env`). Fails if a project requiring a webhook secret
+    /// references an unset variable, or a `secrets` entry does.
+    pub fn resolve_secrets(&mut self) -> Result<(), error::CicdError> {
+        for project in &mut self.project {
+            project.secret_vars()?;
+            let Some(var) = &project.webhook_secret_env else {
+                continue;
+            };
+            if project.webhook_secret.is_some() {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}' sets both webhook_secret and webhook_secret_env",
+                    project.name
+                )));
+            }
+            project.resolved_webhook_secrets = std::env::var(var)
+                .ok()
+                .filter(|s| !s.is_empty())
+                .into_iter()
+                .collect();
+            if project.resolved_webhook_secrets.is_empty() && project.needs_webhook_secret() {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}' reads its webhook secret from {}, which is not set",
+                    project.name, var
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge `[env]` into each project's `env`, expanding `${NAME}`
+    /// references to the server's environment
+    pub fn apply_env(&mut self) {
+        for project in &mut self.project {
+            let mut env = self.env.clone().unwrap_or_default();
+            env.extend(project.env.take().unwrap_or_default());
+            for value in env.values_mut() {
+                *value = expand_env(value);
+            }
+            project.env = (!env.is_empty()).then_some(env);
+        }
+    }
+
+    /// Check what deserializing can't: every project has something to run,
+    /// its steps are well-formed and its environment variables can be set
+    pub fn validate(&self) -> Result<(), error::CicdError> {
+        validate_env("[env]", self.env.as_ref()).map_err(error::CicdError::ConfigError)?;
+        for project in &self.project {
+            let owner = format!("Project '{}'", project.name);
+            validate_env(&owner, project.env.as_ref()).map_err(error::CicdError::ConfigError)?;
+            validate_env(&format!("{} secrets", owner), project.secrets.as_ref())
+                .map_err(error::CicdError::ConfigError)?;
+            let steps = project.steps.as_deref().unwrap_or_default();
+            if steps.is_empty() && project.run_script.trim().is_empty() {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}' needs a run_script or steps",
+                    project.name
+                )));
+            }
+            steps::validate(&project.name, steps).map_err(error::CicdError::ConfigError)?;
+            cache::validate(&project.name, project.caches.as_deref().unwrap_or_default())
+                .map_err(error::CicdError::ConfigError)?;
+            if project.nice.is_some_and(|nice| !(-20..=19).contains(&nice)) {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}': nice must be between -20 and 19",
+                    project.name
+                )));
+            }
+            if project
+                .run_as_user
+                .as_deref()
+                .is_some_and(|user| user.trim().is_empty())
+            {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}' has an empty run_as_user",
+                    project.name
+                )));
+            }
+            if let Some(executor::ExecutorConfig::Container(container)) = &project.executor
+                && container.image.trim().is_empty()
+            {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}': the container executor needs an image",
+                    project.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if `path` is a global or project-specific webhook route
+    pub fn is_webhook_path(&self, path: &str) -> bool {
+        self.server.webhook_paths().iter().any(|p| p == path)
+            || self
+                .project
+                .iter()
+                .any(|p| p.webhook_path().is_some_and(|p| p == path))
+    }
+}
+
+/// Replace `${NAME}` in `value` with the server's environment variable `NAME`
+/// (empty if unset)
+fn expand_env(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        let name = &rest[start + 2..start + 2 + len];
+        expanded.push_str(&std::env::var(name).unwrap_or_default());
+        rest = &rest[start + 2 + len + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Check that the names of `env` can be set for scripts. `CICD_` names are
+/// reserved for the variables the runner sets.
+fn validate_env<T>(owner: &str, env: Option<&BTreeMap<String, T>>) -> Result<(), String> {
+    for name in env.into_iter().flat_map(BTreeMap::keys) {
+        if name.is_empty() || name.contains(['=', '\0']) {
+            return Err(format!(
+                "{} has an invalid environment variable name '{}'",
+                owner, name
+            ));
+        }
+        if name.starts_with("CICD_") {
+            return Err(format!(
+                "{} sets {}; CICD_ variables are reserved for the runner",
+                owner, name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Server-wide settings (`[server]` section)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ServerConfig {
+    /// Sub-path the server is mounted under behind a reverse proxy (e.g. "/cicd")
+    pub base_path: Option<String>,
+    /// Global webhook routes, replacing the default `/webhook`
+    pub webhook_paths: Option<Vec<String>>,
+    /// Only accept webhook requests from these addresses and CIDR ranges
+    pub webhook_allowed_ips: Option<ip_filter::IpFilter>,
+    /// Reverse proxies whose `X-Forwarded-For` header names the client
+    pub trusted_proxies: Option<ip_filter::IpFilter>,
+
+    // HTTP listener tuning (applied at startup)
+    pub request_timeout_seconds: Option<u64>,
+    pub header_read_timeout_seconds: Option<u64>,
+    pub keep_alive: Option<bool>,
+    pub max_connections: Option<usize>,
+    pub max_body_size: Option<usize>,
+
+    /// Jobs run at the same time, at most one per checkout (default: 1;
+    /// applied at startup)
+    pub max_concurrent_jobs: Option<usize>,
+
+    /// Delete finished, unpinned jobs older than this many days
+    pub job_retention_days: Option<u64>,
+    /// Directory job artifacts are stored in (default: `cicd_artifacts`)
+    pub artifacts_dir: Option<String>,
+    /// Directory project caches are stored in (default: `cicd_cache`)
+    pub cache_dir: Option<String>,
+    /// Directory per-job workspaces are created in (default: `cicd_workspaces`)
+    pub workspace_dir: Option<String>,
+
+    /// Store rate limit state in the database so quotas survive restarts
+    pub persist_rate_limits: Option<bool>,
+    /// Hex-encoded SHA-256 of the token that lets webhooks bypass rate limits
+    pub rate_limit_bypass_token_sha256: Option<String>,
+
+    /// Proxy webhooks that trigger no project (and non-push events) to this URL
+    pub forward_unmatched_to: Option<String>,
+
+    /// Push the metrics of every finished job to a Prometheus Pushgateway
+    pub pushgateway: Option<pushgateway::PushgatewayConfig>,
+
+    /// Require HTTP Basic auth for the UI and API (not webhook paths)
+    pub basic_auth: Option<auth::BasicAuthConfig>,
+    /// Authenticate as a GitHub App, see `github_app`
+    pub github_app: Option<github_app::GithubAppConfig>,
+    /// Shell running scripts in shell mode (default: `sh`); setting it turns
+    /// shell mode on for projects that don't set `shell`
+    pub default_shell: Option<String>,
+    /// Programs scripts may start; anything else is refused (default: any)
+    pub allowed_commands: Option<path_filter::PathFilter>,
+    /// Environment variables scripts may not be given (default: none)
+    pub denied_env: Option<path_filter::PathFilter>,
+    /// Strip ANSI escape sequences from the script output of projects that
+    /// don't set `strip_ansi`
+    pub strip_ansi: Option<bool>,
+    /// Keep stripped output as written for the log download, for projects
+    /// that don't set `keep_ansi_in_download`
+    pub keep_ansi_in_download: Option<bool>,
+}
+
+impl ServerConfig {
+    /// Returns the normalized base path: leading slash, no trailing slash,
+    /// or an empty string when served from the root.
+    pub fn base_path(&self) -> String {
+        let trimmed = self.base_path.as_deref().unwrap_or("").trim_matches('/');
+        if trimmed.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", trimmed)
+        }
+    }
+
+    /// Returns the normalized global webhook routes (default: `/webhook`)
+    pub fn webhook_paths(&self) -> Vec<String> {
+        match &self.webhook_paths {
+            Some(paths) => paths.iter().map(|p| normalize_route_path(p)).collect(),
+            None => vec![DEFAULT_WEBHOOK_PATH.to_string()],
+        }
+    }
+
+    /// Returns the maximum time to produce a response's headers.
+    /// Defaults to 30 seconds if `request_timeout_seconds` is not set.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_seconds.unwrap_or(30))
+    }
+
+    /// Returns the maximum time to receive request headers.
+    /// Defaults to 30 seconds if `header_read_timeout_seconds` is not set.
+    pub fn header_read_timeout(&self) -> Duration {
+        Duration::from_secs(self.header_read_timeout_seconds.unwrap_or(30))
+    }
+
+    /// Returns true if HTTP/1.1 keep-alive is enabled (default: true)
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive.unwrap_or(true)
+    }
+
+    /// Returns true if rate limit state is persisted (default: false)
+    pub fn persist_rate_limits(&self) -> bool {
+        self.persist_rate_limits.unwrap_or(false)
+    }
+
+    /// Returns the directory job artifacts are stored in
+    pub fn artifacts_dir(&self) -> &str {
+        self.artifacts_dir
+            .as_deref()
+            .unwrap_or(artifacts::DEFAULT_ARTIFACTS_DIR)
+    }
+
+    /// Returns the directory project caches are stored in
+    pub fn cache_dir(&self) -> &str {
+        self.cache_dir
+            .as_deref()
+            .unwrap_or(cache::DEFAULT_CACHE_DIR)
+    }
+
+    /// Returns the directory per-job workspaces are created in
+    pub fn workspace_dir(&self) -> &str {
+        self.workspace_dir
+            .as_deref()
+            .unwrap_or(workspace::DEFAULT_WORKSPACE_DIR)
+    }
+
+    /// Returns the limits on what pipeline scripts start
+    pub fn command_policy(&self) -> command_policy::CommandPolicy {
+        command_policy::CommandPolicy {
+            allowed_commands: self.allowed_commands.clone(),
+            denied_env: self.denied_env.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProjectConfig {
     pub name: String,
     pub repo_path: String,
-    pub branches: Vec<String>,
+    /// Branches (or globs like `release/*`) whose pushes trigger the project
+    pub branches: path_filter::PathFilter,
+    /// Script run by jobs; may be left out if `steps` are given
+    #[serde(default)]
     pub run_script: String,
+    /// Steps run in order instead of `run_script`
+    pub steps: Option<Vec<steps::StepConfig>>,
+    /// Run scripts through the shell (`sh -c`) instead of splitting them into
+    /// a command and its arguments
+    pub shell: Option<bool>,
+    /// Where the scripts run: on the server, or in containers (default: on
+    /// the server)
+    pub executor: Option<executor::ExecutorConfig>,
+    /// OS user the scripts run as (default: the server's user)
+    pub run_as_user: Option<String>,
+    /// Niceness scripts run with, from -20 to 19 (default: the server's)
+    pub nice: Option<i32>,
+    /// Disk I/O priority of scripts, `idle` or `low` (default: the server's)
+    pub io_priority: Option<priority::IoPriority>,
+    /// Environment variables for all of the project's scripts, steps and
+    /// hooks; includes `[env]` once the config is loaded
+    pub env: Option<BTreeMap<String, String>>,
+    /// Dotenv-style file read into the scripts' environment before each run,
+    /// relative to `repo_path` or absolute; overrides `env`
+    pub env_file: Option<String>,
+    /// Environment variables for the project's scripts whose values are
+    /// masked in job output, see `secrets`
+    pub secrets: Option<BTreeMap<String, secrets::Secret>>,
+    /// Scripts for branches (or branch globs) overriding `run_script`
     pub branch_scripts: Option<HashMap<String, String>>,
+    /// Script for rollback jobs (default: the branch's run script)
+    pub rollback_script: Option<String>,
+    /// Run for pushes of semver tags (`v1.2.3`), see `release_script`
+    pub semver_releases: Option<bool>,
+    /// Run for pushes of tags matching one of these globs (e.g. `v*`)
+    pub tags: Option<path_filter::PathFilter>,
+    /// Script for tag releases (default: `run_script`)
+    pub release_script: Option<String>,
+    /// Run `release_script` for the tags of published GitHub releases
+    pub on_release: Option<bool>,
+    /// Run `pr_script` for GitHub pull requests into one of `branches`
+    pub on_pull_request: Option<bool>,
+    /// Script validating a pull request's head commit
+    pub pr_script: Option<String>,
+    /// Script tearing down what was deployed for a branch once it is deleted
+    pub branch_deleted_script: Option<String>,
+    /// Git host sending this project's webhooks (default: GitHub)
+    pub provider: Option<provider::Provider>,
+    /// Where the push fields are in payloads of the `generic` provider
+    pub generic: Option<provider::GenericMapping>,
+    /// Head commit message markers that skip a push
+    /// (default: `[skip ci]` and `[ci skip]`)
+    pub skip_ci_patterns: Option<Vec<String>>,
+    /// Pushers (e.g. `dependabot[bot]`) whose pushes don't trigger the project
+    pub ignore_pushers: Option<Vec<String>>,
+    /// Only head commits by these authors (name or email) trigger the project
+    pub only_authors: Option<Vec<String>>,
+    /// Ignore force pushes
+    pub ignore_forced: Option<bool>,
+    /// Only branch pushes whose head commit is a merge commit trigger the project
+    pub only_merge_commits: Option<bool>,
     pub with_webhook_secret: Option<bool>,
     pub webhook_secret: Option<String>,
+    /// Further accepted webhook secrets, e.g. the old and new secret while
+    /// rotating it
+    pub webhook_secrets: Option<Vec<String>>,
+    /// Environment variable holding the webhook secret, read at (re)load
+    pub webhook_secret_env: Option<String>,
+    /// Value of `webhook_secret_env` (see `CICDConfig::resolve_secrets`), or
+    /// the secrets of an environment-only config. Never serialized, so they
+    /// stay out of `GET /api/config/current` and job snapshots.
+    #[serde(skip)]
+    resolved_webhook_secrets: Vec<String>,
+    /// Accept legacy HMAC SHA-1 signatures (`X-Hub-Signature`) from
+    /// senders without SHA-256 ones
+    pub allow_sha1_signature: Option<bool>,
+    /// Deliveries come from the `[server.github_app]` GitHub App, and jobs get
+    /// an installation token in `CICD_GITHUB_TOKEN`
+    pub github_app: Option<bool>,
+    /// Dedicated webhook route; the project is then only triggered through it
+    pub webhook_path: Option<String>,
+    /// Only accept pushes for this project from these addresses and CIDR ranges
+    pub allowed_ips: Option<ip_filter::IpFilter>,
+
+    // monorepo sub-projects
+    /// Repository name webhooks must carry (default: `name`), so several
+    /// projects can deploy parts of the same repository
+    pub repository: Option<String>,
+    /// Owner-qualified repository name (`owner/name`) webhooks must carry;
+    /// matched instead of `repository` when the payload has one
+    pub repo_full_name: Option<String>,
+    /// Only run for pushes changing a file that matches one of these globs
+    pub match_paths: Option<path_filter::PathFilter>,
+    /// Directory, relative to the repository, that scripts run in
+    pub working_dir: Option<String>,
 
     // ?
     pub reset_to_remote: Option<bool>,
+    /// Check out the pushed commit (detached) instead of the branch tip
+    pub checkout_commit: Option<bool>,
+    /// Cancel a branch's queued jobs when a newer push to it is queued
+    pub cancel_superseded: Option<bool>,
+    /// `[templates]` entry providing the settings the project doesn't set
+    pub template: Option<String>,
+    /// Jobs of projects in the same group never run at the same time, e.g.
+    /// projects deploying to the same target
+    pub concurrency_group: Option<String>,
 
     // lifecycle hooks
     pub pre_script: Option<String>,
@@ -43,10 +497,67 @@ pub struct ProjectConfig {
     pub post_success_script: Option<String>,
     pub post_failure_script: Option<String>,
     pub post_always_script: Option<String>,
+    /// A failing `pre_script` is recorded, but the job goes on
+    pub pre_script_allow_failure: Option<bool>,
 
     // rate limiting
     pub rate_limit_requests: Option<usize>,
     pub rate_limit_window_seconds: Option<u64>,
+
+    // deploy freezes
+    pub freeze_windows: Option<Vec<freeze::FreezeWindow>>,
+    pub freeze_action: Option<freeze::FreezeAction>,
+
+    /// Cron expression (e.g. `0 3 * * *`) starting a job on a schedule
+    pub schedule: Option<schedule::Schedule>,
+    /// Branch scheduled jobs build (default: the first of `branches`)
+    pub schedule_branch: Option<String>,
+
+    /// Parameters manual triggers may pass (default: any)
+    pub inputs: Option<Vec<params::InputConfig>>,
+
+    /// Pause the project (maintenance mode) after this many failed jobs in a row
+    pub max_consecutive_failures: Option<u32>,
+    /// Time a job may run before it is killed and marked `timed_out`
+    pub timeout_seconds: Option<u64>,
+    /// Time each git operation may take
+    pub git_timeout_seconds: Option<u64>,
+    /// Time `pre_script` may run
+    pub pre_script_timeout_seconds: Option<u64>,
+    /// Time the main script, and each step without its own timeout, may run
+    pub script_timeout_seconds: Option<u64>,
+    /// Time each post hook may run
+    pub post_script_timeout_seconds: Option<u64>,
+    /// Time a stopped script has after `SIGTERM` before it is killed (default: 10)
+    pub kill_grace_seconds: Option<u64>,
+    /// Run a failed main script (or step) again up to this many times
+    pub retry_count: Option<u32>,
+    /// Wait before the first retry, doubled for each further one (default: 10)
+    pub retry_delay_seconds: Option<u64>,
+    /// Most bytes kept of each stream (stdout, stderr) of a step's output;
+    /// 0 for no limit (default: 1MB)
+    pub max_step_output_bytes: Option<u64>,
+    /// Strip ANSI escape sequences from script output before it is stored
+    /// or streamed (default: `[server] strip_ansi`, or false)
+    pub strip_ansi: Option<bool>,
+    /// Also store each step's output with its escape sequences, for the log
+    /// download (default: `[server] keep_ansi_in_download`, or false)
+    pub keep_ansi_in_download: Option<bool>,
+
+    /// Build each deploy in its own release directory and switch a
+    /// `current` symlink to it on success
+    pub release: Option<release::ReleaseConfig>,
+
+    /// Remotes the deployed branch is pushed to after a successful deploy
+    pub mirrors: Option<Vec<mirror::MirrorConfig>>,
+    /// Files of the script directory kept with the job after a successful
+    /// main script, as globs like `dist/**`
+    pub artifacts: Option<path_filter::PathFilter>,
+    /// Directories kept between jobs, e.g. `node_modules`
+    pub caches: Option<Vec<cache::CacheConfig>>,
+    /// Check out each job's commit into a fresh workspace instead of
+    /// resetting `repo_path`
+    pub workspace: Option<workspace::WorkspaceConfig>,
 }
 
 impl ProjectConfig {
@@ -55,28 +566,273 @@ impl ProjectConfig {
         self.with_webhook_secret.unwrap_or(false)
     }
 
-    /// Returns true if a valid (non-empty) webhook_secret is set.
+    /// Returns true if a valid (non-empty) webhook secret is set.
     pub fn has_valid_secret(&self) -> bool {
+        !self.webhook_secrets().is_empty()
+    }
+
+    /// Returns the accepted webhook secrets: `webhook_secret` (or the value of
+    /// the `webhook_secret_env` variable) and `webhook_secrets`, empty ones
+    /// left out
+    pub fn webhook_secrets(&self) -> Vec<&str> {
         self.webhook_secret
-            .as_ref()
-            .map(|s| !s.is_empty())
-            .unwrap_or(false)
+            .iter()
+            .chain(&self.resolved_webhook_secrets)
+            .map(String::as_str)
+            .chain(self.webhook_secrets.iter().flatten().map(String::as_str))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// Returns true if the project uses the GitHub App (default: false)
+    pub fn github_app(&self) -> bool {
+        self.github_app.unwrap_or(false)
+    }
+
+    /// Returns true if legacy SHA-1 webhook signatures are accepted
+    /// (default: false)
+    pub fn allow_sha1_signature(&self) -> bool {
+        self.allow_sha1_signature.unwrap_or(false)
+    }
+
+    /// Returns the git host sending this project's webhooks
+    pub fn provider(&self) -> provider::Provider {
+        self.provider.unwrap_or_default()
+    }
+
+    /// Returns the normalized project-specific webhook route, if set
+    pub fn webhook_path(&self) -> Option<String> {
+        self.webhook_path.as_deref().map(normalize_route_path)
+    }
+
+    /// Returns true if a webhook received on `path` may trigger this project:
+    /// its own `webhook_path` if set, otherwise any global webhook path.
+    pub fn accepts_webhook_path(&self, path: &str, server: &ServerConfig) -> bool {
+        match self.webhook_path() {
+            Some(own) => own == path,
+            None => server.webhook_paths().iter().any(|p| p == path),
+        }
+    }
+
+    /// Returns the repository name that triggers this project
+    pub fn repository(&self) -> &str {
+        self.repository.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Returns true if a push of the repository `name` (`full_name`, if the
+    /// payload has one) triggers this project: by `repo_full_name` if both
+    /// are known (case-insensitive), otherwise by `repository`
+    pub fn matches_repository(&self, name: &str, full_name: Option<&str>) -> bool {
+        match (&self.repo_full_name, full_name) {
+            (Some(own), Some(full_name)) => own.eq_ignore_ascii_case(full_name),
+            _ => self.repository() == name,
+        }
+    }
+
+    /// Returns true if a push changing `changed_files` should run this project.
+    /// Projects without `match_paths` always run, as do pushes whose changed
+    /// files are unknown.
+    pub fn matches_changed_files(&self, changed_files: Option<&[String]>) -> bool {
+        match (&self.match_paths, changed_files) {
+            (Some(filter), Some(files)) => filter.matches_any(files),
+            _ => true,
+        }
+    }
+
+    /// Returns the environment variables set for the project's scripts
+    /// (`env`, including `[env]`)
+    pub fn env_vars(&self) -> Vec<(&str, String)> {
+        self.env
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect()
+    }
+
+    /// Returns the path of `env_file`, if set
+    pub fn env_file_path(&self, repo_path: &str) -> Option<PathBuf> {
+        self.env_file
+            .as_deref()
+            .map(|file| Path::new(repo_path).join(file))
+    }
+
+    /// Returns the project's secrets as environment variables for its
+    /// scripts. Fails if a secret's environment variable isn't set.
+    pub fn secret_vars(&self) -> Result<Vec<(&str, String)>, error::CicdError> {
+        self.secrets
+            .iter()
+            .flatten()
+            .map(|(name, secret)| {
+                let value = secret.value().map_err(|var| {
+                    error::CicdError::ConfigError(format!(
+                        "Project '{}' reads secret {} from {}, which is not set",
+                        self.name, name, var
+                    ))
+                })?;
+                Ok((name.as_str(), value))
+            })
+            .collect()
+    }
+
+    /// A copy of the project to store with a job (the pipeline snapshot
+    /// used to run, retry and resume it): the webhook secrets are left out
+    /// and literal `secrets` values masked. See `restore_secrets`.
+    pub fn snapshot(&self) -> ProjectConfig {
+        let mut project = self.clone();
+        project.webhook_secret = None;
+        project.webhook_secrets = None;
+        for secret in project.secrets.iter_mut().flat_map(BTreeMap::values_mut) {
+            if let secrets::Secret::Value(value) = secret {
+                *value = utils::MASK.to_string();
+            }
+        }
+        project
+    }
+
+    /// Put back the literal `secrets` values masked by `snapshot`, read from
+    /// the project's current config. Fails if one of them is no longer
+    /// configured.
+    pub fn restore_secrets(
+        &mut self,
+        current: Option<&ProjectConfig>,
+    ) -> Result<(), error::CicdError> {
+        for (name, secret) in self.secrets.iter_mut().flatten() {
+            if !matches!(secret, secrets::Secret::Value(_)) {
+                continue;
+            }
+            let Some(current) = current.and_then(|p| p.secrets.as_ref()?.get(name)) else {
+                return Err(error::CicdError::ConfigError(format!(
+                    "Project '{}' no longer configures secret {}",
+                    self.name, name
+                )));
+            };
+            *secret = current.clone();
+        }
+        Ok(())
+    }
+
+    /// Returns the directory scripts run in: `working_dir` inside `base`
+    /// (the repository or release directory), or `base` itself
+    pub fn script_dir(&self, base: &str) -> String {
+        match self.working_dir.as_deref().map(|d| d.trim_matches('/')) {
+            Some(dir) if !dir.is_empty() => Path::new(base).join(dir).display().to_string(),
+            _ => base.to_string(),
+        }
+    }
+
+    /// If a deploy freeze is active at `now`, returns when it ends and why
+    pub fn frozen_until(&self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, Option<String>)> {
+        freeze::frozen_until(self.freeze_windows.as_deref()?, now)
+    }
+
+    /// Returns true if pushes of semver tags trigger this project
+    pub fn semver_releases(&self) -> bool {
+        self.semver_releases.unwrap_or(false)
+    }
+
+    /// Returns true if published GitHub releases trigger this project
+    pub fn on_release(&self) -> bool {
+        self.on_release.unwrap_or(false)
+    }
+
+    /// Returns true if pull requests trigger this project (with `pr_script`)
+    pub fn on_pull_request(&self) -> bool {
+        self.on_pull_request.unwrap_or(false)
+    }
+
+    /// Returns true if a push of `tag` triggers this project: a semver tag
+    /// with `semver_releases`, or a tag matching one of `tags`
+    pub fn accepts_tag(&self, tag: &str) -> bool {
+        (self.semver_releases() && version::parse_tag(tag).is_some())
+            || self.tags.as_ref().is_some_and(|tags| tags.matches(tag))
+    }
+
+    /// Returns the skip marker `commit_message` contains, if any
+    /// (case-insensitive)
+    pub fn skip_ci_marker(&self, commit_message: &str) -> Option<&str> {
+        let message = commit_message.to_lowercase();
+        let contained = |p: &&str| !p.is_empty() && message.contains(&p.to_lowercase());
+        match &self.skip_ci_patterns {
+            Some(patterns) => patterns.iter().map(String::as_str).find(contained),
+            None => DEFAULT_SKIP_CI_PATTERNS.into_iter().find(contained),
+        }
+    }
+
+    /// Returns true if pushes by `pusher` are ignored (case-insensitive)
+    pub fn ignores_pusher(&self, pusher: Option<&str>) -> bool {
+        let (Some(ignored), Some(pusher)) = (&self.ignore_pushers, pusher) else {
+            return false;
+        };
+        ignored.iter().any(|p| p.eq_ignore_ascii_case(pusher))
+    }
+
+    /// Returns true if a head commit by this author may trigger the project:
+    /// `only_authors` is unset or lists the author's name or email
+    /// (case-insensitive)
+    pub fn accepts_author(&self, name: Option<&str>, email: Option<&str>) -> bool {
+        let Some(authors) = &self.only_authors else {
+            return true;
+        };
+        authors.iter().any(|a| {
+            name.is_some_and(|n| a.eq_ignore_ascii_case(n))
+                || email.is_some_and(|e| a.eq_ignore_ascii_case(e))
+        })
+    }
+
+    /// Returns true if force pushes are ignored (default: false)
+    pub fn ignore_forced(&self) -> bool {
+        self.ignore_forced.unwrap_or(false)
+    }
+
+    /// Returns true if only merge commits trigger branch pushes (default: false)
+    pub fn only_merge_commits(&self) -> bool {
+        self.only_merge_commits.unwrap_or(false)
+    }
+
+    /// Returns true if a push cancels the older queued jobs of its branch
+    /// (default: true)
+    pub fn cancel_superseded(&self) -> bool {
+        self.cancel_superseded.unwrap_or(true)
+    }
+
+    /// Returns what happens to pushes during a freeze (default: reject)
+    pub fn freeze_action(&self) -> freeze::FreezeAction {
+        self.freeze_action.unwrap_or_default()
+    }
+
+    /// Returns the branch scheduled jobs build: `schedule_branch`, or the
+    /// first of `branches` unless it's a glob
+    pub fn schedule_branch(&self) -> Option<&str> {
+        match &self.schedule_branch {
+            Some(branch) => Some(branch),
+            None => self
+                .branches
+                .patterns()
+                .first()
+                .filter(|b| !b.contains(['*', '?', '[', '{']))
+                .map(String::as_str),
+        }
+    }
+
+    /// Returns true if pushes to `branch` trigger this project: it is one
+    /// of `branches` or matches one of their globs
+    pub fn accepts_branch(&self, branch: &str) -> bool {
+        self.branches.patterns().iter().any(|b| b == branch) || self.branches.matches(branch)
     }
 
     /// Returns the script to run for a specific branch.
-    /// If `branch_scripts` contains the branch, returns that script,
-    /// otherwise returns the general `run_script`.
+    /// If `branch_scripts` contains the branch, returns that script, else the
+    /// script of the longest glob key matching it, otherwise returns the
+    /// general `run_script`.
     pub fn get_run_script_for_branch(&self, branch: &str) -> &str {
-        if let Some(scripts) = &self.branch_scripts {
-            if let Some(script) = scripts.get(branch) {
-                info!(
-                    project = %self.name,
-                    branch = %branch,
-                    script = %script,
-                    "Using branch-specific script"
-                );
-                return script;
-            }
+        if let Some(script) = self.branch_script(branch) {
+            info!(
+                project = %self.name,
+                branch = %branch,
+                script = %script,
+                "Using branch-specific script"
+            );
+            return script;
         }
         info!(
             project = %self.name,
@@ -87,6 +843,144 @@ impl ProjectConfig {
         &self.run_script
     }
 
+    /// The `branch_scripts` entry for a branch: its own, else the longest
+    /// glob key matching it
+    fn branch_script(&self, branch: &str) -> Option<&String> {
+        let scripts = self.branch_scripts.as_ref()?;
+        scripts.get(branch).or_else(|| {
+            scripts
+                .iter()
+                .filter(|(pattern, _)| path_filter::glob_matches(pattern, branch))
+                .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then(b.cmp(a)))
+                .map(|(_, script)| script)
+        })
+    }
+
+    /// Returns the shell scripts run through if shell mode is on: `[server]
+    /// default_shell`, or `sh`. Shell mode is on with `shell = true`, and by
+    /// default once a `default_shell` is set.
+    pub fn shell(&self, server: &ServerConfig) -> Option<String> {
+        let enabled = self.shell.unwrap_or(server.default_shell.is_some());
+        enabled.then(|| {
+            server
+                .default_shell
+                .clone()
+                .unwrap_or_else(|| "sh".to_string())
+        })
+    }
+
+    /// The executor starting the scripts of a job; `mounts` are the host
+    /// paths they work with
+    pub fn executor(
+        &self,
+        mounts: Vec<String>,
+        user: Option<run_as::RunAs>,
+    ) -> Box<dyn executor::Executor> {
+        let priority = priority::Priority {
+            nice: self.nice,
+            io: self.io_priority,
+        };
+        self.executor
+            .as_ref()
+            .unwrap_or(&executor::ExecutorConfig::Host)
+            .executor(mounts, user, priority)
+    }
+
+    /// The user the project's scripts run as, if not the server's
+    pub fn run_as(&self) -> Result<Option<run_as::RunAs>, error::CicdError> {
+        self.run_as_user
+            .as_deref()
+            .map(|name| {
+                run_as::RunAs::lookup(name).map_err(|e| {
+                    error::CicdError::ConfigError(format!(
+                        "Project '{}': run_as_user: {}",
+                        self.name, e
+                    ))
+                })
+            })
+            .transpose()
+    }
+
+    /// Returns what happens to ANSI escape sequences in script output; unset
+    /// options fall back to the `[server]` ones
+    pub fn ansi_output(&self, server: &ServerConfig) -> output::AnsiOutput {
+        let strip = self.strip_ansi.or(server.strip_ansi).unwrap_or(false);
+        let keep_raw = self
+            .keep_ansi_in_download
+            .or(server.keep_ansi_in_download)
+            .unwrap_or(false);
+        match (strip, keep_raw) {
+            (false, _) => output::AnsiOutput::Keep,
+            (true, false) => output::AnsiOutput::Strip,
+            (true, true) => output::AnsiOutput::StripKeepRaw,
+        }
+    }
+
+    /// Time a job may run before it is killed, if limited
+    pub fn timeout(&self) -> Option<Duration> {
+        timeout(self.timeout_seconds)
+    }
+
+    /// Time each git operation of a job may take, if limited
+    pub fn git_timeout(&self) -> Option<Duration> {
+        timeout(self.git_timeout_seconds)
+    }
+
+    /// Time `pre_script` may run, if limited
+    pub fn pre_script_timeout(&self) -> Option<Duration> {
+        timeout(self.pre_script_timeout_seconds)
+    }
+
+    /// Time the main script may run, if limited
+    pub fn script_timeout(&self) -> Option<Duration> {
+        timeout(self.script_timeout_seconds)
+    }
+
+    /// Time each post hook may run, if limited
+    pub fn post_script_timeout(&self) -> Option<Duration> {
+        timeout(self.post_script_timeout_seconds)
+    }
+
+    /// Time a cancelled or timed out script has to exit after `SIGTERM`
+    pub fn kill_grace(&self) -> Duration {
+        self.kill_grace_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(stop::DEFAULT_KILL_GRACE)
+    }
+
+    /// Wait before the `retry`th retry (from 1) of a failed main script:
+    /// `retry_delay_seconds`, doubled for each retry after the first
+    pub fn retry_delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .retry_delay_seconds
+            .unwrap_or(DEFAULT_RETRY_DELAY_SECONDS);
+        let factor = 1u64 << retry.saturating_sub(1).min(16);
+        Duration::from_secs(delay.saturating_mul(factor))
+    }
+
+    /// Most bytes kept of each stream of a step's output, if capped
+    pub fn step_output_limit(&self) -> Option<usize> {
+        match self.max_step_output_bytes {
+            Some(0) => None,
+            Some(limit) => Some(usize::try_from(limit).unwrap_or(usize::MAX)),
+            None => Some(job::MAX_OUTPUT_SIZE),
+        }
+    }
+
+    /// Returns the steps a job for `branch` runs as its main part: `steps`,
+    /// unless the branch has its own script in `branch_scripts`; otherwise
+    /// the branch's run script as a single `main_script` step.
+    pub fn main_steps(&self, branch: &str) -> Vec<steps::StepConfig> {
+        if let Some(steps) = self.steps.as_ref().filter(|steps| !steps.is_empty())
+            && self.branch_script(branch).is_none()
+        {
+            return steps.clone();
+        }
+        vec![steps::StepConfig::main_script(
+            self.get_run_script_for_branch(branch),
+        )]
+    }
+
     /// Returns the maximum number of requests allowed for rate limiting.
     /// Defaults to 60 if `rate_limit_requests` is not set.
     pub fn get_rate_limit(&self) -> usize {
@@ -103,18 +997,34 @@ impl ProjectConfig {
     pub fn should_reset_to_remote(&self) -> bool {
         self.reset_to_remote.unwrap_or(true)
     }
+
+    /// Returns true if jobs build the pushed commit rather than the
+    /// branch tip (default: false)
+    pub fn checkout_commit(&self) -> bool {
+        self.checkout_commit.unwrap_or(false)
+    }
 }
 
 pub struct AppState {
-    pub job_execution_lock: Mutex<()>,
+    pub dispatcher: Dispatcher,
     pub job_store: SqlJobStore,
-    pub config: RwLock<CICDConfig>,
+    /// Current configuration; swapped atomically on reload so readers never block
+    pub config: ArcSwap<CICDConfig>,
     pub config_path: PathBuf,
+    /// Config was built from `CICD_*` environment variables instead of a file
+    pub config_from_env: bool,
+    /// Normalized `[server] base_path`, fixed at startup
+    pub base_path: String,
     pub start_time: Instant,
     pub started_at: DateTime<Utc>,
     pub rate_limiter: Arc<tokio::sync::Mutex<RateLimiter>>,
-    pub job_events: broadcast::Sender<JobEvent>,
-    pub log_chunks: broadcast::Sender<LogChunkEvent>,
+    pub job_events: EventHub<JobEvent>,
+    pub log_chunks: EventHub<LogChunkEvent>,
+    pub server_logs: ThreadSafeLogManager,
+    /// Client for outgoing HTTP requests (e.g. forwarded webhooks)
+    pub http_client: reqwest::Client,
+    /// GitHub App installation tokens, reused until they are about to expire
+    pub github_tokens: github_app::TokenCache,
 }
 
 /// Reload configuration from disk
@@ -124,9 +1034,11 @@ pub async fn reload_config(config_path: &PathBuf) -> Result<CICDConfig, error::C
     let config_str = fs::read_to_string(config_path)
         .map_err(|e| error::CicdError::ConfigError(format!("Failed to read config file: {}", e)))?;
 
-    // Use toml crate to parse the config
-    let new_config: CICDConfig = toml::from_str(&config_str)
+    let mut new_config = CICDConfig::parse(&config_str)
         .map_err(|e| error::CicdError::ConfigError(format!("Failed to parse config: {}", e)))?;
+    new_config.resolve_secrets()?;
+    new_config.validate()?;
+    new_config.apply_env();
 
     Ok(new_config)
 }
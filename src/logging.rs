@@ -0,0 +1,241 @@
+//! Logging output targets (stdout, journald, syslog) and in-memory server log capture
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use syslog::{Facility, Formatter3164, Logger, LoggerBackend};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Identifier used for journald/syslog entries
+const SYSLOG_IDENTIFIER: &str = "simple_git_cicd";
+
+/// Number of server log entries kept in memory
+pub const DEFAULT_LOG_CAPACITY: usize = 2000;
+
+/// Where server logs are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOutput {
+    Stdout,
+    Journald,
+    Syslog,
+}
+
+impl FromStr for LogOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "stdout" => Ok(LogOutput::Stdout),
+            "journald" => Ok(LogOutput::Journald),
+            "syslog" => Ok(LogOutput::Syslog),
+            other => Err(format!(
+                "Unknown log output '{}'. Use: stdout, journald, syslog",
+                other
+            )),
+        }
+    }
+}
+
+/// Initialize the global tracing subscriber for the given output.
+/// Falls back to stdout if the journald/syslog socket is unavailable.
+/// Every event is also captured into the `GlobalLogManager` buffer.
+pub fn init_tracing(filter: EnvFilter, output: LogOutput) {
+    let capture = LogCaptureLayer::new(GlobalLogManager::get());
+    let registry = tracing_subscriber::registry().with(filter).with(capture);
+
+    match output {
+        LogOutput::Stdout => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogOutput::Journald => match tracing_journald::layer() {
+            Ok(layer) => registry
+                .with(layer.with_syslog_identifier(SYSLOG_IDENTIFIER.to_string()))
+                .init(),
+            Err(e) => {
+                registry.with(tracing_subscriber::fmt::layer()).init();
+                tracing::warn!("journald unavailable ({}), logging to stdout", e);
+            }
+        },
+        LogOutput::Syslog => match SyslogLayer::new() {
+            Ok(layer) => registry.with(layer).init(),
+            Err(e) => {
+                registry.with(tracing_subscriber::fmt::layer()).init();
+                tracing::warn!("syslog unavailable ({}), logging to stdout", e);
+            }
+        },
+    }
+}
+
+/// Tracing layer that writes events to the local syslog socket (RFC 3164).
+/// Event fields are appended to the message as `key=value` pairs.
+pub struct SyslogLayer {
+    logger: Mutex<Logger<LoggerBackend, Formatter3164>>,
+}
+
+impl SyslogLayer {
+    pub fn new() -> Result<Self, syslog::Error> {
+        let formatter = Formatter3164 {
+            facility: Facility::LOG_DAEMON,
+            hostname: None,
+            process: SYSLOG_IDENTIFIER.to_string(),
+            pid: std::process::id(),
+        };
+        let logger = syslog::unix(formatter)?;
+        Ok(Self {
+            logger: Mutex::new(logger),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = format!(
+            "{}: {}{}",
+            event.metadata().target(),
+            visitor.message,
+            visitor.fields
+        );
+
+        let Ok(mut logger) = self.logger.lock() else {
+            return;
+        };
+        // Map tracing levels to syslog priorities
+        let _ = match *event.metadata().level() {
+            Level::ERROR => logger.err(message),
+            Level::WARN => logger.warning(message),
+            Level::INFO => logger.info(message),
+            Level::DEBUG | Level::TRACE => logger.debug(message),
+        };
+    }
+}
+
+/// A captured server log entry
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+    pub fields: String,
+    #[serde(skip)]
+    severity: Level,
+}
+
+/// Fixed-size ring buffer of recent server log entries
+pub struct LogManager {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl LogManager {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Add an entry, evicting the oldest one when full
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Returns up to `limit` most recent entries at `min_level` or more severe,
+    /// oldest first.
+    pub fn recent(&self, min_level: Level, limit: usize) -> Vec<LogEntry> {
+        let mut entries: Vec<LogEntry> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|e| e.severity <= min_level)
+            .take(limit)
+            .cloned()
+            .collect();
+        entries.reverse();
+        entries
+    }
+}
+
+/// Log manager shared between the tracing layer and API handlers
+pub type ThreadSafeLogManager = Arc<Mutex<LogManager>>;
+
+static GLOBAL_LOG_MANAGER: OnceLock<ThreadSafeLogManager> = OnceLock::new();
+
+/// Process-wide log manager fed by the tracing subscriber
+pub struct GlobalLogManager;
+
+impl GlobalLogManager {
+    /// Returns the global log manager, creating it on first use
+    pub fn get() -> ThreadSafeLogManager {
+        GLOBAL_LOG_MANAGER
+            .get_or_init(|| Arc::new(Mutex::new(LogManager::new(DEFAULT_LOG_CAPACITY))))
+            .clone()
+    }
+}
+
+/// Tracing layer that records events into a `ThreadSafeLogManager`
+pub struct LogCaptureLayer {
+    manager: ThreadSafeLogManager,
+}
+
+impl LogCaptureLayer {
+    pub fn new(manager: ThreadSafeLogManager) -> Self {
+        Self { manager }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogCaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let entry = LogEntry {
+            timestamp: Utc::now(),
+            level: metadata.level().to_string().to_lowercase(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields.trim_start().to_string(),
+            severity: *metadata.level(),
+        };
+        if let Ok(mut manager) = self.manager.lock() {
+            manager.push(entry);
+        }
+    }
+}
+
+/// Collects the `message` field and other fields of an event
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
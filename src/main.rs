@@ -1,25 +1,50 @@
-use axum::{Router, routing};
+use arc_swap::ArcSwap;
+use axum::{Router, extract::DefaultBodyLimit, http::StatusCode, middleware, routing};
 use chrono::Utc;
+use simple_git_cicd::api::stream::EventHub;
 use simple_git_cicd::api::{
-    get_config, get_job, get_job_logs, get_jobs, get_projects, get_stats, handle_webhook,
-    reload_config_endpoint, status, stream_jobs, stream_logs,
+    cancel_job, compare_jobs, delete_job_workspace, disable_maintenance, download_job_artifact,
+    download_job_logs, enable_maintenance, get_config, get_job, get_job_artifacts,
+    get_job_log_output, get_job_logs, get_job_output, get_job_payload, get_jobs,
+    get_project_caches, get_project_flaky, get_project_releases, get_projects, get_server_logs,
+    get_stats, pin_job, process_pending_intake, purge_project_cache, purge_project_caches,
+    redeliver, reload_config_endpoint, resume_job, retry_job, rollback_project, route_webhooks,
+    simulate_push, status, stream_jobs, stream_logs, unpin_job,
 };
+use simple_git_cicd::auth;
+#[cfg(unix)]
+use simple_git_cicd::daemon;
 use simple_git_cicd::db::{SqlJobStore, init_db};
+use simple_git_cicd::dispatcher::{self, DEFAULT_MAX_CONCURRENT_JOBS, Dispatcher};
+use simple_git_cicd::env_config;
 use simple_git_cicd::error::CicdError;
-use simple_git_cicd::rate_limit::RateLimiter;
+use simple_git_cicd::git;
+use simple_git_cicd::github_app::TokenCache;
+use simple_git_cicd::http::{self, ListenerSettings};
+use simple_git_cicd::logging::{GlobalLogManager, LogOutput, init_tracing};
+use simple_git_cicd::rate_limit::{self, RateLimiter};
+use simple_git_cicd::retention;
+use simple_git_cicd::schedule;
+use simple_git_cicd::systemd;
 use simple_git_cicd::ui::serve_ui;
+use simple_git_cicd::workspace;
 use simple_git_cicd::{AppState, CICDConfig};
 use std::fs;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::{Mutex, broadcast};
-use tracing::info;
+use tower::Layer;
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::normalize_path::NormalizePathLayer;
+use tower_http::timeout::TimeoutLayer;
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:8888";
 const DEFAULT_CONFIG_PATH: &str = "cicd_config.toml";
 const DEFAULT_DB_PATH: &str = "cicd_data.db";
+/// GitHub caps webhook payloads at 25 MB
+const DEFAULT_MAX_BODY_SIZE: usize = 25 * 1024 * 1024;
 
 /// Load and parse the configuration file
 fn load_config(path: &str) -> Result<CICDConfig, CicdError> {
@@ -27,17 +52,107 @@ fn load_config(path: &str) -> Result<CICDConfig, CicdError> {
         CicdError::ConfigError(format!("Failed to read config file '{}': {}", path, e))
     })?;
 
-    let config: CICDConfig = toml::from_str(&config_str).map_err(|e| {
+    let mut config = CICDConfig::parse(&config_str).map_err(|e| {
         CicdError::ConfigError(format!("Failed to parse config file '{}': {}", path, e))
     })?;
+    config.resolve_secrets()?;
+    config.validate()?;
+    config.apply_env();
 
     Ok(config)
 }
 
-#[tokio::main]
-async fn main() {
+const USAGE: &str = "Usage: simple_git_cicd [--daemon] [--pid-file <path>]
+
+Options:
+  --daemon           Detach and run in the background (Unix only)
+  --pid-file <path>  Write the process ID to <path>, removed on shutdown (Unix only)
+  -h, --help         Show this help
+
+Server settings are read from environment variables (see README).";
+
+/// Command line options
+#[derive(Debug, Default)]
+struct CliArgs {
+    daemon: bool,
+    pid_file: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<CliArgs, String> {
+    let mut cli = CliArgs::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--daemon" => cli.daemon = true,
+            "--pid-file" => {
+                let path = args.next().ok_or("--pid-file requires a path")?;
+                cli.pid_file = Some(PathBuf::from(path));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other => match other.strip_prefix("--pid-file=") {
+                Some(path) => cli.pid_file = Some(PathBuf::from(path)),
+                None => return Err(format!("Unknown argument '{}'", other)),
+            },
+        }
+    }
+    Ok(cli)
+}
+
+fn main() {
     dotenv::dotenv().ok();
 
+    let cli = match parse_args() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let config_path =
+        std::env::var("CICD_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+    // Load the config before detaching so errors still reach the terminal.
+    // Setting CICD_CONFIG_PROJECT_NAME defines a single project from environment variables.
+    let config_from_env = env_config::is_enabled();
+    let loaded = if config_from_env {
+        env_config::load()
+    } else {
+        load_config(&config_path)
+    };
+    let config: CICDConfig = match loaded {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(unix)]
+    if cli.daemon
+        && let Err(e) = daemon::daemonize()
+    {
+        eprintln!("Failed to daemonize: {}", e);
+        std::process::exit(1);
+    }
+    #[cfg(not(unix))]
+    if cli.daemon || cli.pid_file.is_some() {
+        eprintln!("--daemon and --pid-file are only supported on Unix");
+        std::process::exit(2);
+    }
+
+    // The runtime is started only after forking
+    git::init_server_timeout();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start tokio runtime")
+        .block_on(run(cli, config, config_path, config_from_env));
+}
+
+async fn run(cli: CliArgs, config: CICDConfig, config_path: String, config_from_env: bool) {
     // Initialize tracing with environment filter
     // Use RUST_LOG env var to control log levels (e.g., RUST_LOG=debug or RUST_LOG=simple_git_cicd=trace)
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
@@ -48,21 +163,54 @@ async fn main() {
         }
     });
 
-    tracing_subscriber::fmt().with_env_filter(filter).init();
+    // Use LOG_OUTPUT env var to pick the log target (stdout, journald, syslog)
+    let log_output = match std::env::var("LOG_OUTPUT") {
+        Ok(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("{}; logging to stdout", e);
+            LogOutput::Stdout
+        }),
+        Err(_) => LogOutput::Stdout,
+    };
+    init_tracing(filter, log_output);
+
+    #[cfg(unix)]
+    if let Some(path) = &cli.pid_file {
+        match daemon::PidFile::create(path) {
+            Ok(pid_file) => {
+                info!("Wrote PID file {:?}", pid_file.path());
+                // Remove the PID file when asked to stop
+                tokio::spawn(async move {
+                    daemon::wait_for_shutdown_signal().await;
+                    info!("Shutting down");
+                    drop(pid_file);
+                    std::process::exit(0);
+                });
+            }
+            Err(e) => {
+                error!("Failed to write PID file: {}", e);
+                eprintln!("Failed to write PID file: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = &cli;
 
     let bind_address =
         std::env::var("BIND_ADDRESS").unwrap_or_else(|_| DEFAULT_BIND_ADDRESS.to_string());
-    let config_path =
-        std::env::var("CICD_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
     let db_path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| DEFAULT_DB_PATH.to_string());
-
-    let config: CICDConfig = match load_config(&config_path) {
-        Ok(cfg) => cfg,
-        Err(e) => {
-            eprintln!("Configuration error: {}", e);
-            std::process::exit(1);
-        }
+    // Limit request bodies (in bytes): [server] max_body_size, then MAX_BODY_SIZE env var
+    let max_body_size = match std::env::var("MAX_BODY_SIZE") {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Invalid MAX_BODY_SIZE '{}'; using {} bytes",
+                value, DEFAULT_MAX_BODY_SIZE
+            );
+            DEFAULT_MAX_BODY_SIZE
+        }),
+        Err(_) => DEFAULT_MAX_BODY_SIZE,
     };
+    let max_body_size = config.server.max_body_size.unwrap_or(max_body_size);
 
     let pool = match init_db(&db_path).await {
         Ok(p) => p,
@@ -72,46 +220,204 @@ async fn main() {
         }
     };
 
+    let base_path = config.server.base_path();
+    let server_config = config.server.clone();
+
     let job_store = SqlJobStore::new(pool);
+    match job_store.recover_interrupted_jobs().await {
+        Ok(0) => {}
+        Ok(n) => warn!(
+            "Marked {} job(s) interrupted by the last shutdown as failed",
+            n
+        ),
+        Err(e) => error!("Failed to recover interrupted jobs: {}", e),
+    }
     let start_time = Instant::now();
     let started_at = Utc::now();
-    let (job_events, _) = broadcast::channel(100);
-    let (log_chunks, _) = broadcast::channel(1000); // Higher capacity for streaming logs
+    // Per-subscriber queue sizes for SSE streams
+    let job_events = EventHub::new(100);
+    let log_chunks = EventHub::new(1000); // Higher capacity for streaming logs
     let rate_limiter = Arc::new(tokio::sync::Mutex::new(RateLimiter::new()));
 
     let state = Arc::new(AppState {
-        job_execution_lock: Mutex::new(()),
+        dispatcher: Dispatcher::new(
+            server_config
+                .max_concurrent_jobs
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_JOBS),
+        ),
         job_store,
-        config: RwLock::new(config),
+        config: ArcSwap::from_pointee(config),
         config_path: PathBuf::from(config_path.clone()),
+        config_from_env,
+        base_path: base_path.clone(),
         start_time,
         started_at,
         rate_limiter,
         job_events,
         log_chunks,
+        server_logs: GlobalLogManager::get(),
+        http_client: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build HTTP client"),
+        github_tokens: TokenCache::default(),
     });
 
+    // Start the scheduler; it also picks up jobs left queued by a previous run
+    dispatcher::spawn_scheduler(state.clone());
+    retention::spawn_pruner(state.clone());
+    workspace::spawn_cleaner(state.clone());
+    rate_limit::restore_persisted(&state).await;
+    rate_limit::spawn_evictor(state.clone());
+    // Turn webhooks a crash interrupted into jobs
+    process_pending_intake(&state).await;
+    schedule::spawn_scheduler(state.clone());
+
     let app = Router::new()
-        // Webhook endpoint (kept at root for GitHub compatibility)
-        .route("/webhook", routing::post(handle_webhook))
         // API endpoints
         .route("/api/status", routing::get(status))
         .route("/api/reload", routing::post(reload_config_endpoint))
         .route("/api/jobs", routing::get(get_jobs))
         .route("/api/jobs/{id}", routing::get(get_job))
+        .route("/api/jobs/{id}/output", routing::get(get_job_output))
+        .route("/api/jobs/{id}/payload", routing::get(get_job_payload))
+        .route(
+            "/api/jobs/{id}/pin",
+            routing::post(pin_job).delete(unpin_job),
+        )
+        .route("/api/jobs/{id}/resume", routing::post(resume_job))
+        .route("/api/jobs/{id}/retry", routing::post(retry_job))
+        .route("/api/jobs/{id}/cancel", routing::post(cancel_job))
+        .route("/api/jobs/{id}/compare/{other}", routing::get(compare_jobs))
         .route("/api/jobs/{id}/logs", routing::get(get_job_logs))
+        .route("/api/jobs/{id}/artifacts", routing::get(get_job_artifacts))
+        .route(
+            "/api/jobs/{id}/artifacts/{*name}",
+            routing::get(download_job_artifact),
+        )
+        .route(
+            "/api/jobs/{id}/workspace",
+            routing::delete(delete_job_workspace),
+        )
+        .route(
+            "/api/jobs/{id}/logs/download",
+            routing::get(download_job_logs),
+        )
+        .route(
+            "/api/jobs/{id}/logs/{log_id}/output",
+            routing::get(get_job_log_output),
+        )
+        .route("/api/deliveries/{id}/redeliver", routing::post(redeliver))
         .route("/api/projects", routing::get(get_projects))
+        .route(
+            "/api/projects/{name}/flaky",
+            routing::get(get_project_flaky),
+        )
+        .route(
+            "/api/projects/{name}/maintenance",
+            routing::post(enable_maintenance).delete(disable_maintenance),
+        )
+        .route(
+            "/api/projects/{name}/caches",
+            routing::get(get_project_caches).delete(purge_project_caches),
+        )
+        .route(
+            "/api/projects/{name}/caches/{cache}",
+            routing::delete(purge_project_cache),
+        )
+        .route(
+            "/api/projects/{name}/releases",
+            routing::get(get_project_releases),
+        )
+        .route(
+            "/api/projects/{name}/rollback",
+            routing::post(rollback_project),
+        )
+        .route(
+            "/api/projects/{name}/simulate",
+            routing::post(simulate_push),
+        )
         .route("/api/stats", routing::get(get_stats))
         .route("/api/config/current", routing::get(get_config))
+        .route("/api/server/logs", routing::get(get_server_logs))
         // SSE streams
         .route("/api/stream/jobs", routing::get(stream_jobs))
         .route("/api/stream/logs", routing::get(stream_logs))
-        .with_state(state)
         // UI fallback - serves embedded static files
-        .fallback(serve_ui);
+        .fallback(serve_ui)
+        // Webhook endpoints ([server] webhook_paths, default /webhook, and
+        // per-project webhook_path); resolved per request so reloads apply
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            route_webhooks,
+        ))
+        // Optional basic auth for UI and API; webhooks rely on signatures
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_basic_auth,
+        ))
+        .with_state(state);
+
+    // Mount everything under the configured base path (reverse proxy sub-path)
+    let app = if base_path.is_empty() {
+        app
+    } else {
+        Router::new().nest(&base_path, app)
+    };
+
+    let app = app
+        // Reject oversized bodies with 413 before buffering them. The
+        // Content-Length is checked up front and streamed bodies are cut off
+        // at the limit; axum's own 2 MB default is replaced by this one.
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_size))
+        // Requests that don't produce a response in time get 408. This only
+        // covers the handler: streamed bodies (SSE, log output) may run longer.
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            server_config.request_timeout(),
+        ));
+
+    // Use the socket passed by systemd socket activation, if any
+    let listener = match systemd::activated_listener() {
+        Ok(Some(listener)) => {
+            info!("Using listening socket passed by systemd");
+            listener
+                .set_nonblocking(true)
+                .and_then(|_| tokio::net::TcpListener::from_std(listener))
+                .unwrap()
+        }
+        Ok(None) => tokio::net::TcpListener::bind(&bind_address).await.unwrap(),
+        Err(e) => {
+            eprintln!("Failed to use socket passed by systemd: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match listener.local_addr() {
+        Ok(addr) => info!("Listening on {}", addr),
+        Err(_) => info!("Listening on {}", bind_address),
+    }
+    if config_from_env {
+        info!("Using config from CICD_* environment variables");
+    } else {
+        info!("Using config at {:?}", config_path);
+    }
+    if !base_path.is_empty() {
+        info!("Serving under base path {}", base_path);
+    }
+    info!("Maximum request body size: {} bytes", max_body_size);
+    let listener_settings = ListenerSettings {
+        header_read_timeout: server_config.header_read_timeout(),
+        keep_alive: server_config.keep_alive(),
+        max_connections: server_config.max_connections,
+    };
 
-    info!("Listening on {}", bind_address);
-    info!("Using config at {:?}", config_path);
-    let listener = tokio::net::TcpListener::bind(bind_address).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Migrations ran and the listener is bound: tell systemd we're ready (Type=notify)
+    if let Err(e) = systemd::notify("READY=1") {
+        warn!("Failed to notify systemd: {}", e);
+    }
+    // Trim trailing slashes before routing so e.g. "/cicd/" matches the nested base path
+    let app = NormalizePathLayer::trim_trailing_slash().layer(app);
+    http::serve(listener, app, listener_settings).await;
 }
@@ -0,0 +1,75 @@
+//! Mirroring the deployed branch to additional remotes
+//!
+//! With `[[project.mirrors]]`, a job whose main script succeeded pushes the
+//! branch (or release tag) it deployed to every mirror, e.g. to keep a backup
+//! copy of the repository on another host in sync. Each mirror has its own
+//! credentials.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::error::{CicdError, Result};
+use crate::git::{self, Cancel, Credentials};
+use crate::webhook::WebhookData;
+
+/// A `[[project.mirrors]]` entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MirrorConfig {
+    /// Remote URL, e.g. `git@backup.example.com:me/app.git`
+    pub url: String,
+    /// Overrides the user name in the URL
+    pub username: Option<String>,
+    /// Environment variable holding the password or access token (HTTPS)
+    pub password_env: Option<String>,
+    /// Private key file (SSH); default: ssh-agent and `~/.ssh` keys
+    pub ssh_key: Option<String>,
+    /// Force-push so the mirror follows rewritten history (default: true)
+    pub force: Option<bool>,
+}
+
+impl MirrorConfig {
+    pub fn force(&self) -> bool {
+        self.force.unwrap_or(true)
+    }
+
+    /// Refspec pushing what a job deployed: the fetched state of its branch
+    /// to the same branch on the mirror, or its release tag
+    pub fn refspec(&self, webhook_data: &WebhookData) -> String {
+        let force = if self.force() { "+" } else { "" };
+        match &webhook_data.tag {
+            Some(tag) => format!("{}refs/tags/{1}:refs/tags/{1}", force, tag),
+            None => format!(
+                "{}refs/remotes/origin/{1}:refs/heads/{1}",
+                force, webhook_data.branch
+            ),
+        }
+    }
+
+    /// Resolve the configured credentials; fails if `password_env` is unset
+    pub fn credentials(&self) -> Result<Credentials> {
+        let password = match &self.password_env {
+            Some(var) => Some(std::env::var(var).map_err(|_| {
+                CicdError::ConfigError(format!(
+                    "Mirror {}: environment variable '{}' is not set",
+                    self.url, var
+                ))
+            })?),
+            None => None,
+        };
+        Ok(Credentials {
+            username: self.username.clone(),
+            password,
+            ssh_key: self.ssh_key.as_ref().map(PathBuf::from),
+        })
+    }
+
+    /// Push `refspec` from the repository at `repo_path` to this mirror
+    pub fn push(&self, repo_path: &str, refspec: &str, cancel: &Cancel) -> Result<String> {
+        git::push_url(repo_path, &self.url, refspec, &self.credentials()?, cancel)
+    }
+
+    /// Check that the mirror accepts pushes with the configured credentials
+    pub fn check(&self, repo_path: &str) -> Result<()> {
+        git::check_push_access(repo_path, &self.url, &self.credentials()?)
+    }
+}
@@ -0,0 +1,293 @@
+//! Bounded buffering of script output while a job is running
+//!
+//! Each job gets an in-memory budget shared by all of its steps. Output beyond
+//! the budget is appended to a spill file on disk instead of being held in
+//! memory, and the step output is marked as truncated. Steps can also be
+//! capped on their own (`max_step_output_bytes`): only the start and the end
+//! of their output is kept, and what's cut from the middle goes to the spill
+//! file. ANSI escape sequences can be stripped from the output before it is
+//! stored or streamed (`strip_ansi`).
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::str::Chars;
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, warn};
+
+/// Maximum script output held in memory per running job (8MB)
+pub const MAX_IN_MEMORY_OUTPUT: usize = 8 * 1024 * 1024;
+
+/// A partial line is streamed once this much of it is buffered
+const MAX_PENDING_LINE: usize = 8 * 1024;
+
+/// Directory for spilled output files
+pub fn spill_dir() -> PathBuf {
+    std::env::temp_dir().join("simple_git_cicd")
+}
+
+/// In-memory output budget for one job
+pub struct OutputBudget {
+    job_id: String,
+    capacity: usize,
+    remaining: usize,
+    spill: Option<File>,
+    spilled_bytes: u64,
+}
+
+impl OutputBudget {
+    pub fn new(job_id: &str, capacity: usize) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            capacity,
+            remaining: capacity,
+            spill: None,
+            spilled_bytes: 0,
+        }
+    }
+
+    /// Path of this job's spill file
+    pub fn spill_path(&self) -> PathBuf {
+        spill_dir().join(format!("job-{}.log", self.job_id))
+    }
+
+    /// Returns true once any output has been spilled to disk
+    pub fn truncated(&self) -> bool {
+        self.spilled_bytes > 0
+    }
+
+    /// Append `data` to `buffer` while the budget lasts; the rest goes to the spill file.
+    /// Returns the number of bytes spilled.
+    pub async fn capture(&mut self, buffer: &mut Vec<u8>, data: &[u8]) -> usize {
+        let keep = data.len().min(self.remaining);
+        buffer.extend_from_slice(&data[..keep]);
+        self.remaining -= keep;
+
+        let overflow = &data[keep..];
+        if !overflow.is_empty() {
+            self.spill(overflow).await;
+        }
+        overflow.len()
+    }
+
+    async fn spill(&mut self, data: &[u8]) {
+        if self.spill.is_none() {
+            let path = self.spill_path();
+            let opened = match fs::create_dir_all(spill_dir()).await {
+                Ok(()) => File::create(&path).await,
+                Err(e) => Err(e),
+            };
+            match opened {
+                Ok(file) => {
+                    warn!(
+                        "Job {} exceeded its output limits; spilling to {:?}",
+                        self.job_id, path
+                    );
+                    self.spill = Some(file);
+                }
+                Err(e) => error!("Failed to create spill file {:?}: {}", path, e),
+            }
+        }
+        // Bytes are counted even if the spill file is unavailable, so the
+        // output is still marked as truncated
+        self.spilled_bytes += data.len() as u64;
+        if let Some(file) = self.spill.as_mut()
+            && let Err(e) = file.write_all(data).await
+        {
+            error!("Failed to write spill file for job {}: {}", self.job_id, e);
+            self.spill = None;
+        }
+    }
+
+    /// Write output cut from a capped step to the spill file, so it isn't lost
+    pub async fn set_aside(&mut self, data: &[u8]) {
+        if !data.is_empty() {
+            self.spill(data).await;
+        }
+    }
+
+    /// Marker put where output was cut from a capped step
+    pub fn cut_note(&self, cut: u64) -> String {
+        format!(
+            "\n... ({} bytes cut, written to {}) ...\n",
+            cut,
+            self.spill_path().display()
+        )
+    }
+
+    /// Note appended to a step's output when part of it was spilled
+    pub fn truncation_note(&self, spilled: usize) -> String {
+        format!(
+            "\n... (output truncated: {} bytes over the {} byte in-memory limit were written to {})\n",
+            spilled,
+            self.capacity,
+            self.spill_path().display()
+        )
+    }
+
+    /// Flush the spill file, if any
+    pub async fn finish(&mut self) {
+        if let Some(file) = self.spill.as_mut() {
+            let _ = file.flush().await;
+        }
+    }
+}
+
+/// Splits output read from a pipe into whole lines, so it can be streamed
+/// as it arrives without cutting lines (or UTF-8 characters) apart
+#[derive(Default)]
+pub struct LineBuffer(Vec<u8>);
+
+impl LineBuffer {
+    /// Add `data`; returns the lines it completed, or the start of a line
+    /// too long to hold back
+    pub fn push(&mut self, data: &[u8]) -> Option<String> {
+        self.0.extend_from_slice(data);
+        let end = match self.0.iter().rposition(|&b| b == b'\n') {
+            Some(newline) => newline + 1,
+            None if self.0.len() >= MAX_PENDING_LINE => match std::str::from_utf8(&self.0) {
+                Ok(_) => self.0.len(),
+                Err(e) if e.valid_up_to() > 0 => e.valid_up_to(),
+                Err(_) => self.0.len(),
+            },
+            None => return None,
+        };
+        let rest = self.0.split_off(end);
+        let lines = std::mem::replace(&mut self.0, rest);
+        Some(String::from_utf8_lossy(&lines).into_owned())
+    }
+
+    /// The unfinished last line, once the pipe is closed
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.0);
+        (!rest.is_empty()).then(|| String::from_utf8_lossy(&rest).into_owned())
+    }
+}
+
+/// One stream of a step's output, capped at `limit` bytes: the first half of
+/// the limit and the most recent half are kept, what's in between is cut
+pub struct CappedOutput {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    limit: Option<usize>,
+    cut: u64,
+}
+
+impl CappedOutput {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            limit,
+            cut: 0,
+        }
+    }
+
+    /// Add `data`; returns the bytes cut from the middle to make room
+    pub fn push(&mut self, data: &[u8]) -> Vec<u8> {
+        let Some(limit) = self.limit else {
+            self.head.extend_from_slice(data);
+            return Vec::new();
+        };
+        let head_room = (limit - limit / 2).saturating_sub(self.head.len());
+        let (head, rest) = data.split_at(head_room.min(data.len()));
+        self.head.extend_from_slice(head);
+        self.tail.extend(rest);
+        let excess = self.tail.len().saturating_sub(limit / 2);
+        let cut: Vec<u8> = self.tail.drain(..excess).collect();
+        self.cut += cut.len() as u64;
+        cut
+    }
+
+    /// Returns true once output was cut
+    pub fn truncated(&self) -> bool {
+        self.cut > 0
+    }
+
+    /// The kept output as text, with `note(bytes cut)` where it was cut
+    pub fn text(&self, note: impl FnOnce(u64) -> String) -> String {
+        let mut bytes = self.head.clone();
+        if self.truncated() {
+            bytes.extend_from_slice(note(self.cut).as_bytes());
+        }
+        bytes.extend(self.tail.iter());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+/// What happens to ANSI escape sequences (colors, cursor movement) in script
+/// output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AnsiOutput {
+    /// Stored and streamed as written
+    #[default]
+    Keep,
+    /// Stripped before output is stored or streamed
+    Strip,
+    /// Stripped, but each step's output is also stored as written, for the
+    /// log download
+    StripKeepRaw,
+}
+
+impl AnsiOutput {
+    /// `text` as it is stored and streamed
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            AnsiOutput::Keep => Cow::Borrowed(text),
+            AnsiOutput::Strip | AnsiOutput::StripKeepRaw => strip_ansi(text),
+        }
+    }
+}
+
+/// Remove ANSI escape sequences from `text`: CSI sequences (colors, cursor
+/// movement), OSC sequences (window titles, hyperlinks) and other escapes
+pub fn strip_ansi(text: &str) -> Cow<'_, str> {
+    if !text.contains(['\x1b', '\u{9b}']) {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                Some('[') => skip_csi(&mut chars),
+                Some(']') => skip_osc(&mut chars),
+                // Intermediate bytes, then the final one (e.g. `ESC ( B`)
+                Some('\x20'..='\x2f') => {
+                    while chars.next_if(|c| matches!(c, '\x20'..='\x2f')).is_some() {}
+                    chars.next();
+                }
+                // Two-character escapes (e.g. `ESC =`)
+                _ => {}
+            },
+            '\u{9b}' => skip_csi(&mut chars),
+            c => stripped.push(c),
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+/// Skip the parameter and intermediate bytes of a CSI sequence, then its
+/// final byte
+fn skip_csi(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| matches!(c, '\x20'..='\x3f')).is_some() {}
+    chars.next_if(|c| matches!(c, '\x40'..='\x7e'));
+}
+
+/// Skip an OSC sequence up to its terminator (BEL or `ESC \`). An
+/// unterminated one ends at the end of the line.
+fn skip_osc(chars: &mut Peekable<Chars<'_>>) {
+    while let Some(c) = chars.next_if(|&c| c != '\n') {
+        match c {
+            '\x07' => return,
+            '\x1b' => {
+                chars.next_if_eq(&'\\');
+                return;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Main-script output diff against the previous run of the same branch
+//!
+//! Computed when a job finishes and stored with it, so a failed job shows at
+//! a glance what its output did differently from the last run.
+
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::error;
+
+use crate::SharedState;
+
+/// Give up looking for a minimal diff after this long and use a coarser one
+const DIFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// At most this many new error lines are kept
+const MAX_ERROR_LINES: usize = 20;
+
+/// Longer error lines are cut to this many characters
+const MAX_ERROR_LINE_CHARS: usize = 300;
+
+/// Case-insensitive markers of a line worth pointing out
+const ERROR_MARKERS: &[&str] = &[
+    "error",
+    "fail",
+    "fatal",
+    "panic",
+    "exception",
+    "traceback",
+    "denied",
+];
+
+/// Summary of how a job's main-script output differs from the previous run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputDiff {
+    /// The previous completed run on the same project and branch
+    pub previous_job_id: String,
+    pub added_lines: usize,
+    pub removed_lines: usize,
+    /// Added lines that look like errors and didn't appear in the previous output
+    pub new_error_lines: Vec<String>,
+}
+
+/// Compare two outputs line by line
+pub fn summarize(previous_job_id: &str, old: &str, new: &str) -> OutputDiff {
+    let mut added_lines = 0;
+    let mut removed_lines = 0;
+    let mut added = Vec::new();
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(old, new);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => {
+                added_lines += 1;
+                added.push(change.value().trim_end());
+            }
+            ChangeTag::Delete => removed_lines += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+
+    // Lines that merely moved aren't new
+    let old_lines: HashSet<&str> = old.lines().map(str::trim_end).collect();
+    let mut seen = HashSet::new();
+    let new_error_lines = added
+        .into_iter()
+        .filter(|line| !old_lines.contains(line) && is_error_line(line) && seen.insert(*line))
+        .take(MAX_ERROR_LINES)
+        .map(|line| line.chars().take(MAX_ERROR_LINE_CHARS).collect())
+        .collect();
+
+    OutputDiff {
+        previous_job_id: previous_job_id.to_string(),
+        added_lines,
+        removed_lines,
+        new_error_lines,
+    }
+}
+
+fn is_error_line(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    ERROR_MARKERS.iter().any(|marker| lower.contains(marker))
+}
+
+/// Compute and store the output diff of a finished job against the previous
+/// run. Jobs without a previous run or without a main-script step are skipped.
+pub async fn record(state: &SharedState, job_id: &str) {
+    let store = &state.job_store;
+    let previous = match store.get_previous_run(job_id).await {
+        Ok(Some(previous)) => previous,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to find previous run of job {}: {}", job_id, e);
+            return;
+        }
+    };
+    let (Some(new), Some(old)) = (
+        main_script_output(state, job_id).await,
+        main_script_output(state, &previous.id).await,
+    ) else {
+        return;
+    };
+
+    // Diffing large outputs is CPU-bound; keep it off the async workers
+    let previous_id = previous.id.clone();
+    let Ok(diff) = tokio::task::spawn_blocking(move || summarize(&previous_id, &old, &new)).await
+    else {
+        return;
+    };
+    if let Err(e) = store.set_output_diff(job_id, &diff).await {
+        error!("Failed to store output diff for job {}: {}", job_id, e);
+    }
+}
+
+/// Output of a job's (last) main-script step
+async fn main_script_output(state: &SharedState, job_id: &str) -> Option<String> {
+    state
+        .job_store
+        .get_job_logs(job_id)
+        .await
+        .ok()?
+        .into_iter()
+        .rev()
+        .find(|log| log.log_type == "main_script")
+        .map(|log| log.output.unwrap_or_default())
+}
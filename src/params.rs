@@ -0,0 +1,87 @@
+//! Parameters of manually triggered jobs
+//!
+//! `POST /api/projects/{name}/simulate` accepts key/value `params`, passed
+//! to scripts as `CICD_PARAM_<NAME>`. A project may declare its `inputs`,
+//! like GitHub's `workflow_dispatch`; params are then checked against them
+//! and defaults filled in.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A declared parameter (`[[project.inputs]]`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InputConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Reject triggers without this parameter (unless it has a default)
+    pub required: Option<bool>,
+    /// Value used when the parameter isn't given
+    pub default: Option<String>,
+    /// Allowed values (default: any)
+    pub options: Option<Vec<String>>,
+}
+
+impl InputConfig {
+    pub fn required(&self) -> bool {
+        self.required.unwrap_or(false)
+    }
+}
+
+/// Check trigger params against the declared inputs, if any, and fill in
+/// defaults. Without declared inputs any params are accepted.
+pub fn resolve(
+    inputs: Option<&[InputConfig]>,
+    mut params: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    if let Some(name) = params.keys().find(|name| !is_valid_name(name)) {
+        return Err(format!(
+            "Invalid parameter name '{}': use letters, digits, '_' and '-'",
+            name
+        ));
+    }
+    let Some(inputs) = inputs else {
+        return Ok(params);
+    };
+
+    if let Some(name) = params
+        .keys()
+        .find(|name| !inputs.iter().any(|input| &input.name == *name))
+    {
+        return Err(format!("Unknown parameter '{}'", name));
+    }
+    for input in inputs {
+        if !params.contains_key(&input.name) {
+            match &input.default {
+                Some(default) => {
+                    params.insert(input.name.clone(), default.clone());
+                }
+                None if input.required() => {
+                    return Err(format!("Missing required parameter '{}'", input.name));
+                }
+                None => continue,
+            }
+        }
+        if let Some(options) = &input.options
+            && !options.contains(&params[&input.name])
+        {
+            return Err(format!(
+                "Parameter '{}' must be one of: {}",
+                input.name,
+                options.join(", ")
+            ));
+        }
+    }
+    Ok(params)
+}
+
+/// Name of the environment variable passing a parameter to scripts
+pub fn env_name(name: &str) -> String {
+    format!("CICD_PARAM_{}", name.to_uppercase().replace('-', "_"))
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
@@ -0,0 +1,70 @@
+//! Changed-path filters for monorepo sub-projects
+//!
+//! Several `[[project]]` entries may deploy parts of the same repository,
+//! each with its own `match_paths`. A push then only triggers the projects
+//! whose globs match at least one file the push added, modified or removed.
+//! The same globs select the branches (`branches`, `branch_scripts`) and
+//! tags (`tags`) that trigger a project.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Compiled `match_paths` globs, written as a list of patterns in the config.
+/// `*` stays within one path segment, `**` crosses directories, e.g.
+/// `services/api/**` or `*.md`.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    patterns: Vec<String>,
+    set: GlobSet,
+}
+
+impl PathFilter {
+    pub fn new(patterns: Vec<String>) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &patterns {
+            // Paths in push payloads are relative to the repository root
+            let pattern = pattern.trim_start_matches('/');
+            builder.add(GlobBuilder::new(pattern).literal_separator(true).build()?);
+        }
+        Ok(Self {
+            set: builder.build()?,
+            patterns,
+        })
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// Returns true if `name` matches one of the globs
+    pub fn matches(&self, name: &str) -> bool {
+        self.set.is_match(name)
+    }
+
+    /// Returns true if any of `files` matches one of the globs
+    pub fn matches_any(&self, files: &[String]) -> bool {
+        files.iter().any(|file| self.set.is_match(file))
+    }
+}
+
+/// Returns true if `name` matches the glob `pattern` (false for invalid
+/// patterns), with the same syntax as `PathFilter`
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .is_ok_and(|glob| glob.compile_matcher().is_match(name))
+}
+
+impl Serialize for PathFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.patterns.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let patterns = Vec::<String>::deserialize(deserializer)?;
+        Self::new(patterns).map_err(|e| serde::de::Error::custom(format!("invalid glob: {}", e)))
+    }
+}
@@ -0,0 +1,69 @@
+//! Scheduling priority of scripts (`nice`, `io_priority`)
+//!
+//! Builds running on the machine that serves the deployed services
+//! shouldn't starve them of CPU or disk time. The priority is set in the
+//! script's process before it starts, and is inherited by everything the
+//! script runs.
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+/// Disk I/O priority of scripts (Linux only)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IoPriority {
+    /// Disk time only when no other process wants it (`ionice -c 3`)
+    Idle,
+    /// The lowest best-effort level (`ionice -c 2 -n 7`)
+    Low,
+}
+
+impl IoPriority {
+    /// The value for ioprio_set(2): class and level
+    #[cfg(target_os = "linux")]
+    fn ioprio(self) -> libc::c_int {
+        const CLASS_SHIFT: libc::c_int = 13;
+        match self {
+            IoPriority::Idle => 3 << CLASS_SHIFT,
+            IoPriority::Low => (2 << CLASS_SHIFT) | 7,
+        }
+    }
+}
+
+/// CPU and disk priority a script starts with
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Priority {
+    /// Niceness, from -20 (highest priority) to 19 (lowest)
+    pub nice: Option<i32>,
+    pub io: Option<IoPriority>,
+}
+
+impl Priority {
+    /// Set the priority in the process `cmd` starts. Must come before
+    /// switching users, as raising the priority needs root.
+    pub fn apply(self, cmd: &mut Command) {
+        if self.nice.is_none() && self.io.is_none() {
+            return;
+        }
+        #[cfg(unix)]
+        // SAFETY: the closure only makes async-signal-safe system calls
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(nice) = self.nice
+                    && libc::setpriority(libc::PRIO_PROCESS, 0, nice) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                #[cfg(target_os = "linux")]
+                if let Some(io) = self.io {
+                    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+                    if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, io.ioprio()) != 0
+                    {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Git hosting providers that send push webhooks
+//!
+//! Providers are recognized by their event header, or for Azure DevOps
+//! service hooks (which have none) by the payload's `eventType`. Their push
+//! payloads are converted to a common `PushEvent` (see `webhook`), so
+//! matching and job creation are the same for all of them. Requests from
+//! none of them are read as generic pushes, using the JSON pointers each
+//! generic project declares.
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+/// Where a project's webhooks come from (`provider` in the project config)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Provider {
+    /// `X-GitHub-Event`, signed with `X-Hub-Signature-256` (default)
+    #[default]
+    Github,
+    /// `X-Gitlab-Event`, authenticated with `X-Gitlab-Token`
+    Gitlab,
+    /// Azure DevOps service hooks, authenticated with HTTP Basic auth
+    AzureDevops,
+    /// Any JSON payload, read with the project's `generic` field mapping and
+    /// signed with `X-Signature-256`
+    Generic,
+}
+
+/// `generic` settings of a project: JSON pointers (RFC 6901, e.g.
+/// `/data/branch`) to the push fields in the payload. Only the branch is
+/// required; the others are left empty when not set or not found.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GenericMapping {
+    /// Branch, or ref like `refs/heads/main` or `refs/tags/v1.0.0`
+    /// (default: `/ref`)
+    pub branch_path: Option<String>,
+    /// Repository name (default: any payload triggers the project)
+    pub repository_path: Option<String>,
+    pub commit_sha_path: Option<String>,
+    pub commit_message_path: Option<String>,
+    pub commit_author_name_path: Option<String>,
+    pub commit_author_email_path: Option<String>,
+    pub pusher_name_path: Option<String>,
+    pub repository_url_path: Option<String>,
+    /// Array of changed file paths
+    pub changed_files_path: Option<String>,
+}
+
+impl GenericMapping {
+    pub fn branch_path(&self) -> &str {
+        self.branch_path.as_deref().unwrap_or("/ref")
+    }
+}
+
+/// Kinds of webhook events that can start jobs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Push,
+    /// GitHub `pull_request`
+    PullRequest,
+    /// GitHub `release`
+    Release,
+}
+
+/// The fields identifying an Azure DevOps service hook event
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServiceHookEvent {
+    publisher_id: String,
+    event_type: String,
+}
+
+impl Provider {
+    /// The provider that sent a webhook and the event it reports
+    pub fn detect(headers: &HeaderMap, body: &[u8]) -> Option<(Self, String)> {
+        let from_header = [
+            (Self::Github, "X-GitHub-Event"),
+            (Self::Gitlab, "X-Gitlab-Event"),
+        ]
+        .into_iter()
+        .find_map(|(provider, header)| {
+            let event = headers.get(header)?.to_str().ok()?;
+            Some((provider, event.to_string()))
+        });
+        from_header.or_else(|| {
+            let event: ServiceHookEvent = serde_json::from_slice(body).ok()?;
+            (event.publisher_id == "tfs").then_some((Self::AzureDevops, event.event_type))
+        })
+    }
+
+    /// What kind of event `event` is, if it can start jobs
+    pub fn event_kind(&self, event: &str) -> Option<EventKind> {
+        match (self, event) {
+            (Self::Github, "push")
+            | (Self::Gitlab, "Push Hook" | "Tag Push Hook")
+            | (Self::AzureDevops, "git.push")
+            | (Self::Generic, _) => Some(EventKind::Push),
+            (Self::Github, "pull_request") => Some(EventKind::PullRequest),
+            (Self::Github, "release") => Some(EventKind::Release),
+            _ => None,
+        }
+    }
+
+    /// Header carrying the webhook's signature, secret token or credentials
+    pub fn secret_header(&self) -> &'static str {
+        match self {
+            Self::Github => "X-Hub-Signature-256",
+            Self::Gitlab => "X-Gitlab-Token",
+            Self::AzureDevops => "Authorization",
+            Self::Generic => "X-Signature-256",
+        }
+    }
+
+    /// Header carrying the legacy HMAC SHA-1 signature, for providers that
+    /// may send one (`allow_sha1_signature`)
+    pub fn sha1_signature_header(&self) -> Option<&'static str> {
+        match self {
+            Self::Github => Some("X-Hub-Signature"),
+            Self::Gitlab | Self::AzureDevops | Self::Generic => None,
+        }
+    }
+}
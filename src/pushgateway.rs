@@ -0,0 +1,198 @@
+//! Pushing job metrics to a Prometheus Pushgateway
+//!
+//! With `[server.pushgateway]`, the result of every finished (non-dry-run)
+//! job is pushed to the gateway, grouped by project and branch, so job
+//! durations, outcomes and step timings can be graphed without Prometheus
+//! scraping this server. Each push replaces the group's previous metrics.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::SharedState;
+use crate::db::store::JobLog;
+use crate::job::{Job, JobStatus};
+
+/// Time the gateway has to accept a push
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `[server.pushgateway]` settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PushgatewayConfig {
+    /// Base URL of the gateway, e.g. `http://pushgateway:9091`
+    pub url: String,
+    /// Value of the `job` grouping label (default: `simple_git_cicd`)
+    pub job: Option<String>,
+    /// User name for HTTP Basic auth
+    pub username: Option<String>,
+    /// Environment variable holding the Basic auth password
+    pub password_env: Option<String>,
+}
+
+impl PushgatewayConfig {
+    pub fn job(&self) -> &str {
+        self.job.as_deref().unwrap_or("simple_git_cicd")
+    }
+
+    /// URL of the metrics group of a project's branch. Label values are
+    /// base64-encoded, since branch names may contain slashes.
+    fn group_url(&self, project: &str, branch: &str) -> String {
+        format!(
+            "{}/metrics/job@base64/{}/project@base64/{}/branch@base64/{}",
+            self.url.trim_end_matches('/'),
+            encode_label(self.job()),
+            encode_label(project),
+            encode_label(branch)
+        )
+    }
+}
+
+/// Encode a grouping label value for a Pushgateway URL path
+fn encode_label(value: &str) -> String {
+    // An empty value is written as `=` (a lone padding character)
+    if value.is_empty() {
+        "=".to_string()
+    } else {
+        URL_SAFE_NO_PAD.encode(value)
+    }
+}
+
+/// Push the metrics of a finished job, if a Pushgateway is configured.
+/// Runs in the background; failures are logged and otherwise ignored.
+pub fn push_job_metrics(state: &SharedState, job_id: &str) {
+    let Some(config) = state.config.load().server.pushgateway.clone() else {
+        return;
+    };
+    let state = state.clone();
+    let job_id = job_id.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = push(&state, &config, &job_id).await {
+            warn!(
+                "Failed to push metrics of job {} to {}: {}",
+                job_id, config.url, e
+            );
+        }
+    });
+}
+
+async fn push(state: &SharedState, config: &PushgatewayConfig, job_id: &str) -> Result<(), String> {
+    let job = match state.job_store.get_job(job_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return Ok(()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let logs = state
+        .job_store
+        .get_job_logs(job_id)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut request = state
+        .http_client
+        .put(config.group_url(&job.project_name, &job.branch))
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(render(&job, &logs))
+        .timeout(PUSH_TIMEOUT);
+    if let Some(username) = &config.username {
+        let password = match &config.password_env {
+            Some(var) => Some(
+                std::env::var(var)
+                    .map_err(|_| format!("environment variable '{}' is not set", var))?,
+            ),
+            None => None,
+        };
+        request = request.basic_auth(username, password);
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("gateway answered {}", response.status()));
+    }
+    debug!("Pushed metrics of job {} to {}", job_id, config.url);
+    Ok(())
+}
+
+/// Render a job's metrics in the Prometheus text format
+fn render(job: &Job, logs: &[JobLog]) -> String {
+    let success = u8::from(job.status == JobStatus::Success);
+    let duration = job
+        .completed_at
+        .map(|completed| (completed - job.started_at).num_milliseconds() as f64 / 1000.0)
+        .unwrap_or(0.0);
+    let completed = job.completed_at.map(|t| t.timestamp()).unwrap_or(0);
+
+    let mut out = String::new();
+    gauge(
+        &mut out,
+        "cicd_job_success",
+        "1 if the last job succeeded, 0 if it failed",
+    );
+    let _ = writeln!(out, "cicd_job_success {}", success);
+    gauge(
+        &mut out,
+        "cicd_job_duration_seconds",
+        "Duration of the last job",
+    );
+    let _ = writeln!(out, "cicd_job_duration_seconds {}", duration);
+    gauge(
+        &mut out,
+        "cicd_job_completed_timestamp_seconds",
+        "Unix time the last job finished",
+    );
+    let _ = writeln!(out, "cicd_job_completed_timestamp_seconds {}", completed);
+
+    // Steps that ran several times (e.g. one push per mirror) are summed up
+    let mut steps: Vec<(&str, i64, bool)> = Vec::new();
+    for log in logs {
+        let duration_ms = log.duration_ms.unwrap_or(0);
+        let ok = log.status == "success";
+        match steps.iter_mut().find(|(step, ..)| *step == log.log_type) {
+            Some((_, total, all_ok)) => {
+                *total += duration_ms;
+                *all_ok &= ok;
+            }
+            None => steps.push((&log.log_type, duration_ms, ok)),
+        }
+    }
+    gauge(
+        &mut out,
+        "cicd_job_step_duration_seconds",
+        "Duration of each step of the last job",
+    );
+    for (step, duration_ms, _) in &steps {
+        let _ = writeln!(
+            out,
+            "cicd_job_step_duration_seconds{{step=\"{}\"}} {}",
+            escape_label(step),
+            *duration_ms as f64 / 1000.0
+        );
+    }
+    gauge(
+        &mut out,
+        "cicd_job_step_success",
+        "1 if a step of the last job succeeded, 0 if it failed",
+    );
+    for (step, _, ok) in &steps {
+        let _ = writeln!(
+            out,
+            "cicd_job_step_success{{step=\"{}\"}} {}",
+            escape_label(step),
+            u8::from(*ok)
+        );
+    }
+    out
+}
+
+fn gauge(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
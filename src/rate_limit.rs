@@ -1,36 +1,249 @@
-use std::{collections::HashMap, time::Instant};
+//! Per-project webhook rate limiting with token buckets
+//!
+//! Each key gets a bucket holding up to `max` tokens that refills at
+//! `max / window` tokens per second; a request takes one token. Full buckets
+//! carry no state and are evicted periodically, and with
+//! `[server] persist_rate_limits` buckets are stored in the database so a
+//! restart doesn't reset everyone's quota. A webhook carrying the
+//! `[server] rate_limit_bypass_token_sha256` token in `X-CICD-Rate-Limit-Bypass`
+//! gets past an exhausted limit, e.g. for an urgent redeploy.
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+use crate::SharedState;
+use crate::auth::constant_time_eq;
+use crate::db::store::RateLimitBucket;
+
+/// How often idle (full) buckets are evicted
+const EVICT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Header carrying the token that lets a webhook past an exhausted limit
+pub const BYPASS_TOKEN_HEADER: &str = "X-CICD-Rate-Limit-Bypass";
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    tokens: f64,
+    capacity: f64,
+    /// Tokens added per second (infinite for a zero-length window)
+    refill_per_sec: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(capacity: f64, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            capacity,
+            refill_per_sec,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = if self.refill_per_sec.is_infinite() {
+            self.capacity
+        } else {
+            (self.tokens + elapsed * self.refill_per_sec).min(self.capacity)
+        };
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds `tokens` tokens
+    fn time_until(&self, tokens: f64) -> Duration {
+        let missing = (tokens.min(self.capacity) - self.tokens).max(0.0);
+        if missing == 0.0 || self.refill_per_sec.is_infinite() {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing / self.refill_per_sec)
+    }
+}
+
+/// Outcome of a rate limit check, with the quota left for the key
+#[derive(Debug, Clone)]
+pub struct RateLimitStatus {
+    pub allowed: bool,
+    pub limit: usize,
+    /// Requests that can be made right now
+    pub remaining: usize,
+    /// Time until the full quota is available again
+    pub reset_after: Duration,
+    /// When denied, time until the next request will be allowed
+    pub retry_after: Option<Duration>,
+}
+
+/// Returns true if `token` hashes to `expected_sha256` (hex-encoded SHA-256,
+/// see `[server] rate_limit_bypass_token_sha256`)
+pub fn bypass_token_matches(expected_sha256: &str, token: &str) -> bool {
+    let Ok(expected) = hex::decode(expected_sha256.trim()) else {
+        warn!("rate_limit_bypass_token_sha256 is not valid hex; ignoring bypass tokens");
+        return false;
+    };
+    let digest = Sha256::digest(token.trim().as_bytes());
+    constant_time_eq(&digest, &expected)
+}
 
 pub struct RateLimiter {
-    /// tracks timestamps per project
-    requests: HashMap<String, Vec<Instant>>,
+    /// Token bucket per project
+    buckets: HashMap<String, Bucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RateLimiter {
     pub fn new() -> Self {
         Self {
-            requests: HashMap::new(),
+            buckets: HashMap::new(),
         }
     }
 
-    /// Checks if the given key has exceeded the rate limit within the specified time window.
-    /// Returns `true` if the rate limit is exceeded, `false` otherwise.
-    pub fn check_rate_limit(&mut self, key: &str, max: usize, window_secs: u64) -> bool {
+    /// Takes a token from the key's bucket, allowing `max` requests per
+    /// `window_secs` with bursts of up to `max`.
+    pub fn check(&mut self, key: &str, max: usize, window_secs: u64) -> RateLimitStatus {
         let now = Instant::now();
-        let window_duration = std::time::Duration::from_secs(window_secs);
+        let capacity = max as f64;
+        let refill_per_sec = if window_secs == 0 {
+            f64::INFINITY
+        } else {
+            capacity / window_secs as f64
+        };
 
-        let timestamps = self
-            .requests
+        let bucket = self
+            .buckets
             .entry(key.to_string())
-            .or_insert_with(Vec::new);
+            .or_insert_with(|| Bucket::full(capacity, refill_per_sec, now));
+        bucket.refill(now);
+        // Follow config reloads
+        bucket.capacity = capacity;
+        bucket.refill_per_sec = refill_per_sec;
+        bucket.tokens = bucket.tokens.min(capacity);
 
-        // Remove timestamps older than window
-        timestamps.retain(|&t| now.duration_since(t) < window_duration);
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let retry_after = match allowed {
+            true => None,
+            // A zero limit never refills
+            false if max == 0 => Some(Duration::from_secs(window_secs)),
+            false => Some(bucket.time_until(1.0)),
+        };
 
-        if timestamps.len() < max {
-            timestamps.push(now);
-            false
-        } else {
-            true
+        RateLimitStatus {
+            allowed,
+            limit: max,
+            remaining: bucket.tokens.floor() as usize,
+            reset_after: bucket.time_until(capacity),
+            retry_after,
+        }
+    }
+
+    /// Drop buckets that have refilled completely; they behave exactly like
+    /// missing ones. Returns the evicted keys.
+    pub fn evict_idle(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let mut evicted = Vec::new();
+        self.buckets.retain(|key, bucket| {
+            bucket.refill(now);
+            let idle = bucket.tokens >= bucket.capacity;
+            if idle {
+                evicted.push(key.clone());
+            }
+            !idle
+        });
+        evicted
+    }
+
+    /// Current state of a key's bucket, for persisting
+    pub fn snapshot(&self, key: &str) -> Option<RateLimitBucket> {
+        let bucket = self.buckets.get(key)?;
+        let age = chrono::Duration::from_std(bucket.updated.elapsed()).unwrap_or_default();
+        Some(RateLimitBucket {
+            key: key.to_string(),
+            tokens: bucket.tokens,
+            capacity: bucket.capacity,
+            refill_per_sec: bucket.refill_per_sec,
+            updated_at: (Utc::now() - age).to_rfc3339(),
+        })
+    }
+
+    /// Load a persisted bucket, refilling it for the time since it was saved
+    pub fn restore(&mut self, saved: RateLimitBucket) {
+        let Ok(updated_at) = DateTime::parse_from_rfc3339(&saved.updated_at) else {
+            return;
+        };
+        let age = (Utc::now() - updated_at.with_timezone(&Utc))
+            .to_std()
+            .unwrap_or_default();
+        // Older than the monotonic clock can represent: long since refilled
+        let Some(updated) = Instant::now().checked_sub(age) else {
+            return;
+        };
+        let mut bucket = Bucket {
+            tokens: saved.tokens,
+            capacity: saved.capacity,
+            refill_per_sec: saved.refill_per_sec,
+            updated,
+        };
+        bucket.refill(Instant::now());
+        if bucket.tokens < bucket.capacity {
+            self.buckets.insert(saved.key, bucket);
         }
     }
 }
+
+/// Restore persisted buckets, if `[server] persist_rate_limits` is enabled
+pub async fn restore_persisted(state: &SharedState) {
+    if !state.config.load().server.persist_rate_limits() {
+        return;
+    }
+    match state.job_store.get_rate_limit_buckets().await {
+        Ok(saved) => {
+            let mut limiter = state.rate_limiter.lock().await;
+            for bucket in saved {
+                limiter.restore(bucket);
+            }
+        }
+        Err(e) => warn!("Failed to restore rate limit state: {}", e),
+    }
+}
+
+/// Save a key's bucket after a check, if persistence is enabled
+pub async fn persist(state: &SharedState, key: &str) {
+    if !state.config.load().server.persist_rate_limits() {
+        return;
+    }
+    let Some(bucket) = state.rate_limiter.lock().await.snapshot(key) else {
+        return;
+    };
+    if let Err(e) = state.job_store.save_rate_limit_bucket(&bucket).await {
+        error!("Failed to persist rate limit state for '{}': {}", key, e);
+    }
+}
+
+/// Spawn the background task evicting idle buckets (and their stored copies)
+pub fn spawn_evictor(state: SharedState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EVICT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let evicted = state.rate_limiter.lock().await.evict_idle();
+            if evicted.is_empty() || !state.config.load().server.persist_rate_limits() {
+                continue;
+            }
+            for key in evicted {
+                if let Err(e) = state.job_store.delete_rate_limit_bucket(&key).await {
+                    error!("Failed to delete rate limit state for '{}': {}", key, e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,225 @@
+//! Release directories for zero-downtime (symlink swap) deploys
+//!
+//! With `[project.release]`, every job exports the checked-out commit into a
+//! fresh `<dir>/releases/<id>` directory and runs its scripts there. Only when
+//! the main script succeeds is `<dir>/current` atomically switched to the new
+//! release, so the live directory never contains a half-built deploy. Old
+//! releases are pruned, keeping the last `keep` for quick manual switch-backs.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{CicdError, Result};
+use crate::git;
+
+/// Previous releases kept besides the current one, by default
+const DEFAULT_KEEP: usize = 5;
+
+/// `[project.release]` settings
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReleaseConfig {
+    /// Deploy root holding `releases/`, `shared/` and the `current` symlink
+    pub dir: String,
+    /// Previous releases to keep besides the current one (default: 5)
+    pub keep: Option<usize>,
+    /// Paths (relative to the release) replaced by symlinks into `<dir>/shared`,
+    /// for state that must survive deploys, e.g. `.env` or `storage/uploads`
+    #[serde(default)]
+    pub shared: Vec<String>,
+}
+
+impl ReleaseConfig {
+    pub fn keep(&self) -> usize {
+        self.keep.unwrap_or(DEFAULT_KEEP)
+    }
+
+    pub fn releases_dir(&self) -> PathBuf {
+        Path::new(&self.dir).join("releases")
+    }
+
+    pub fn shared_dir(&self) -> PathBuf {
+        Path::new(&self.dir).join("shared")
+    }
+
+    /// The symlink pointing at the live release
+    pub fn current_link(&self) -> PathBuf {
+        Path::new(&self.dir).join("current")
+    }
+
+    /// Directory of the live release, if any
+    pub fn current_release(&self) -> Option<PathBuf> {
+        let target = fs::read_link(self.current_link()).ok()?;
+        Some(Path::new(&self.dir).join(target))
+    }
+}
+
+/// A release being built by a job
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub config: ReleaseConfig,
+    /// `<UTC timestamp>-<short sha>`; sorts chronologically
+    pub id: String,
+    pub path: PathBuf,
+    /// The release that was live when this one was started
+    pub previous: Option<PathBuf>,
+}
+
+impl Release {
+    /// Plan a new release of commit `sha`
+    pub fn new(config: &ReleaseConfig, sha: &str) -> Self {
+        let id = format!(
+            "{}-{}",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            &sha[..7.min(sha.len())]
+        );
+        Self {
+            path: config.releases_dir().join(&id),
+            previous: config.current_release(),
+            config: config.clone(),
+            id,
+        }
+    }
+
+    /// Environment variables describing the release, for scripts
+    pub fn script_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![
+            ("CICD_RELEASE_ID", self.id.clone()),
+            ("CICD_RELEASE_DIR", self.path.display().to_string()),
+        ];
+        if let Some(previous) = &self.previous {
+            env.push(("CICD_PREVIOUS_RELEASE_DIR", previous.display().to_string()));
+        }
+        env
+    }
+
+    /// Create the release directory from HEAD of `repo_path` and link shared paths
+    pub fn prepare(&self, repo_path: &str) -> Result<String> {
+        fs::create_dir_all(self.config.releases_dir())
+            .map_err(|e| release_error("create releases directory", e))?;
+        fs::create_dir(&self.path).map_err(|e| release_error("create release directory", e))?;
+        let sha = git::export_head(repo_path, &self.path)?;
+
+        let mut output = format!(
+            "Prepared release {} at {} (commit {})\n",
+            self.id,
+            self.path.display(),
+            sha
+        );
+        for shared in &self.config.shared {
+            output.push_str(&self.link_shared(shared)?);
+        }
+        Ok(output)
+    }
+
+    fn link_shared(&self, shared: &str) -> Result<String> {
+        let relative = shared.trim_matches('/');
+        if relative.is_empty()
+            || Path::new(relative)
+                .components()
+                .any(|c| c.as_os_str() == "..")
+        {
+            return Err(CicdError::ReleaseFailed(format!(
+                "Invalid shared path '{}': must be relative to the release",
+                shared
+            )));
+        }
+        let link = self.path.join(relative);
+        let target = self.config.shared_dir().join(relative);
+
+        // The repository's copy is replaced by the shared one
+        match fs::symlink_metadata(&link) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(&link),
+            Ok(_) => fs::remove_file(&link),
+            Err(_) => Ok(()),
+        }
+        .map_err(|e| release_error("replace shared path", e))?;
+        if let Some(parent) = link.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| release_error("create shared link parent", e))?;
+        }
+        symlink(&target, &link).map_err(|e| release_error("link shared path", e))?;
+
+        let missing = if target.exists() {
+            ""
+        } else {
+            " (target does not exist yet)"
+        };
+        Ok(format!(
+            "Linked {} -> {}{}\n",
+            relative,
+            target.display(),
+            missing
+        ))
+    }
+
+    /// Atomically point `current` at this release
+    pub fn activate(&self) -> Result<String> {
+        let current = self.config.current_link();
+        if fs::symlink_metadata(&current).is_ok_and(|m| !m.file_type().is_symlink()) {
+            return Err(CicdError::ReleaseFailed(format!(
+                "{} exists and is not a symlink; move it away to let releases manage it",
+                current.display()
+            )));
+        }
+
+        // Relative target, so the deploy root can be moved or mounted elsewhere
+        let target = Path::new("releases").join(&self.id);
+        let staging = Path::new(&self.config.dir).join(format!(".current-{}", self.id));
+        symlink(&target, &staging).map_err(|e| release_error("create symlink", e))?;
+        // rename(2) replaces the old link in one step: there is always a `current`
+        if let Err(e) = fs::rename(&staging, &current) {
+            let _ = fs::remove_file(&staging);
+            return Err(release_error("switch current symlink", e));
+        }
+        Ok(format!("{} -> {}\n", current.display(), target.display()))
+    }
+
+    /// Remove this release if it never went live (`discard`), then prune
+    /// releases beyond `keep`. The live release is never removed.
+    pub fn cleanup(&self, discard: bool) -> Result<String> {
+        let mut output = String::new();
+        if discard {
+            fs::remove_dir_all(&self.path)
+                .map_err(|e| release_error("remove failed release", e))?;
+            output.push_str(&format!("Removed failed release {}\n", self.id));
+        }
+
+        let live = self.config.current_release();
+        let mut releases: Vec<PathBuf> = fs::read_dir(self.config.releases_dir())
+            .map_err(|e| release_error("list releases", e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir() && Some(path) != live.as_ref())
+            .collect();
+        releases.sort();
+
+        let excess = releases.len().saturating_sub(self.config.keep());
+        for old in &releases[..excess] {
+            fs::remove_dir_all(old).map_err(|e| release_error("remove old release", e))?;
+            output.push_str(&format!("Removed old release {}\n", old.display()));
+        }
+        output.push_str(&format!(
+            "{} previous release(s) kept\n",
+            releases.len() - excess
+        ));
+        Ok(output)
+    }
+}
+
+fn release_error(operation: &str, e: std::io::Error) -> CicdError {
+    CicdError::ReleaseFailed(format!("Failed to {}: {}", operation, e))
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "release deploys require symlinks (Unix only)",
+    ))
+}
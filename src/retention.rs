@@ -0,0 +1,55 @@
+//! Job retention: periodically deletes old finished jobs
+//!
+//! Enabled by `[server] job_retention_days`. Pinned jobs are never pruned.
+//! Recorded webhook delivery IDs are always pruned after
+//! `DELIVERY_RETENTION_DAYS`. The artifacts of pruned jobs are deleted with
+//! them.
+
+use chrono::Utc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::SharedState;
+use crate::artifacts;
+
+/// How often the pruner runs
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long webhook delivery IDs are kept to detect redeliveries. GitHub
+/// only offers redelivery of recent deliveries.
+const DELIVERY_RETENTION_DAYS: i64 = 7;
+
+/// Spawn the background task pruning jobs older than the retention period
+/// and old webhook delivery IDs. The retention setting is read on every run,
+/// so it follows config reloads.
+pub fn spawn_pruner(state: SharedState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let cutoff = Utc::now() - chrono::Duration::days(DELIVERY_RETENTION_DAYS);
+            if let Err(e) = state.job_store.prune_deliveries_before(cutoff).await {
+                error!("Failed to prune webhook deliveries: {}", e);
+            }
+
+            let Some(days) = state.config.load().server.job_retention_days else {
+                continue;
+            };
+            let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+            match state.job_store.prune_jobs_completed_before(cutoff).await {
+                Ok(ids) if ids.is_empty() => {}
+                Ok(ids) => {
+                    info!("Pruned {} job(s) older than {} day(s)", ids.len(), days);
+                    let artifacts_dir = state.config.load().server.artifacts_dir().to_string();
+                    let removal = tokio::task::spawn_blocking(move || {
+                        artifacts::remove(&artifacts_dir, &ids)
+                    });
+                    if let Err(e) = removal.await {
+                        error!("Failed to remove artifacts of pruned jobs: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to prune old jobs: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,160 @@
+//! Running a project's scripts as another OS user (`run_as_user`)
+//!
+//! A server running as root switches to the user itself (setgid/setuid,
+//! with the user's groups) before the script starts. Otherwise scripts are
+//! started through `sudo -n -u <user>`, which needs a sudoers rule allowing
+//! the server's user to run commands as that user, with `SETENV` so the
+//! job's environment variables are passed on.
+
+use tokio::process::Command;
+
+/// The user a project's scripts run as
+#[derive(Debug, Clone)]
+pub struct RunAs {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups, including `gid`
+    pub groups: Vec<u32>,
+    pub home: String,
+}
+
+impl RunAs {
+    /// Look up `name` in the user database
+    #[cfg(unix)]
+    pub fn lookup(name: &str) -> Result<Self, String> {
+        use std::ffi::{CStr, CString};
+
+        let c_name = CString::new(name).map_err(|_| format!("invalid user name '{}'", name))?;
+        // SAFETY: all-zero is a valid `passwd` (null pointers, zero ids);
+        // getpwnam_r fills it in, pointing into `buf`
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut buf = vec![0 as libc::c_char; 16 * 1024];
+        let mut found = std::ptr::null_mut();
+        // SAFETY: the pointers are valid for the call, `buf.len()` is the
+        // buffer's size
+        let rc = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut passwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut found,
+            )
+        };
+        if found.is_null() {
+            return Err(match rc {
+                0 => format!("user '{}' doesn't exist", name),
+                rc => format!(
+                    "failed to look up user '{}': {}",
+                    name,
+                    std::io::Error::from_raw_os_error(rc)
+                ),
+            });
+        }
+        // SAFETY: getpwnam_r succeeded, so `pw_dir` is a C string in `buf`
+        let home = unsafe { CStr::from_ptr(passwd.pw_dir) }
+            .to_string_lossy()
+            .into_owned();
+
+        let mut groups: Vec<libc::gid_t> = vec![0; 32];
+        loop {
+            let mut count = groups.len() as libc::c_int;
+            // SAFETY: `groups` holds `count` entries
+            let rc = unsafe {
+                libc::getgrouplist(
+                    c_name.as_ptr(),
+                    passwd.pw_gid as _,
+                    groups.as_mut_ptr().cast(),
+                    &mut count,
+                )
+            };
+            if rc >= 0 {
+                groups.truncate(count as usize);
+                break;
+            }
+            // Too small: `count` is the number needed on some systems only
+            let needed = (count as usize).max(groups.len() * 2);
+            groups.resize(needed, 0);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            uid: passwd.pw_uid,
+            gid: passwd.pw_gid,
+            groups,
+            home,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn lookup(name: &str) -> Result<Self, String> {
+        Err(format!(
+            "can't run scripts as '{}': run_as_user is only supported on Unix",
+            name
+        ))
+    }
+
+    /// Variables describing the user, as a login would set them
+    pub fn env(&self) -> [(&str, &str); 3] {
+        [
+            ("HOME", self.home.as_str()),
+            ("USER", self.name.as_str()),
+            ("LOGNAME", self.name.as_str()),
+        ]
+    }
+
+    /// The command starting `program` as this user, once `switch_user`
+    /// was applied to it. Without root, that's `sudo` passing on the
+    /// variables named in `env`.
+    pub fn command(&self, program: &str, env: &[&str]) -> Command {
+        if is_root() {
+            return Command::new(program);
+        }
+        let mut cmd = Command::new("sudo");
+        cmd.args(["-n", "-u", &self.name]);
+        if !env.is_empty() {
+            cmd.arg(format!("--preserve-env={}", env.join(",")));
+        }
+        cmd.arg("--").arg(program);
+        cmd
+    }
+
+    /// Switch to the user in the child process, before it runs the script;
+    /// without root, `sudo` does that
+    #[cfg(unix)]
+    pub fn switch_user(&self, cmd: &mut Command) {
+        if !is_root() {
+            return;
+        }
+        let (uid, gid) = (self.uid, self.gid);
+        let groups: Vec<libc::gid_t> = self.groups.clone();
+        // SAFETY: the closure only makes async-signal-safe system calls
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn switch_user(&self, _cmd: &mut Command) {}
+}
+
+/// Returns true if the server runs as root
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid(2) has no memory safety requirements
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}
@@ -0,0 +1,195 @@
+//! Scheduled builds: jobs started on a cron schedule instead of a push
+//!
+//! A project's `schedule` (e.g. `0 3 * * *`, in the server's local time)
+//! starts a job for its `schedule_branch` at every matching minute, e.g.
+//! for nightly rebuilds. Schedules are read on every check, so they follow
+//! config reloads.
+
+use chrono::{DateTime, Local, Utc};
+use croner::Cron;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::api::stream::JobEvent;
+use crate::freeze::FreezeAction;
+use crate::job::{Job, JobStatus};
+use crate::webhook::WebhookData;
+use crate::{ProjectConfig, SharedState};
+
+/// Standard five-field cron expression (minute, hour, day of month, month,
+/// day of week), written as a string in the config
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    expression: String,
+    cron: Cron,
+}
+
+impl Schedule {
+    pub fn new(expression: &str) -> Result<Self, String> {
+        let cron = Cron::new(expression)
+            .parse()
+            .map_err(|e| format!("invalid cron expression '{}': {}", expression, e))?;
+        Ok(Self {
+            expression: expression.to_string(),
+            cron,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// The first time after `time` the schedule fires
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron
+            .find_next_occurrence(&time.with_timezone(&Local), false)
+            .ok()
+            .map(|next| next.with_timezone(&Utc))
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.expression)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::new(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Spawn the background task starting scheduled jobs. It wakes up at the
+/// start of every minute and runs the projects whose schedule fired since
+/// the last check.
+pub fn spawn_scheduler(state: SharedState) {
+    tokio::spawn(async move {
+        let mut last_check = Utc::now();
+        loop {
+            tokio::time::sleep(until_next_minute(Utc::now())).await;
+            let now = Utc::now();
+
+            let due: Vec<ProjectConfig> = state
+                .config
+                .load()
+                .project
+                .iter()
+                .filter(|p| {
+                    p.schedule
+                        .as_ref()
+                        .and_then(|s| s.next_after(last_check))
+                        .is_some_and(|next| next <= now)
+                })
+                .cloned()
+                .collect();
+            last_check = now;
+
+            for project in due {
+                start_scheduled_job(&state, project).await;
+            }
+        }
+    });
+}
+
+/// Time left until the start of the next minute (with a little slack, so
+/// the check doesn't run just before it)
+fn until_next_minute(now: DateTime<Utc>) -> Duration {
+    let elapsed = now.timestamp_millis().rem_euclid(60_000) as u64;
+    Duration::from_millis(60_000 - elapsed + 100)
+}
+
+/// Create and enqueue a job for a project whose schedule fired
+async fn start_scheduled_job(state: &SharedState, project: ProjectConfig) {
+    let Some(branch) = project.schedule_branch().map(String::from) else {
+        warn!(
+            "Project '{}' has a schedule but no branch to build; set schedule_branch",
+            project.name
+        );
+        return;
+    };
+    let expression = project
+        .schedule
+        .as_ref()
+        .map(|s| s.expression().to_string())
+        .unwrap_or_default();
+
+    // Deploy freeze: skip the run, or hold the job until the freeze ends
+    let mut hold_until = None;
+    if let Some((until, reason)) = project.frozen_until(Utc::now()) {
+        let reason = reason.map(|r| format!(" ({})", r)).unwrap_or_default();
+        match project.freeze_action() {
+            FreezeAction::Reject => {
+                warn!(
+                    "Deploy freeze for project '{}' until {}{}; skipping scheduled build of '{}'",
+                    project.name, until, reason, branch
+                );
+                return;
+            }
+            FreezeAction::Queue => {
+                info!(
+                    "Deploy freeze for project '{}' until {}{}; holding scheduled build of '{}'",
+                    project.name, until, reason, branch
+                );
+                hold_until = Some(until);
+            }
+        }
+    }
+
+    let job = Job::from_webhook(
+        project.name.clone(),
+        branch.clone(),
+        None,
+        Some(format!("Scheduled build ({})", expression)),
+        None,
+    );
+    let job_id = job.id.clone();
+    if let Err(e) = state.job_store.create_job(&job).await {
+        error!(
+            "Failed to create scheduled job for project '{}': {}",
+            project.name, e
+        );
+        return;
+    }
+    info!(
+        "Created scheduled job {} for project '{}' branch '{}'",
+        job_id, project.name, branch
+    );
+    state.job_events.send(JobEvent {
+        event_type: "created".to_string(),
+        job_id: job_id.clone(),
+        project_name: project.name.clone(),
+        branch: branch.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    });
+
+    let webhook_data = WebhookData {
+        commit_message: job.commit_message.clone(),
+        ..WebhookData::minimal(project.name.clone(), branch, project.repo_path.clone())
+    };
+    if let Err(e) = state
+        .dispatcher
+        .enqueue(
+            &state.job_store,
+            &job_id,
+            &project,
+            &webhook_data,
+            hold_until,
+        )
+        .await
+    {
+        error!("Failed to enqueue scheduled job {}: {}", job_id, e);
+        let _ = state
+            .job_store
+            .complete_job(
+                &job_id,
+                JobStatus::Failed,
+                None,
+                Some(e.to_string()),
+                Utc::now(),
+            )
+            .await;
+    }
+}
@@ -0,0 +1,67 @@
+//! Project secrets (`[project.secrets]`)
+//!
+//! Secrets are passed to a project's scripts, steps and hooks as environment
+//! variables, like `env`, but their values never end up in what is recorded
+//! of a job: every occurrence in script output (stored logs, the live log
+//! stream, error messages) and in the environment snapshot is replaced by
+//! `***`. Values given in the config are masked in a job's pipeline snapshot
+//! as well, and read from the current config when the job runs.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::utils::MASK;
+
+/// Secret values shorter than this aren't masked, so a value like `1`
+/// doesn't garble all output
+pub const MIN_MASKED_LEN: usize = 4;
+
+/// A secret's value: given in the config, or read from an environment
+/// variable of the server (`{ env = "NAME" }`) when a job starts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Secret {
+    Value(String),
+    Env { env: String },
+}
+
+impl Secret {
+    /// The secret's value; fails with the name of its environment variable
+    /// if that isn't set
+    pub fn value(&self) -> Result<String, &str> {
+        match self {
+            Secret::Value(value) => Ok(value.clone()),
+            Secret::Env { env } => std::env::var(env).map_err(|_| env.as_str()),
+        }
+    }
+}
+
+/// Replaces secret values in text with `***`
+#[derive(Debug, Clone, Default)]
+pub struct SecretMask {
+    /// Longest first, so a secret containing another is masked as a whole
+    values: Vec<String>,
+}
+
+impl SecretMask {
+    pub fn new(values: impl IntoIterator<Item = String>) -> Self {
+        let mut values: Vec<String> = values
+            .into_iter()
+            .filter(|value| value.len() >= MIN_MASKED_LEN)
+            .collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        values.dedup();
+        Self { values }
+    }
+
+    /// `text` with every occurrence of a secret value replaced
+    pub fn mask<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut masked = Cow::Borrowed(text);
+        for value in &self.values {
+            if masked.contains(value.as_str()) {
+                masked = Cow::Owned(masked.replace(value.as_str(), MASK));
+            }
+        }
+        masked
+    }
+}
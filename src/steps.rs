@@ -0,0 +1,206 @@
+//! Multi-step pipelines: `[[project.steps]]` replacing the single `run_script`
+//!
+//! Steps run as the job's main part, each recorded as its own job log under
+//! its name. Without `needs`, they run in order and the first failing step
+//! fails the job; the rest are skipped. A step with `needs` runs after the
+//! steps it names and is skipped if one of them didn't succeed, while steps
+//! not depending on a failed one still run. A step with `allow_failure` is
+//! recorded as failed, but counts as succeeded for the job and for the steps
+//! needing it. Hooks (`pre_script`, `post_*`)
+//! run around the steps as they do around `run_script`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+/// Step names the pipeline uses for its own steps
+const RESERVED_NAMES: &[&str] = &[
+    "resume",
+    "git_fetch",
+    "git_reset",
+    "git_switch",
+    "git_pull",
+    "git_checkout",
+    "workspace",
+    "git_log",
+    "release_prepare",
+    "release_activate",
+    "release_cleanup",
+    "pre_script",
+    "main_script",
+    "post_success",
+    "post_failure",
+    "post_script",
+    "post_always",
+    "mirror",
+    "artifacts",
+    "cache_restore",
+    "cache_save",
+    "branch_deleted",
+];
+
+/// One `[[project.steps]]` entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StepConfig {
+    /// Name the step is logged under, e.g. `build`
+    pub name: String,
+    /// Command to run, like `run_script`
+    pub command: String,
+    /// Extra environment variables for this step
+    pub env: Option<BTreeMap<String, String>>,
+    /// Directory to run in, relative to the project's `working_dir` (default:
+    /// the project's `working_dir`)
+    pub working_dir: Option<String>,
+    /// Time the step may run (default: the project's `script_timeout_seconds`)
+    pub timeout_seconds: Option<u64>,
+    /// Names of the steps that must succeed before this one runs
+    pub needs: Option<Vec<String>>,
+    /// A failing step is recorded, but the job goes on as if it succeeded
+    pub allow_failure: Option<bool>,
+}
+
+impl StepConfig {
+    /// A step running `command` as the job's `main_script`
+    pub fn main_script(command: &str) -> Self {
+        Self {
+            name: "main_script".to_string(),
+            command: command.to_string(),
+            env: None,
+            working_dir: None,
+            timeout_seconds: None,
+            needs: None,
+            allow_failure: None,
+        }
+    }
+
+    /// Returns true if the step may fail without failing the job
+    pub fn allow_failure(&self) -> bool {
+        self.allow_failure.unwrap_or(false)
+    }
+
+    /// Names of the steps this one needs
+    pub fn needs(&self) -> impl Iterator<Item = &str> {
+        self.needs.iter().flatten().map(|need| need.trim())
+    }
+
+    /// Time the step may run, if limited, given the project's script timeout
+    pub fn timeout(&self, script_timeout: Option<Duration>) -> Option<Duration> {
+        match self.timeout_seconds {
+            Some(_) => crate::timeout(self.timeout_seconds),
+            None => script_timeout,
+        }
+    }
+
+    /// Directory the step runs in, given the project's script directory
+    pub fn dir(&self, script_dir: &str) -> String {
+        match self.working_dir.as_deref().map(|d| d.trim_matches('/')) {
+            Some(dir) if !dir.is_empty() => Path::new(script_dir).join(dir).display().to_string(),
+            _ => script_dir.to_string(),
+        }
+    }
+
+    /// The step's environment variables, after `base`
+    pub fn env<'a>(&'a self, base: &[(&'a str, String)]) -> Vec<(&'a str, String)> {
+        let mut env = base.to_vec();
+        for (key, value) in self.env.iter().flatten() {
+            env.push((key.as_str(), value.clone()));
+        }
+        env
+    }
+}
+
+/// The steps in the order they run: each after the steps it needs, otherwise
+/// in config order. If no step has `needs`, each one needs the step before it.
+pub fn schedule(mut steps: Vec<StepConfig>) -> Vec<StepConfig> {
+    if steps.iter().all(|step| step.needs.is_none()) {
+        for i in 1..steps.len() {
+            let previous = steps[i - 1].name.trim().to_string();
+            steps[i].needs = Some(vec![previous]);
+        }
+        return steps;
+    }
+    let mut done = HashSet::new();
+    let mut ordered = Vec::with_capacity(steps.len());
+    while !steps.is_empty() {
+        // Validated steps don't need each other; should they, the rest run
+        // in config order
+        let next = steps
+            .iter()
+            .position(|step| step.needs().all(|need| done.contains(need)))
+            .unwrap_or(0);
+        let step = steps.remove(next);
+        done.insert(step.name.trim().to_string());
+        ordered.push(step);
+    }
+    ordered
+}
+
+/// Check a project's steps: each needs a command and a unique name that
+/// isn't one of the pipeline's own, and may only need other steps, without
+/// steps needing each other
+pub fn validate(project_name: &str, steps: &[StepConfig]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for step in steps {
+        let name = step.name.trim();
+        if name.is_empty() {
+            return Err(format!(
+                "Project '{}' has a step without a name",
+                project_name
+            ));
+        }
+        if RESERVED_NAMES.contains(&name) {
+            return Err(format!(
+                "Project '{}': step name '{}' is reserved",
+                project_name, name
+            ));
+        }
+        if !names.insert(name) {
+            return Err(format!(
+                "Project '{}' has several steps named '{}'",
+                project_name, name
+            ));
+        }
+        if step.command.trim().is_empty() {
+            return Err(format!(
+                "Project '{}': step '{}' has no command",
+                project_name, name
+            ));
+        }
+    }
+    for step in steps {
+        for need in step.needs() {
+            if need == step.name.trim() {
+                return Err(format!(
+                    "Project '{}': step '{}' needs itself",
+                    project_name, need
+                ));
+            }
+            if !names.contains(need) {
+                return Err(format!(
+                    "Project '{}': step '{}' needs unknown step '{}'",
+                    project_name,
+                    step.name.trim(),
+                    need
+                ));
+            }
+        }
+    }
+    let mut done = HashSet::new();
+    let mut pending: Vec<&StepConfig> = steps.iter().collect();
+    while let Some(next) = pending
+        .iter()
+        .position(|step| step.needs().all(|need| done.contains(need)))
+    {
+        done.insert(pending.remove(next).name.trim());
+    }
+    if !pending.is_empty() {
+        let names: Vec<&str> = pending.iter().map(|step| step.name.trim()).collect();
+        return Err(format!(
+            "Project '{}': steps {} need each other",
+            project_name,
+            names.join(", ")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,166 @@
+//! Stopping jobs: cancellation, timeouts and terminating scripts
+//!
+//! A job is stopped through its `StopSignal` when it is cancelled or runs
+//! past its `timeout_seconds`. The running script is sent `SIGTERM` and has
+//! the project's `kill_grace_seconds` to clean up (e.g. stop containers)
+//! before its process group is killed with `SIGKILL`; steps that haven't
+//! started don't run.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::error::CicdError;
+
+/// Time a script has to exit after `SIGTERM` unless `kill_grace_seconds` is set
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(10);
+
+/// Why a job or step is stopped
+#[derive(Debug, Clone)]
+pub enum StopReason {
+    /// Cancelled through the API
+    Cancelled,
+    /// Ran longer than allowed; the message says what
+    TimedOut(String),
+}
+
+impl StopReason {
+    /// The error a step stopped for this reason fails with, including the
+    /// output its script wrote until then
+    pub fn error(&self, output: &str) -> CicdError {
+        let message = match self {
+            Self::Cancelled => "stopped through the API",
+            Self::TimedOut(message) => message,
+        };
+        let output = output.trim();
+        let message = if output.is_empty() {
+            message.to_string()
+        } else {
+            format!("{}.\nOutput: {}", message, output)
+        };
+        match self {
+            Self::Cancelled => CicdError::Cancelled(message),
+            Self::TimedOut(_) => CicdError::TimedOut(message),
+        }
+    }
+}
+
+/// Asks a job's pipeline to stop; clones share the same signal
+#[derive(Debug, Clone)]
+pub struct StopSignal {
+    reason: Arc<watch::Sender<Option<StopReason>>>,
+    /// Time a script has to exit after `SIGTERM` before it is killed
+    pub grace: Duration,
+}
+
+impl StopSignal {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            reason: Arc::new(watch::channel(None).0),
+            grace,
+        }
+    }
+
+    /// Ask the job to stop. The first reason given sticks.
+    pub fn stop(&self, reason: StopReason) {
+        self.reason.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(reason);
+            true
+        });
+    }
+
+    /// Why the job was asked to stop, if it was
+    pub fn reason(&self) -> Option<StopReason> {
+        self.reason.borrow().clone()
+    }
+
+    /// Wait until the job is asked to stop
+    pub async fn stopped(&self) -> StopReason {
+        let mut receiver = self.reason.subscribe();
+        let reason = receiver
+            .wait_for(Option::is_some)
+            .await
+            .map(|reason| reason.clone());
+        match reason {
+            Ok(Some(reason)) => reason,
+            // Can't happen: the sender lives as long as `self`
+            _ => std::future::pending().await,
+        }
+    }
+}
+
+/// Signals a stopped script is sent, in order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Term,
+    Kill,
+}
+
+impl Signal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Term => "SIGTERM",
+            Self::Kill => "SIGKILL",
+        }
+    }
+}
+
+/// Process group a script leads. Whatever is left of it is killed when
+/// this is dropped before `release`, e.g. when the script is abandoned or
+/// processes it started outlive it after it was stopped.
+pub struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    /// The process group led by the process `pid`
+    pub fn new(pid: Option<u32>) -> Self {
+        Self(pid)
+    }
+
+    /// Send `signal` to every process in the group
+    pub fn signal(&self, signal: Signal) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.0 {
+            let signal = match signal {
+                Signal::Term => libc::SIGTERM,
+                Signal::Kill => libc::SIGKILL,
+            };
+            // SAFETY: kill(2) has no memory safety requirements
+            unsafe {
+                libc::kill(-(pgid as libc::pid_t), signal);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = signal;
+    }
+
+    /// Leave the group alone once the script exited by itself
+    pub fn release(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.signal(Signal::Kill);
+    }
+}
+
+/// Name of the signal that terminated a process, e.g. `SIGSEGV`
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGPIPE => "SIGPIPE",
+        libc::SIGTERM => "SIGTERM",
+        _ => return format!("signal {}", signal),
+    };
+    name.to_string()
+}
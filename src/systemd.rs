@@ -0,0 +1,91 @@
+//! systemd integration: `Type=notify` readiness and socket activation.
+//!
+//! Implements the small parts of the sd_notify / sd_listen_fds protocols
+//! directly, so no libsystemd is needed. Outside systemd everything is a no-op.
+
+use std::io;
+use std::net::TcpListener;
+
+/// Take the listening socket passed via systemd socket activation, if any.
+/// Only the first passed socket is used.
+#[cfg(unix)]
+pub fn activated_listener() -> io::Result<Option<TcpListener>> {
+    use std::os::fd::{FromRawFd, RawFd};
+
+    /// First file descriptor passed by systemd (SD_LISTEN_FDS_START)
+    const LISTEN_FDS_START: RawFd = 3;
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        == Some(std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0);
+    if !for_us || count == 0 {
+        return Ok(None);
+    }
+    if count > 1 {
+        tracing::warn!(
+            "systemd passed {} sockets; only the first one is used",
+            count
+        );
+    }
+
+    // SAFETY: systemd guarantees LISTEN_FDS descriptors starting at fd 3 are
+    // open and owned by this process once LISTEN_PID matches.
+    let passed = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    // The passed fd is not close-on-exec; keep a CLOEXEC duplicate instead so
+    // job scripts don't inherit the listening socket
+    let listener = passed.try_clone()?;
+    drop(passed);
+    Ok(Some(listener))
+}
+
+#[cfg(not(unix))]
+pub fn activated_listener() -> io::Result<Option<TcpListener>> {
+    Ok(None)
+}
+
+/// Send a state update (e.g. `READY=1`) to the service manager.
+/// Returns `Ok(false)` when not running under systemd with `Type=notify`.
+#[cfg(unix)]
+pub fn notify(state: &str) -> io::Result<bool> {
+    use std::os::unix::net::UnixDatagram;
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+
+    // '@' marks a socket in the abstract namespace (Linux only)
+    if let Some(name) = path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::SocketAddr;
+
+            let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+            return Ok(true);
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = name;
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract NOTIFY_SOCKET is only supported on Linux",
+            ));
+        }
+    }
+
+    socket.send_to(state.as_bytes(), path.as_ref())?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) -> io::Result<bool> {
+    Ok(false)
+}
@@ -0,0 +1,67 @@
+//! Pipeline templates (`[templates.<name>]`)
+//!
+//! A template holds project settings shared by several similar projects,
+//! typically `steps` and hooks. A project using one (`template = "name"`)
+//! gets every setting of the template it doesn't set itself. Tables like
+//! `env` are merged, so a project can override single entries; other
+//! settings, `steps` included, are replaced as a whole.
+
+use toml::{Table, Value};
+
+/// Settings a template can't provide
+const PROJECT_ONLY: &[&str] = &["name", "template"];
+
+/// Fill in the settings of the projects of a parsed config file from the
+/// templates they use
+pub fn apply(config: &mut Table) -> Result<(), String> {
+    let templates = match config.get("templates") {
+        Some(Value::Table(templates)) => templates.clone(),
+        Some(_) => return Err("templates must be a table of templates".to_string()),
+        None => Table::new(),
+    };
+    for (name, template) in &templates {
+        let Value::Table(template) = template else {
+            return Err(format!("Template '{}' must be a table", name));
+        };
+        if let Some(key) = PROJECT_ONLY.iter().find(|key| template.contains_key(**key)) {
+            return Err(format!("Template '{}' can't set {}", name, key));
+        }
+    }
+
+    let Some(Value::Array(projects)) = config.get_mut("project") else {
+        return Ok(());
+    };
+    for project in projects.iter_mut() {
+        let Value::Table(project) = project else {
+            continue;
+        };
+        let Some(name) = project.get("template") else {
+            continue;
+        };
+        let project_name = project
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let template = name
+            .as_str()
+            .and_then(|name| templates.get(name))
+            .and_then(Value::as_table)
+            .ok_or_else(|| format!("Project '{}' uses unknown template {}", project_name, name))?;
+        merge(project, template);
+    }
+    Ok(())
+}
+
+/// Add the settings of `template` that `project` doesn't set, merging tables
+fn merge(project: &mut Table, template: &Table) {
+    for (key, value) in template {
+        match (project.get_mut(key), value) {
+            (None, value) => {
+                project.insert(key.clone(), value.clone());
+            }
+            (Some(Value::Table(own)), Value::Table(value)) => merge(own, value),
+            (Some(_), _) => {}
+        }
+    }
+}
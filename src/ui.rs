@@ -1,35 +1,83 @@
+//! Web UI served from assets embedded at compile time (`ui` feature).
+//! Without the feature, unmatched routes get a JSON 404 instead.
+
 use axum::{
-    body::Body,
     extract::Request,
-    http::{StatusCode, header},
+    http::StatusCode,
     response::{IntoResponse, Response},
 };
+
+#[cfg(feature = "ui")]
+use axum::{body::Body, extract::State as AxumState, http::header};
+#[cfg(feature = "ui")]
 use rust_embed::RustEmbed;
+#[cfg(feature = "ui")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "ui")]
+use crate::SharedState;
 
+#[cfg(feature = "ui")]
 #[derive(RustEmbed)]
 #[folder = "ui/dist/"]
 struct UiAssets;
 
-pub async fn serve_ui(req: Request) -> impl IntoResponse {
+/// Hashed build output (SvelteKit puts content-hashed files here); safe to cache forever
+#[cfg(feature = "ui")]
+const IMMUTABLE_PREFIX: &str = "_app/immutable/";
+
+#[cfg(feature = "ui")]
+pub async fn serve_ui(AxumState(state): AxumState<SharedState>, req: Request) -> Response {
     let path = req.uri().path().trim_start_matches('/');
 
+    // Unknown API routes get a JSON 404 instead of the SPA
+    if path == "api" || path.starts_with("api/") {
+        return api_not_found(req.uri().path());
+    }
+
+    let if_none_match = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
     // Try to serve the exact path first
-    if let Some(content) = UiAssets::get(path) {
+    if path != "index.html"
+        && let Some(content) = UiAssets::get(path)
+    {
         let mime = mime_guess::from_path(path).first_or_octet_stream();
-        return Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, mime.as_ref())
-            .body(Body::from(content.data.into_owned()))
-            .unwrap();
+        let cache_control = if path.starts_with(IMMUTABLE_PREFIX) {
+            "public, max-age=31536000, immutable"
+        } else {
+            "no-cache"
+        };
+        let etag = format!("\"{}\"", hex::encode(content.metadata.sha256_hash()));
+        return cached_response(
+            mime.as_ref(),
+            cache_control,
+            etag,
+            if_none_match.as_deref(),
+            content.data.into_owned(),
+        );
     }
 
-    // For SPA: serve index.html for any unmatched routes
+    // For SPA: serve index.html for any unmatched routes. It references the
+    // current hashed assets, so browsers must revalidate it on every load.
     if let Some(content) = UiAssets::get("index.html") {
-        return Response::builder()
-            .status(StatusCode::OK)
-            .header(header::CONTENT_TYPE, "text/html")
-            .body(Body::from(content.data.into_owned()))
-            .unwrap();
+        let body = if state.base_path.is_empty() {
+            content.data.into_owned()
+        } else {
+            let html = String::from_utf8_lossy(&content.data);
+            rewrite_index(&html, &state.base_path).into_bytes()
+        };
+        let etag = format!("\"{}\"", hex::encode(Sha256::digest(&body)));
+        return cached_response(
+            "text/html",
+            "no-cache",
+            etag,
+            if_none_match.as_deref(),
+            body,
+        );
     }
 
     Response::builder()
@@ -37,3 +85,83 @@ pub async fn serve_ui(req: Request) -> impl IntoResponse {
         .body(Body::from("Not Found"))
         .unwrap()
 }
+
+/// Build a response with caching headers, or `304 Not Modified` when the
+/// client's `If-None-Match` matches the ETag
+#[cfg(feature = "ui")]
+fn cached_response(
+    content_type: &str,
+    cache_control: &str,
+    etag: String,
+    if_none_match: Option<&str>,
+    body: Vec<u8>,
+) -> Response {
+    let not_modified = if_none_match
+        .is_some_and(|tags| tags.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+
+    let builder = Response::builder()
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ETAG, &etag);
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap();
+    }
+    builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Point the SPA shell at `base_path`: root-relative asset URLs get the prefix
+/// and SvelteKit's runtime `base` is set, so client-side routing, `$app/paths`
+/// and API calls all resolve under the sub-path.
+#[cfg(feature = "ui")]
+fn rewrite_index(html: &str, base_path: &str) -> String {
+    let mut html = html.to_string();
+    for attr in ["href=\"", "src=\"", "import(\""] {
+        html = html
+            .replace(&format!("{}/", attr), &format!("{}{}/", attr, base_path))
+            // Leave protocol-relative URLs alone
+            .replace(&format!("{}{}//", attr, base_path), &format!("{}//", attr));
+    }
+    for key in ["base", "assets"] {
+        html = html.replace(
+            &format!("{}: \"\"", key),
+            &format!("{}: \"{}\"", key, base_path),
+        );
+    }
+    html
+}
+
+/// Built without the `ui` feature: API-only, so answer unmatched routes with JSON
+#[cfg(not(feature = "ui"))]
+pub async fn serve_ui(req: Request) -> Response {
+    let path = req.uri().path();
+    if path == "/api" || path.starts_with("/api/") {
+        return api_not_found(path);
+    }
+    (
+        StatusCode::NOT_FOUND,
+        axum::Json(serde_json::json!({
+            "error": "Not found",
+            "path": path,
+            "message": "This build does not include the web UI; use the /api endpoints"
+        })),
+    )
+        .into_response()
+}
+
+/// JSON 404 for requests to unknown API routes
+fn api_not_found(path: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        axum::Json(serde_json::json!({
+            "error": "API endpoint not found",
+            "path": path
+        })),
+    )
+        .into_response()
+}
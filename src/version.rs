@@ -0,0 +1,30 @@
+//! Semver tag releases
+//!
+//! With `semver_releases = true`, pushing a tag like `v1.4.0` or
+//! `2.0.0-rc.1` runs the project's `release_script` (default: `run_script`)
+//! on the tagged commit, with the parsed version passed to scripts.
+
+use semver::Version;
+
+/// Parse a release tag: a semantic version with an optional `v` prefix
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let version = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+    Version::parse(version).ok()
+}
+
+/// Environment variables describing a release tag, for scripts
+pub fn script_env(tag: &str) -> Vec<(&'static str, String)> {
+    let mut env = vec![("CICD_TAG", tag.to_string())];
+    let Some(version) = parse_tag(tag) else {
+        return env;
+    };
+    env.extend([
+        ("CICD_VERSION", version.to_string()),
+        ("CICD_VERSION_MAJOR", version.major.to_string()),
+        ("CICD_VERSION_MINOR", version.minor.to_string()),
+        ("CICD_VERSION_PATCH", version.patch.to_string()),
+        ("CICD_VERSION_PRERELEASE", version.pre.to_string()),
+        ("CICD_IS_PRERELEASE", (!version.pre.is_empty()).to_string()),
+    ]);
+    env
+}
@@ -1,8 +1,28 @@
 //! Webhook related structures
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ProjectConfig;
+use crate::job::ResumeStep;
+use crate::params;
+use crate::provider::{GenericMapping, Provider};
+use crate::version;
+
+/// `CICD_CHANGED_FILES` is omitted above this size; Linux caps a single
+/// environment string at 128 KiB
+const MAX_CHANGED_FILES_ENV_BYTES: usize = 64 * 1024;
+
+/// Longer commit messages are truncated
+const MAX_COMMIT_MSG_LEN: usize = 500;
+
+/// Object ID providers send for the new state of a deleted ref
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
 /// Data extracted from webhook payload and configuration
 /// This data is passed to scripts as environment variables
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebhookData {
     pub project_name: String,
     pub branch: String,
@@ -13,6 +33,61 @@ pub struct WebhookData {
     pub commit_author_email: Option<String>,
     pub pusher_name: Option<String>,
     pub repository_url: Option<String>,
+    /// Build this commit instead of the branch tip (used by rollbacks)
+    #[serde(default)]
+    pub checkout_sha: Option<String>,
+    /// Release tag being built; `branch` then holds the tag name
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Files added, modified or removed by the pushed commits, if known
+    #[serde(default)]
+    pub changed_files: Option<Vec<String>>,
+    /// Skip the steps before this one (used by resumes)
+    #[serde(default)]
+    pub resume_from: Option<ResumeStep>,
+    /// Pull request being validated; `branch` then holds its head branch
+    #[serde(default)]
+    pub pull_request: Option<PullRequest>,
+    /// GitHub release being deployed; `tag` then holds its tag
+    #[serde(default)]
+    pub published_release: Option<PublishedRelease>,
+    /// Parameters of a manual trigger, passed as `CICD_PARAM_*`
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    /// The branch was deleted; only its teardown script runs
+    #[serde(default)]
+    pub branch_deleted: bool,
+    /// Skip the job after the fetch unless the head commit is a merge commit
+    /// (`only_merge_commits`)
+    #[serde(default)]
+    pub require_merge_commit: bool,
+}
+
+/// A published GitHub release
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedRelease {
+    pub name: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A GitHub pull request, validated by checking out its head commit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: i64,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    /// Branch the pull request would be merged into
+    pub base_branch: String,
+}
+
+impl PullRequest {
+    /// Refspec fetching the head commit, which may come from a fork
+    pub fn refspec(&self) -> String {
+        format!(
+            "+refs/pull/{0}/head:refs/remotes/origin/pull/{0}",
+            self.number
+        )
+    }
 }
 
 impl WebhookData {
@@ -28,6 +103,440 @@ impl WebhookData {
             commit_author_email: None,
             pusher_name: None,
             repository_url: None,
+            checkout_sha: None,
+            tag: None,
+            changed_files: None,
+            resume_from: None,
+            pull_request: None,
+            published_release: None,
+            params: BTreeMap::new(),
+            branch_deleted: false,
+            require_merge_commit: false,
+        }
+    }
+
+    /// Environment variables passed to every script of the job
+    pub fn script_env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        env.insert("CICD_PROJECT_NAME".to_string(), self.project_name.clone());
+        env.insert("CICD_BRANCH".to_string(), self.branch.clone());
+        env.insert("CICD_REPO_PATH".to_string(), self.repo_path.clone());
+
+        let optional = [
+            ("CICD_COMMIT_SHA", &self.commit_sha),
+            ("CICD_COMMIT_MESSAGE", &self.commit_message),
+            ("CICD_COMMIT_AUTHOR_NAME", &self.commit_author_name),
+            ("CICD_COMMIT_AUTHOR_EMAIL", &self.commit_author_email),
+            ("CICD_PUSHER_NAME", &self.pusher_name),
+            ("CICD_REPOSITORY_URL", &self.repository_url),
+        ];
+        for (key, value) in optional {
+            if let Some(value) = value {
+                env.insert(key.to_string(), value.clone());
+            }
+        }
+        if let Some(files) = &self.changed_files {
+            let files = files.join("\n");
+            // Long lists only go to CICD_CHANGED_FILES_FILE (see the pipeline)
+            if files.len() <= MAX_CHANGED_FILES_ENV_BYTES {
+                env.insert("CICD_CHANGED_FILES".to_string(), files);
+            }
+        }
+        if let Some(pr) = &self.pull_request {
+            env.insert("CICD_PR_NUMBER".to_string(), pr.number.to_string());
+            env.insert("CICD_PR_BASE_BRANCH".to_string(), pr.base_branch.clone());
+            if let Some(title) = &pr.title {
+                env.insert("CICD_PR_TITLE".to_string(), title.clone());
+            }
+            if let Some(url) = &pr.url {
+                env.insert("CICD_PR_URL".to_string(), url.clone());
+            }
+        }
+        for (name, value) in &self.params {
+            env.insert(params::env_name(name), value.clone());
+        }
+        if let (Some(tag), Some(release)) = (&self.tag, &self.published_release) {
+            env.insert("CICD_RELEASE_TAG".to_string(), tag.clone());
+            if let Some(name) = &release.name {
+                env.insert("CICD_RELEASE_NAME".to_string(), name.clone());
+            }
+            if let Some(url) = &release.url {
+                env.insert("CICD_RELEASE_URL".to_string(), url.clone());
+            }
         }
+        if let Some(tag) = &self.tag {
+            env.extend(
+                version::script_env(tag)
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value)),
+            );
+        }
+        env
+    }
+}
+
+/// The ref a push updated
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushRef {
+    Branch(String),
+    Tag(String),
+}
+
+impl PushRef {
+    /// Parse a full ref name (`refs/heads/main`, `refs/tags/v1.0.0`); anything
+    /// else is taken as a branch name
+    pub fn parse(full_ref: &str) -> Self {
+        match full_ref.strip_prefix("refs/tags/") {
+            Some(tag) => Self::Tag(tag.to_string()),
+            None => Self::Branch(
+                full_ref
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(full_ref)
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Branch or tag name
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Branch(name) | Self::Tag(name) => name,
+        }
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            Self::Tag(tag) => Some(tag),
+            Self::Branch(_) => None,
+        }
+    }
+}
+
+/// A push, normalized from a provider's webhook payload
+#[derive(Debug, Clone)]
+pub struct PushEvent {
+    pub push_ref: PushRef,
+    /// Repository name projects are matched against (`repository`)
+    pub repo_name: String,
+    /// Owner-qualified repository name (`owner/name`), preferred over
+    /// `repo_name` for projects with `repo_full_name`
+    pub repo_full_name: Option<String>,
+    /// The ref was deleted rather than updated
+    pub deleted: bool,
+    /// The push rewrote the branch's history (force push), where the
+    /// provider reports it
+    pub forced: bool,
+    /// The pushed commit; for tags, the tagged commit where the provider
+    /// reports it
+    pub commit_sha: Option<String>,
+    pub commit_message: Option<String>,
+    pub commit_author_name: Option<String>,
+    pub commit_author_email: Option<String>,
+    pub pusher_name: Option<String>,
+    pub repository_url: Option<String>,
+    pub changed_files: Option<Vec<String>>,
+    /// Set for pull request events, whose head commit is built
+    pub pull_request: Option<PullRequest>,
+    /// Set for release events, which build the release's tag
+    pub published_release: Option<PublishedRelease>,
+    /// Parameters of a manual trigger
+    pub params: BTreeMap<String, String>,
+}
+
+impl PushEvent {
+    /// Normalize a push payload sent by `provider`. None if it lacks the ref
+    /// or the repository name, and for generic payloads, which each project
+    /// reads with its own mapping (see `from_mapping`).
+    pub fn parse(provider: Provider, payload: &Value) -> Option<Self> {
+        match provider {
+            Provider::Github => Self::from_github(payload),
+            Provider::Gitlab => Self::from_gitlab(payload),
+            Provider::AzureDevops => Self::from_azure_devops(payload),
+            Provider::Generic => None,
+        }
+    }
+
+    /// Read a generic payload with a project's field mapping. Without a
+    /// `repository_path`, the push is taken to be for `repository`. None if
+    /// the payload has no branch at `branch_path`.
+    pub fn from_mapping(
+        payload: &Value,
+        mapping: &GenericMapping,
+        repository: &str,
+    ) -> Option<Self> {
+        let field = |path: &Option<String>| match payload.pointer(path.as_deref()?)? {
+            Value::String(s) => Some(s.clone()),
+            value @ (Value::Number(_) | Value::Bool(_)) => Some(value.to_string()),
+            _ => None,
+        };
+        let changed_files = mapping
+            .changed_files_path
+            .as_deref()
+            .and_then(|path| payload.pointer(path)?.as_array())
+            .map(|files| {
+                let files: BTreeSet<String> = files
+                    .iter()
+                    .filter_map(|file| file.as_str().map(String::from))
+                    .collect();
+                files.into_iter().collect()
+            });
+
+        Some(Self {
+            push_ref: PushRef::parse(str_at(payload, mapping.branch_path())?),
+            repo_name: match &mapping.repository_path {
+                Some(_) => field(&mapping.repository_path)?,
+                None => repository.to_string(),
+            },
+            repo_full_name: None,
+            deleted: false,
+            forced: false,
+            commit_sha: field(&mapping.commit_sha_path),
+            commit_message: field(&mapping.commit_message_path)
+                .as_deref()
+                .map(truncate_message),
+            commit_author_name: field(&mapping.commit_author_name_path),
+            commit_author_email: field(&mapping.commit_author_email_path),
+            pusher_name: field(&mapping.pusher_name_path),
+            repository_url: field(&mapping.repository_url_path),
+            changed_files,
+            pull_request: None,
+            published_release: None,
+            params: BTreeMap::new(),
+        })
+    }
+
+    /// GitHub `pull_request` event, as a push of the head branch. None
+    /// unless a pull request was opened or got new commits.
+    pub fn from_github_pull_request(payload: &Value) -> Option<Self> {
+        let action = str_at(payload, "/action")?;
+        if !matches!(action, "opened" | "synchronize") {
+            return None;
+        }
+        let pr = payload.get("pull_request")?;
+        Some(Self {
+            push_ref: PushRef::Branch(str_at(pr, "/head/ref")?.to_string()),
+            repo_name: str_at(payload, "/repository/name")?.to_string(),
+            repo_full_name: string_at(payload, "/repository/full_name"),
+            deleted: false,
+            forced: false,
+            commit_sha: string_at(pr, "/head/sha"),
+            commit_message: str_at(pr, "/title").map(truncate_message),
+            commit_author_name: string_at(pr, "/user/login"),
+            commit_author_email: None,
+            pusher_name: string_at(payload, "/sender/login"),
+            repository_url: string_at(payload, "/repository/html_url"),
+            changed_files: None,
+            pull_request: Some(PullRequest {
+                number: pr.get("number")?.as_i64()?,
+                title: string_at(pr, "/title"),
+                url: string_at(pr, "/html_url"),
+                base_branch: str_at(pr, "/base/ref")?.to_string(),
+            }),
+            published_release: None,
+            params: BTreeMap::new(),
+        })
+    }
+
+    /// GitHub `release` event, as a push of the release's tag. None unless
+    /// the release was published.
+    pub fn from_github_release(payload: &Value) -> Option<Self> {
+        if str_at(payload, "/action")? != "published" {
+            return None;
+        }
+        let release = payload.get("release")?;
+        Some(Self {
+            push_ref: PushRef::Tag(str_at(release, "/tag_name")?.to_string()),
+            repo_name: str_at(payload, "/repository/name")?.to_string(),
+            repo_full_name: string_at(payload, "/repository/full_name"),
+            deleted: false,
+            forced: false,
+            commit_sha: None,
+            commit_message: str_at(release, "/name").map(truncate_message),
+            commit_author_name: string_at(release, "/author/login"),
+            commit_author_email: None,
+            pusher_name: string_at(payload, "/sender/login"),
+            repository_url: string_at(payload, "/repository/html_url"),
+            changed_files: None,
+            pull_request: None,
+            published_release: Some(PublishedRelease {
+                name: string_at(release, "/name"),
+                url: string_at(release, "/html_url"),
+            }),
+            params: BTreeMap::new(),
+        })
+    }
+
+    /// GitHub `push` event
+    fn from_github(payload: &Value) -> Option<Self> {
+        let push_ref = PushRef::parse(str_at(payload, "/ref")?);
+        // For annotated tags `after` is the tag object, so prefer the tagged
+        // commit from head_commit
+        let tagged_commit = str_at(payload, "/head_commit/id").filter(|_| push_ref.tag().is_some());
+        Some(Self {
+            repo_name: str_at(payload, "/repository/name")?.to_string(),
+            repo_full_name: string_at(payload, "/repository/full_name"),
+            deleted: payload.get("deleted").and_then(Value::as_bool) == Some(true),
+            forced: payload.get("forced").and_then(Value::as_bool) == Some(true),
+            commit_sha: tagged_commit
+                .or(str_at(payload, "/after"))
+                .map(String::from),
+            commit_message: str_at(payload, "/head_commit/message").map(truncate_message),
+            commit_author_name: string_at(payload, "/head_commit/author/name"),
+            commit_author_email: string_at(payload, "/head_commit/author/email"),
+            pusher_name: string_at(payload, "/pusher/name"),
+            repository_url: string_at(payload, "/repository/html_url"),
+            changed_files: changed_files(payload),
+            pull_request: None,
+            published_release: None,
+            params: BTreeMap::new(),
+            push_ref,
+        })
+    }
+
+    /// GitLab `Push Hook` or `Tag Push Hook`. The repository name is the
+    /// project's path (`my-app` in `group/my-app`).
+    fn from_gitlab(payload: &Value) -> Option<Self> {
+        let after = str_at(payload, "/after").unwrap_or(NULL_SHA);
+        // `checkout_sha` is the pushed commit, for annotated tags the tagged
+        // one rather than the tag object in `after`; it's null for deletions
+        let commit_sha =
+            str_at(payload, "/checkout_sha").or(Some(after).filter(|&a| a != NULL_SHA));
+        // Commits are listed oldest first; tag pushes list none
+        let commits = payload.get("commits").and_then(Value::as_array);
+        let head_commit = commits.and_then(|commits| {
+            commits
+                .iter()
+                .find(|c| c.get("id").and_then(Value::as_str) == commit_sha)
+                .or(commits.last())
+        });
+        let repo_name = str_at(payload, "/project/path_with_namespace")
+            .and_then(|path| path.rsplit('/').next())
+            .or_else(|| str_at(payload, "/project/name"))?;
+
+        Some(Self {
+            push_ref: PushRef::parse(str_at(payload, "/ref")?),
+            repo_name: repo_name.to_string(),
+            repo_full_name: string_at(payload, "/project/path_with_namespace"),
+            deleted: after == NULL_SHA,
+            forced: false,
+            commit_sha: commit_sha.map(String::from),
+            commit_message: head_commit
+                .and_then(|c| str_at(c, "/message"))
+                .or_else(|| str_at(payload, "/message"))
+                .map(truncate_message),
+            commit_author_name: head_commit.and_then(|c| string_at(c, "/author/name")),
+            commit_author_email: head_commit.and_then(|c| string_at(c, "/author/email")),
+            pusher_name: string_at(payload, "/user_username")
+                .or_else(|| string_at(payload, "/user_name")),
+            repository_url: string_at(payload, "/project/web_url"),
+            changed_files: changed_files(payload),
+            pull_request: None,
+            published_release: None,
+            params: BTreeMap::new(),
+        })
+    }
+
+    /// Azure DevOps `git.push` service hook. Only the first updated ref is
+    /// used; the payload lists no changed files.
+    fn from_azure_devops(payload: &Value) -> Option<Self> {
+        let resource = payload.get("resource")?;
+        let ref_update = resource.pointer("/refUpdates/0")?;
+        let new_object = str_at(ref_update, "/newObjectId").unwrap_or(NULL_SHA);
+        let commit_sha = Some(new_object).filter(|&sha| sha != NULL_SHA);
+        // Commits are listed newest first
+        let commits = resource.get("commits").and_then(Value::as_array);
+        let head_commit = commits.and_then(|commits| {
+            commits
+                .iter()
+                .find(|c| c.get("commitId").and_then(Value::as_str) == commit_sha)
+                .or(commits.first())
+        });
+
+        let repo_name = str_at(resource, "/repository/name")?;
+
+        Some(Self {
+            push_ref: PushRef::parse(str_at(ref_update, "/name")?),
+            repo_name: repo_name.to_string(),
+            repo_full_name: str_at(resource, "/repository/project/name")
+                .map(|project| format!("{}/{}", project, repo_name)),
+            deleted: commit_sha.is_none(),
+            forced: false,
+            commit_sha: commit_sha.map(String::from),
+            commit_message: head_commit
+                .and_then(|c| str_at(c, "/comment"))
+                .map(truncate_message),
+            commit_author_name: head_commit.and_then(|c| string_at(c, "/author/name")),
+            commit_author_email: head_commit.and_then(|c| string_at(c, "/author/email")),
+            pusher_name: string_at(resource, "/pushedBy/displayName"),
+            repository_url: string_at(resource, "/repository/remoteUrl"),
+            changed_files: None,
+            pull_request: None,
+            published_release: None,
+            params: BTreeMap::new(),
+        })
+    }
+
+    /// Data for a job of `project` run by this push
+    pub fn webhook_data(&self, project: &ProjectConfig) -> WebhookData {
+        WebhookData {
+            project_name: project.name.clone(),
+            branch: self.push_ref.name().to_string(),
+            repo_path: project.repo_path.clone(),
+            commit_sha: self.commit_sha.clone(),
+            commit_message: self.commit_message.clone(),
+            commit_author_name: self.commit_author_name.clone(),
+            commit_author_email: self.commit_author_email.clone(),
+            pusher_name: self.pusher_name.clone(),
+            repository_url: self.repository_url.clone(),
+            // Pull requests build their head commit, not the branch tip
+            checkout_sha: self.pull_request.as_ref().and(self.commit_sha.clone()),
+            tag: self.push_ref.tag().map(String::from),
+            changed_files: self.changed_files.clone(),
+            resume_from: None,
+            pull_request: self.pull_request.clone(),
+            published_release: self.published_release.clone(),
+            params: self.params.clone(),
+            branch_deleted: self.deleted && matches!(self.push_ref, PushRef::Branch(_)),
+            // Push payloads don't list parents; the job checks them once it
+            // has fetched the commit
+            require_merge_commit: project.only_merge_commits()
+                && !self.deleted
+                && self.pull_request.is_none()
+                && matches!(self.push_ref, PushRef::Branch(_)),
+        }
+    }
+}
+
+fn str_at<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(Value::as_str)
+}
+
+fn string_at(value: &Value, pointer: &str) -> Option<String> {
+    str_at(value, pointer).map(String::from)
+}
+
+fn truncate_message(message: &str) -> String {
+    if message.len() > MAX_COMMIT_MSG_LEN {
+        format!("{}... (truncated)", &message[..MAX_COMMIT_MSG_LEN])
+    } else {
+        message.to_string()
+    }
+}
+
+/// Files added, modified or removed by the commits of a push payload, sorted
+/// and deduplicated. None if the payload carries no commits (e.g. a tag or a
+/// push of commits already on another branch), so the changes are unknown.
+fn changed_files(payload: &Value) -> Option<Vec<String>> {
+    let commits = payload.get("commits")?.as_array()?;
+    if commits.is_empty() {
+        return None;
     }
+    let files: BTreeSet<String> = commits
+        .iter()
+        .flat_map(|commit| ["added", "modified", "removed"].map(|key| commit.get(key)))
+        .filter_map(|list| list?.as_array())
+        .flatten()
+        .filter_map(|file| file.as_str().map(String::from))
+        .collect();
+    Some(files.into_iter().collect())
 }
@@ -0,0 +1,263 @@
+//! Isolated per-job workspaces (`[project.workspace]`)
+//!
+//! Normally a job resets `repo_path` to the pushed commit and runs its
+//! scripts there, so a service running from that checkout sees its files
+//! change mid-deploy. With a workspace, `repo_path` is only fetched: each job
+//! checks out its commit into a fresh directory of its own,
+//! `<[server] workspace_dir>/<project>/<job id>` and runs its scripts there.
+//! The workspace is a local clone of `repo_path`, or a git worktree of it,
+//! which is faster and shares its objects.
+//!
+//! When a job ends, the project's workspaces beyond the newest `keep` are
+//! removed, so failed builds can be inspected. A background cleaner applies
+//! the same rule to every project, e.g. after `keep` was lowered, and removes
+//! the workspaces of projects that no longer use them.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::SharedState;
+use crate::error::{CicdError, Result};
+use crate::git;
+
+/// Directory workspaces are created in, relative to the working directory,
+/// unless `[server] workspace_dir` is set
+pub const DEFAULT_WORKSPACE_DIR: &str = "cicd_workspaces";
+
+/// How often the background cleaner runs
+const CLEAN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How a workspace is created from `repo_path`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceMode {
+    /// A repository of its own, with the remote branches and tags of
+    /// `repo_path` (default)
+    #[default]
+    Clone,
+    /// A git worktree of `repo_path`, sharing its objects and refs
+    Worktree,
+}
+
+/// `[project.workspace]` settings
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WorkspaceConfig {
+    /// `clone` (default) or `worktree`
+    pub mode: Option<WorkspaceMode>,
+    /// Number of finished jobs whose workspaces are kept (default: 0)
+    pub keep: Option<usize>,
+}
+
+impl WorkspaceConfig {
+    pub fn mode(&self) -> WorkspaceMode {
+        self.mode.unwrap_or_default()
+    }
+
+    pub fn keep(&self) -> usize {
+        self.keep.unwrap_or(0)
+    }
+}
+
+/// The workspace of one job
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub path: PathBuf,
+    pub mode: WorkspaceMode,
+    repo_path: String,
+    /// Names the worktree in `repo_path`
+    job_id: String,
+}
+
+impl Workspace {
+    pub fn new(
+        config: &WorkspaceConfig,
+        workspace_dir: &str,
+        project: &str,
+        repo_path: &str,
+        job_id: &str,
+    ) -> Self {
+        // Absolute, as scripts get the path and run in it
+        let path = job_path(workspace_dir, project, job_id);
+        Self {
+            path: std::path::absolute(&path).unwrap_or(path),
+            mode: config.mode(),
+            repo_path: repo_path.to_string(),
+            job_id: job_id.to_string(),
+        }
+    }
+
+    /// The git command equivalent to `create`, for the step log
+    pub fn command(&self, spec: &str) -> String {
+        match self.mode {
+            WorkspaceMode::Clone => format!(
+                "git clone {} {} && git checkout --detach {}",
+                self.repo_path,
+                self.path.display(),
+                spec
+            ),
+            WorkspaceMode::Worktree => {
+                format!("git worktree add --detach {} {}", self.path.display(), spec)
+            }
+        }
+    }
+
+    /// Create the workspace with `spec` (e.g. `origin/main`) checked out;
+    /// a leftover workspace of the same job is replaced
+    pub fn create(&self, spec: &str) -> Result<String> {
+        self.remove()?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| workspace_error("create", parent, e))?;
+        }
+        let output = match self.mode {
+            WorkspaceMode::Clone => git::clone_local(&self.repo_path, &self.path, spec)?,
+            WorkspaceMode::Worktree => {
+                git::add_worktree(&self.repo_path, &self.job_id, &self.path, spec)?
+            }
+        };
+        Ok(format!(
+            "Created workspace {}\n{}",
+            self.path.display(),
+            output
+        ))
+    }
+
+    /// Delete the workspace; a worktree is also unregistered from `repo_path`
+    pub fn remove(&self) -> Result<()> {
+        remove(&self.path, Some(&self.repo_path), &self.job_id).map(|_| ())
+    }
+}
+
+/// Directory of the workspace of job `job_id` of `project`, absolute so
+/// scripts can be given it
+pub fn job_path(workspace_dir: &str, project: &str, job_id: &str) -> PathBuf {
+    let path = Path::new(workspace_dir).join(project).join(job_id);
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// Delete a job's workspace; returns false if there was none. A worktree is
+/// unregistered from `repo_path`, if given.
+pub fn remove(path: &Path, repo_path: Option<&str>, job_id: &str) -> Result<bool> {
+    let existed = path.is_dir();
+    if let Some(repo_path) = repo_path
+        && path.join(".git").is_file()
+    {
+        git::remove_worktree(repo_path, job_id)?;
+    }
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(workspace_error("remove", path, e)),
+        _ => Ok(existed),
+    }
+}
+
+/// Job ids of the workspaces of `project`, newest first. Only directories
+/// named like job ids are listed, so a misconfigured `workspace_dir` isn't
+/// emptied.
+pub fn list(workspace_dir: &str, project: &str) -> Result<Vec<String>> {
+    let dir = Path::new(workspace_dir).join(project);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(workspace_error("list", &dir, e)),
+    };
+    let mut job_ids = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| workspace_error("list", &dir, e))?;
+        if let Some(name) = entry.file_name().to_str()
+            && Uuid::parse_str(name).is_ok()
+            && entry.path().is_dir()
+        {
+            job_ids.push(name.to_string());
+        }
+    }
+    // Job ids are UUIDv7s, which sort by creation time
+    job_ids.sort_by(|a, b| b.cmp(a));
+    Ok(job_ids)
+}
+
+/// Remove the workspaces of `project`'s finished jobs beyond the newest
+/// `keep`; `is_running` tells which jobs are still using theirs. Returns the
+/// job ids of the removed workspaces.
+pub fn clean(
+    workspace_dir: &str,
+    project: &str,
+    repo_path: Option<&str>,
+    keep: usize,
+    is_running: impl Fn(&str) -> bool,
+) -> Result<Vec<String>> {
+    let mut removed = Vec::new();
+    let finished = list(workspace_dir, project)?
+        .into_iter()
+        .filter(|job_id| !is_running(job_id));
+    for job_id in finished.skip(keep) {
+        let path = job_path(workspace_dir, project, &job_id);
+        match remove(&path, repo_path, &job_id) {
+            Ok(_) => removed.push(job_id),
+            Err(e) => error!("Failed to remove workspace {}: {}", path.display(), e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Spawn the background task removing the workspaces `keep` doesn't cover,
+/// for every project in `[server] workspace_dir`. The config is read on
+/// every run, so it follows reloads.
+pub fn spawn_cleaner(state: SharedState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEAN_INTERVAL);
+        loop {
+            interval.tick().await;
+            let state = state.clone();
+            let sweep = tokio::task::spawn_blocking(move || clean_all(&state));
+            if let Err(e) = sweep.await {
+                error!("Failed to clean workspaces: {}", e);
+            }
+        }
+    });
+}
+
+fn clean_all(state: &SharedState) {
+    let config = state.config.load();
+    let workspace_dir = config.server.workspace_dir();
+    let entries = match fs::read_dir(workspace_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            error!("Failed to list workspaces in {}: {}", workspace_dir, e);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        // Projects no longer using workspaces keep none
+        let project = config.project.iter().find(|p| p.name == name);
+        let keep = project
+            .and_then(|p| p.workspace.as_ref())
+            .map_or(0, WorkspaceConfig::keep);
+        let repo_path = project.map(|p| p.repo_path.as_str());
+        match clean(workspace_dir, &name, repo_path, keep, |job_id| {
+            state.dispatcher.is_running(job_id)
+        }) {
+            Ok(removed) if removed.is_empty() => {}
+            Ok(removed) => info!(
+                "Removed {} workspace(s) of project '{}'",
+                removed.len(),
+                name
+            ),
+            Err(e) => error!("Failed to clean workspaces of project '{}': {}", name, e),
+        }
+    }
+}
+
+fn workspace_error(operation: &str, path: &Path, e: io::Error) -> CicdError {
+    CicdError::IoError(io::Error::new(
+        e.kind(),
+        format!("Failed to {} {}: {}", operation, path.display(), e),
+    ))
+}
@@ -1,13 +1,18 @@
 import type {
+	CancelResponse,
 	ConfigResponse,
 	Job,
 	JobLog,
 	JobsResponse,
+	MaintenanceStatus,
 	ProjectsResponse,
+	ResumeResponse,
+	RetryResponse,
 	StatsResponse
 } from './types';
+import { base } from '$app/paths';
 
-const API_BASE = '/api';
+const API_BASE = `${base}/api`;
 
 async function fetchJson<T>(url: string, options?: RequestInit): Promise<T> {
 	const res = await fetch(`${API_BASE}${url}`, options);
@@ -50,6 +55,22 @@ export const api = {
 		return fetchJson<Job>(`/jobs/${id}`);
 	},
 
+	async pinJob(id: string, pinned: boolean): Promise<Job> {
+		return fetchJson<Job>(`/jobs/${id}/pin`, { method: pinned ? 'POST' : 'DELETE' });
+	},
+
+	async resumeJob(id: string): Promise<ResumeResponse> {
+		return fetchJson<ResumeResponse>(`/jobs/${id}/resume`, { method: 'POST' });
+	},
+
+	async retryJob(id: string): Promise<RetryResponse> {
+		return fetchJson<RetryResponse>(`/jobs/${id}/retry`, { method: 'POST' });
+	},
+
+	async cancelJob(id: string): Promise<CancelResponse> {
+		return fetchJson<CancelResponse>(`/jobs/${id}/cancel`, { method: 'POST' });
+	},
+
 	async getJobLogs(id: string): Promise<{ job_id: string; logs: JobLog[]; count: number }> {
 		return fetchJson<{ job_id: string; logs: JobLog[]; count: number }>(`/jobs/${id}/logs`);
 	},
@@ -58,6 +79,12 @@ export const api = {
 		return fetchJson<ProjectsResponse>('/projects');
 	},
 
+	async setMaintenance(name: string, enabled: boolean): Promise<MaintenanceStatus> {
+		return fetchJson<MaintenanceStatus>(`/projects/${encodeURIComponent(name)}/maintenance`, {
+			method: enabled ? 'POST' : 'DELETE'
+		});
+	},
+
 	async getStats(): Promise<StatsResponse> {
 		return fetchJson<StatsResponse>('/stats');
 	},
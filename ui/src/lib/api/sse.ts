@@ -1,4 +1,5 @@
 import { writable } from 'svelte/store';
+import { base } from '$app/paths';
 import type { JobEvent, LogChunkEvent } from './types';
 
 function createJobStream() {
@@ -44,7 +45,7 @@ function createJobStream() {
 		// Clean up any partial connection
 		if (eventSource) disconnect();
 
-		eventSource = new EventSource('/api/stream/jobs');
+		eventSource = new EventSource(`${base}/api/stream/jobs`);
 
 		eventSource.onopen = () => {
 			setConnected(true);
@@ -65,6 +66,10 @@ function createJobStream() {
 		eventSource.addEventListener('running', handleEvent);
 		eventSource.addEventListener('success', handleEvent);
 		eventSource.addEventListener('failed', handleEvent);
+		eventSource.addEventListener('skipped', handleEvent);
+		eventSource.addEventListener('cancelled', handleEvent);
+		eventSource.addEventListener('timed_out', handleEvent);
+		eventSource.addEventListener('paused', handleEvent);
 
 		eventSource.onerror = () => {
 			// On error, close and attempt manual reconnect with backoff
@@ -128,7 +133,7 @@ function createLogStream() {
 		// Clean up any partial connection
 		if (eventSource) disconnect();
 
-		eventSource = new EventSource('/api/stream/logs');
+		eventSource = new EventSource(`${base}/api/stream/logs`);
 
 		eventSource.onopen = () => {
 			setConnected(true);
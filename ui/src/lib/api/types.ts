@@ -1,4 +1,4 @@
-export type JobStatus = 'queued' | 'running' | 'success' | 'failed';
+export type JobStatus = 'queued' | 'running' | 'success' | 'failed' | 'skipped' | 'cancelled' | 'timed_out';
 
 export interface Job {
 	id: string;
@@ -14,6 +14,55 @@ export interface Job {
 	output_truncated: boolean;
 	error?: string;
 	dry_run: boolean;
+	pinned: boolean;
+	/** For rollback jobs: the successful job whose commit is restored */
+	rollback_of?: string;
+	/** For jobs triggered by a tag push: the tag (also in `branch`) */
+	tag?: string;
+	/** For resumed jobs: the failed job whose pipeline is continued */
+	resumed_from?: string;
+	/** For retried jobs: the job whose pipeline is run again */
+	retried_from?: string;
+	/** For jobs validating a pull request: its number */
+	pull_request?: number;
+	/** The webhook got past the project's exhausted rate limit with the bypass token */
+	rate_limit_bypassed: boolean;
+	/** Environment passed to scripts (secrets masked); only on single-job responses */
+	env?: Record<string, string>;
+	/** Main-script output changes since the previous run; only on single-job responses */
+	output_diff?: OutputDiff;
+	/** `git log --oneline` of commits deployed since the last successful deploy; only on single-job responses */
+	changelog?: string;
+}
+
+export interface ResumeResponse {
+	job_id: string;
+	project: string;
+	branch: string;
+	commit_sha?: string;
+	resumed_from: string;
+	resume_step: 'pre_script' | 'main_script';
+}
+
+export interface RetryResponse {
+	job_id: string;
+	project: string;
+	branch: string;
+	commit_sha?: string;
+	retried_from: string;
+}
+
+export interface CancelResponse {
+	job_id: string;
+	/** `cancelled` for a queued job, `cancelling` while a running job is being stopped */
+	status: 'cancelled' | 'cancelling';
+}
+
+export interface OutputDiff {
+	previous_job_id: string;
+	added_lines: number;
+	removed_lines: number;
+	new_error_lines: string[];
 }
 
 export interface JobLog {
@@ -26,6 +75,13 @@ export interface JobLog {
 	exit_code?: number;
 	output?: string;
 	status: string;
+	signal?: string;
+	/** What the step's script wrote to stdout (`output` has both streams) */
+	stdout?: string;
+	/** What the step's script wrote to stderr */
+	stderr?: string;
+	/** The step's output was cut to stay within `max_step_output_bytes` */
+	output_truncated?: boolean;
 }
 
 export interface JobsResponse {
@@ -49,6 +105,9 @@ export interface JobStats {
 	running: number;
 	success: number;
 	failed: number;
+	skipped: number;
+	cancelled: number;
+	timed_out: number;
 	success_rate: number;
 }
 
@@ -64,6 +123,17 @@ export interface ProjectSummary {
 	last_job_at?: string;
 	success_rate: number;
 	total_jobs: number;
+	maintenance: boolean;
+	maintenance_since?: string;
+	maintenance_reason?: string;
+}
+
+export interface MaintenanceStatus {
+	project: string;
+	maintenance: boolean;
+	since?: string;
+	reason?: string;
+	held_jobs: number;
 }
 
 export interface ProjectsResponse {
@@ -88,5 +158,6 @@ export interface LogChunkEvent {
 	job_id: string;
 	step_type: string;
 	chunk: string;
+	channel: 'stdout' | 'stderr';
 	timestamp: string;
 }